@@ -0,0 +1,141 @@
+use clap::Parser;
+
+use crate::modes::Mode;
+
+/// Command-line arguments
+#[derive(Debug, Default, Parser)]
+#[command(version, about = "AI-assisted commit messages and repository analysis")]
+pub struct Args {
+    /// Paths to the git repositories to open. With several, or a glob in the last part of
+    /// a path such as 'repos/*', the chosen mode runs in each repository in turn.
+    pub repo_paths: Vec<String>,
+
+    /// Never write to the repository; show what would be committed instead
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Skip files matching this gitignore-style glob during file analysis (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Largest diff in bytes sent to the model; bigger diffs keep only the most-changed files
+    #[arg(long, value_name = "BYTES")]
+    pub max_diff_bytes: Option<usize>,
+
+    /// Unchanged lines shown around each change in the diffs sent to the model (default 3)
+    #[arg(long, value_name = "LINES")]
+    pub diff_context_lines: Option<u32>,
+
+    /// Ask before sending requests estimated above this many tokens
+    #[arg(long, value_name = "TOKENS")]
+    pub token_threshold: Option<usize>,
+
+    /// Generate this many commit messages at once and pick one
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub candidates: Option<u16>,
+
+    /// Always rescan history instead of using cached contributor statistics, and ask the
+    /// model again instead of using cached responses
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Remove the cached AI responses of the repositories and exit
+    #[arg(long, conflicts_with_all = ["headless", "patch"])]
+    pub clear_cache: bool,
+
+    /// Send diffs without checking them for likely secrets first
+    #[arg(long)]
+    pub no_secret_scan: bool,
+
+    /// Read the changes from this patch file instead of the repository (`-` for stdin).
+    /// Only commit messages, file analysis, review and explaining a failure are available, and
+    /// nothing is committed.
+    #[arg(long, value_name = "PATH")]
+    pub patch: Option<String>,
+
+    /// Don't animate spinners or decorate section headers, e.g. for logs and CI
+    #[arg(long)]
+    pub quiet: bool,
+
+    /// Log every AI request and raw response to stderr, with API keys redacted.
+    /// `RUST_LOG=debug` does the same.
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Don't offer the setup wizard on first run, when there is no config file yet
+    #[arg(long)]
+    pub no_setup: bool,
+
+    /// Run without menus or prompts and print plain output, for scripts and CI
+    #[arg(long, requires = "mode")]
+    pub headless: bool,
+
+    /// Mode to run with --headless
+    #[arg(long, value_enum)]
+    pub mode: Option<Mode>,
+
+    /// Only analyze the changes to this file in file analysis, without asking which files
+    #[arg(long, value_name = "PATH", conflicts_with = "patch")]
+    pub file: Option<String>,
+
+    /// Error log for the failure mode, e.g. of a failed build (`-` for stdin); without it
+    /// the log is pasted into $EDITOR
+    #[arg(long, value_name = "PATH", conflicts_with = "patch")]
+    pub log: Option<String>,
+
+    /// Commit or range for the explain mode, e.g. `HEAD~2` or `main..feature`, or the commit to reword
+    #[arg(long, value_name = "REVISION")]
+    pub rev: Option<String>,
+
+    /// Generate the commit message or analyze the files for everything the current branch
+    /// changed since it left BRANCH, e.g. for a pull request description. Without BRANCH,
+    /// `base_branch` from the config or the repository's default branch
+    #[arg(long, value_name = "BRANCH", num_args = 0..=1)]
+    pub base: Option<Option<String>>,
+
+    /// Only show review findings of at least this severity
+    #[arg(long, value_enum)]
+    pub severity: Option<crate::Severity>,
+
+    /// Only show analyzed files the model rates at least this risky
+    #[arg(long, value_enum)]
+    pub min_risk: Option<crate::RiskLevel>,
+
+    /// With `--mode hook`, replace an existing prepare-commit-msg hook; with `--mode report`,
+    /// an existing contributors.md
+    #[arg(long, requires = "headless")]
+    pub force: bool,
+
+    /// With `--mode hook`, remove the hook instead of installing it
+    #[arg(long, requires = "headless", conflicts_with = "force")]
+    pub uninstall: bool,
+
+    /// Output format for --headless runs; interactive sessions mix results with menus and
+    /// status lines on stdout, so they always print text
+    #[arg(long, value_enum, requires = "headless")]
+    pub format: Option<OutputFormat>,
+}
+
+/// How headless runs print their results
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Plain text and markdown
+    #[default]
+    Text,
+    /// One JSON document on stdout, for other programs to consume
+    Json,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn json_output_is_only_for_headless_runs() {
+        let args = Args::try_parse_from(["noah", "--headless", "--mode", "commit", "--format", "json"]).unwrap();
+        assert_eq!(args.format, Some(OutputFormat::Json));
+        let error = Args::try_parse_from(["noah", "--format", "json"]).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+    }
+}
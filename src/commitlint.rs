@@ -0,0 +1,134 @@
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+/// Files commitlint reads its configuration from, in the order it looks for them
+const CONFIG_FILES: [&str; 12] = [
+    ".commitlintrc",
+    ".commitlintrc.json",
+    ".commitlintrc.yaml",
+    ".commitlintrc.yml",
+    ".commitlintrc.js",
+    ".commitlintrc.cjs",
+    ".commitlintrc.mjs",
+    ".commitlintrc.ts",
+    "commitlint.config.js",
+    "commitlint.config.cjs",
+    "commitlint.config.mjs",
+    "commitlint.config.ts",
+];
+
+/// The types `@commitlint/config-conventional` allows
+const CONVENTIONAL_TYPES: [&str; 11] = ["build", "chore", "ci", "docs", "feat", "fix", "perf", "refactor", "revert", "style", "test"];
+
+/// The header length `@commitlint/config-conventional` allows
+const CONVENTIONAL_HEADER_MAX_LENGTH: usize = 100;
+
+/// The rules of a commitlint config that noah follows. Those the config doesn't set, or
+/// turns off, are `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommitlintRules {
+    /// `type-enum`: the commit types allowed
+    pub types: Option<Vec<String>>,
+    /// `header-max-length`: the longest subject line allowed
+    pub header_max_length: Option<usize>,
+}
+
+impl CommitlintRules {
+    /// The rules of the first commitlint config in `repo_root`, or of the `commitlint` key
+    /// in its `package.json`, with the file they came from. `None` without a config.
+    pub fn find(repo_root: &Path) -> Option<(PathBuf, CommitlintRules)> {
+        for name in CONFIG_FILES {
+            let path = repo_root.join(name);
+            if let Ok(text) = std::fs::read_to_string(&path) {
+                return Some((path, Self::parse(&text)));
+            }
+        }
+        let path = repo_root.join("package.json");
+        let package: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).ok()?).ok()?;
+        let config = package.get("commitlint")?;
+        Some((path, Self::parse(&config.to_string())))
+    }
+
+    /// Picks `type-enum` and `header-max-length` out of a config in JSON, YAML or
+    /// JavaScript. Nothing is evaluated: the rules have to be written out as
+    /// `[level, "always", value]` arrays, and anything else is left at what
+    /// `@commitlint/config-conventional` sets, if the config extends it.
+    pub fn parse(text: &str) -> CommitlintRules {
+        let mut rules = if text.contains("config-conventional") {
+            CommitlintRules {
+                types: Some(CONVENTIONAL_TYPES.iter().map(|t| t.to_string()).collect()),
+                header_max_length: Some(CONVENTIONAL_HEADER_MAX_LENGTH),
+            }
+        } else {
+            CommitlintRules::default()
+        };
+        let rules_section = text.find("rules").map_or(text, |start| &text[start..]);
+
+        if let Some((enabled, values)) = rule(rules_section, "type-enum", r"\[([^\]]*)\]") {
+            let types: Vec<String> = values
+                .split(',')
+                .map(|value| value.trim().trim_matches(|c| c == '"' || c == '\'' || c == '`').trim())
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+                .collect();
+            rules.types = (enabled && !types.is_empty()).then_some(types);
+        }
+        if let Some((enabled, length)) = rule(rules_section, "header-max-length", r"(\d+)") {
+            rules.header_max_length = length.parse().ok().filter(|_| enabled);
+        }
+        rules
+    }
+}
+
+/// The value of `name` matched by `value` when the rule is written out as
+/// `[level, "always", value]`, and whether it is turned on (a level other than 0)
+fn rule<'a>(text: &'a str, name: &str, value: &str) -> Option<(bool, &'a str)> {
+    let pattern = format!(r#"["']?{}["']?\s*:\s*\[\s*([0-2])\s*,\s*["']?always["']?\s*,\s*{}"#, regex::escape(name), value);
+    let captures = Regex::new(&pattern).ok()?.captures(text)?;
+    Some((&captures[1] != "0", captures.get(2)?.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rule_reads_written_out_rules() {
+        let json = r#"{"rules": {"header-max-length": [2, "always", 72]}}"#;
+        assert_eq!(rule(json, "header-max-length", r"(\d+)"), Some((true, "72")));
+
+        let js = "module.exports = { rules: { 'type-enum': [1, 'always', ['feat', 'fix']] } };";
+        assert_eq!(rule(js, "type-enum", r"\[([^\]]*)\]"), Some((true, "'feat', 'fix'")));
+    }
+
+    #[test]
+    fn rule_reports_turned_off_rules() {
+        let yaml = "rules:\n  header-max-length: [0, always, 72]\n";
+        assert_eq!(rule(yaml, "header-max-length", r"(\d+)"), Some((false, "72")));
+    }
+
+    #[test]
+    fn rule_skips_never_and_computed_rules() {
+        assert_eq!(rule(r#"{"type-enum": [2, "never", ["wip"]]}"#, "type-enum", r"\[([^\]]*)\]"), None);
+        assert_eq!(rule("'header-max-length': [2, 'always', max]", "header-max-length", r"(\d+)"), None);
+    }
+
+    #[test]
+    fn parse_overrides_the_conventional_defaults() {
+        let text = r#"{"extends": ["@commitlint/config-conventional"], "rules": {"type-enum": [2, "always", ["feat", "fix", "ops"]]}}"#;
+        assert_eq!(
+            CommitlintRules::parse(text),
+            CommitlintRules {
+                types: Some(vec!["feat".to_string(), "fix".to_string(), "ops".to_string()]),
+                header_max_length: Some(CONVENTIONAL_HEADER_MAX_LENGTH),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_drops_turned_off_rules() {
+        let text = r#"{"extends": ["@commitlint/config-conventional"], "rules": {"header-max-length": [0, "always", 72]}}"#;
+        assert_eq!(CommitlintRules::parse(text).header_max_length, None);
+    }
+}
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::modes::{DefaultMode, Mode};
+use crate::providers::{self, GenerationOptions, LocalEndpoint};
+use crate::ui::{ColorTheme, SpinnerStyle};
+use crate::{CommitType, MessageStyle, NoahError};
+
+/// Name of the repo-local config file, looked up in the repository root
+pub const REPO_CONFIG_FILE: &str = "noah.toml";
+
+/// Repo-local file with a system prompt prefix, used when no config file sets one
+pub const PROMPT_PREFIX_FILE: &str = ".noah-prompt.md";
+
+/// Settings read from `noah.toml`. Every field is optional so that a layer only
+/// overrides what it actually sets.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ConfigFile {
+    /// Provider to use without asking, e.g. "Claude" or "openai"
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    /// An OpenAI-compatible server such as Ollama or LM Studio, offered as the "Local" provider
+    pub local: Option<LocalEndpoint>,
+    pub exclude: Option<Vec<String>>,
+    /// Commit types offered when editing the commit type; replaces the built-in list
+    pub commit_types: Option<Vec<CommitTypeSetting>>,
+    /// "plain", "gitmoji" or "conventional": whether commit types carry their emoji
+    pub message_style: Option<MessageStyle>,
+    /// Column generated commit bodies are wrapped at; 0 leaves them as they are
+    pub body_wrap: Option<usize>,
+    /// Wraps generated summaries, e.g. "{ticket}: {message}"
+    pub commit_template: Option<String>,
+    /// Regex finding `{ticket}` in the branch name; the first capture group if it has one
+    pub ticket_pattern: Option<String>,
+    /// People to offer as `Co-authored-by:` trailers, as `"Name <email>"`
+    pub co_authors: Option<Vec<String>>,
+    pub spinner: Option<SpinnerStyle>,
+    pub theme: Option<ColorTheme>,
+    /// No spinners or decorated headers, for logs and CI
+    pub quiet: Option<bool>,
+    /// Mode an interactive session starts with instead of the menu, by its `--mode` name,
+    /// or "auto" to generate a commit message when something is staged
+    pub default_mode: Option<String>,
+    /// Print what the session asked of the model (messages, files, estimated tokens) when
+    /// an interactive session ends; counted locally, nothing is sent
+    pub usage_summary: Option<bool>,
+    /// Clear the screen after each contributor's analysis; false keeps earlier ones in the
+    /// scrollback, separated by a line
+    pub clear_between_contributors: Option<bool>,
+    /// Branch `--base` compares with, e.g. "main" or "origin/develop"; the repository's
+    /// default branch without it
+    pub base_branch: Option<String>,
+    pub max_diff_bytes: Option<usize>,
+    /// Unchanged lines around each change in the diffs sent to the model; more helps with
+    /// refactorings but costs tokens
+    pub diff_context_lines: Option<u32>,
+    pub token_threshold: Option<usize>,
+    /// Diffs with fewer changed lines get a message made from the file names, without a request
+    pub min_diff_lines: Option<usize>,
+    pub cache: Option<bool>,
+    /// Answer identical AI requests from the response cache: true for every mode, false
+    /// for none; unset caches file analysis only
+    pub cache_responses: Option<bool>,
+    /// Hours a cached response is used before the model is asked again
+    pub cache_ttl_hours: Option<u64>,
+    pub scan_secrets: Option<bool>,
+    /// Put before the system prompt of every AI request, e.g. a team's style guide
+    pub system_prompt_prefix: Option<String>,
+    /// File analysis requests in flight at once
+    pub max_concurrency: Option<usize>,
+    /// Explain each hunk of a file separately instead of the file as a whole
+    pub hunk_level_analysis: Option<bool>,
+    /// Commit messages generated at once to pick from
+    pub candidates: Option<usize>,
+    /// Recent commits listed per contributor and sent with their statistics
+    pub recent_commits_shown: Option<usize>,
+    pub retry: Option<RetrySettings>,
+    /// Seconds an AI request may take before it is cancelled
+    pub request_timeout: Option<u64>,
+    /// Per-mode overrides of `request_timeout`, e.g. `contributors = 180`
+    pub mode_timeouts: HashMap<String, u64>,
+    /// Sampling temperature for AI requests; clamped to the range the provider accepts
+    pub temperature: Option<f32>,
+    /// Most tokens a response may have
+    pub max_tokens: Option<u32>,
+    /// Per-mode overrides of `temperature`, e.g. `commit = 0.2`
+    pub mode_temperatures: HashMap<String, f32>,
+    /// Per-mode overrides of `max_tokens`, e.g. `changelog = 4096`
+    pub mode_max_tokens: HashMap<String, u32>,
+    /// Commit type picked most recently in the commit type menu, written back by noah
+    pub last_commit_type: Option<String>,
+    /// Extra file analysis guidance by file extension, e.g. `rs = "focus on ..."`
+    pub file_prompts: HashMap<String, String>,
+    /// API keys by environment variable name, e.g. `OPENAI_API_KEY = "..."`
+    pub api_keys: HashMap<String, String>,
+}
+
+/// A `commit_types` entry: either just the prefix, or a table with emoji and label
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum CommitTypeSetting {
+    Prefix(String),
+    Full {
+        prefix: String,
+        #[serde(default)]
+        emoji: String,
+        #[serde(default)]
+        label: String,
+    },
+}
+
+impl CommitTypeSetting {
+    fn prefix(&self) -> &str {
+        match self {
+            CommitTypeSetting::Prefix(prefix) => prefix,
+            CommitTypeSetting::Full { prefix, .. } => prefix,
+        }
+    }
+
+    fn to_commit_type(&self) -> CommitType {
+        match self {
+            CommitTypeSetting::Prefix(prefix) => CommitType::from_prefix(prefix.trim()),
+            CommitTypeSetting::Full { prefix, emoji, label } => CommitType {
+                prefix: prefix.trim().to_string(),
+                emoji: emoji.clone(),
+                label: label.clone(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RetrySettings {
+    pub max_attempts: Option<u32>,
+    pub base_delay_ms: Option<u64>,
+    pub jitter_ms: Option<u64>,
+}
+
+impl ConfigFile {
+    /// Loads the user config and the repo-local `noah.toml`, the latter taking precedence.
+    /// Missing files are treated as empty. Without a `system_prompt_prefix` in either, the
+    /// repository's `.noah-prompt.md` is used if there is one.
+    pub fn load(repo_path: &Path) -> Result<Self, NoahError> {
+        let user = match user_config_path() {
+            Some(path) => Self::read(&path)?,
+            None => Self::default(),
+        };
+        let repo = Self::read(&repo_path.join(REPO_CONFIG_FILE))?;
+        let mut settings = user.merge(repo);
+        if settings.system_prompt_prefix.is_none() {
+            settings.system_prompt_prefix = read_prompt_prefix(&repo_path.join(PROMPT_PREFIX_FILE))?;
+        }
+        Ok(settings)
+    }
+
+    fn read(path: &Path) -> Result<Self, NoahError> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => {
+                let file: Self = toml::from_str(&content)
+                    .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+                file.validate().map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+                Ok(file)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read config file {}: {}", path.display(), e).into()),
+        }
+    }
+
+    /// Checks values that parse fine but can't be used
+    fn validate(&self) -> Result<(), String> {
+        for (idx, commit_type) in self.commit_types.iter().flatten().enumerate() {
+            if commit_type.prefix().trim().is_empty() {
+                return Err(format!("commit_types entry {} has an empty prefix", idx + 1));
+            }
+        }
+        let mode_tables = [
+            ("mode_timeouts", self.mode_timeouts.keys().collect::<Vec<_>>()),
+            ("mode_temperatures", self.mode_temperatures.keys().collect()),
+            ("mode_max_tokens", self.mode_max_tokens.keys().collect()),
+        ];
+        for (table, names) in mode_tables {
+            if let Some(name) = names.into_iter().find(|name| parse_mode(name).is_none()) {
+                return Err(format!("{} has an unknown mode {} (use commit, stage, split, files, review, failure, branch, explain, reword, contributors, report, changelog or pr)", table, name));
+            }
+        }
+        if let Some(name) = self.default_mode.as_ref().filter(|name| DefaultMode::parse(name).is_none()) {
+            return Err(format!("default_mode {} is not a mode (use auto, commit, stage, split, files, review, failure, branch, explain, reword, contributors, report, changelog, pr or hook)", name));
+        }
+        if self.request_timeout == Some(0) || self.mode_timeouts.values().any(|&secs| secs == 0) {
+            return Err("Request timeouts must be at least 1 second".to_string());
+        }
+        if self.temperature.iter().chain(self.mode_temperatures.values()).any(|temperature| !temperature.is_finite()) {
+            return Err("Temperatures must be numbers, e.g. 0.7".to_string());
+        }
+        if self.commit_template.as_ref().is_some_and(|template| !template.contains("{message}")) {
+            return Err("commit_template needs a {message} placeholder for the generated message".to_string());
+        }
+        if let Some(pattern) = &self.ticket_pattern {
+            regex::Regex::new(pattern).map_err(|e| format!("ticket_pattern is not a valid regex: {}", e))?;
+        }
+        if self.candidates == Some(0) {
+            return Err("candidates must be at least 1".to_string());
+        }
+        if self.recent_commits_shown == Some(0) {
+            return Err("recent_commits_shown must be at least 1".to_string());
+        }
+        if self.cache_ttl_hours == Some(0) {
+            return Err("cache_ttl_hours must be at least 1".to_string());
+        }
+        if self.max_tokens == Some(0) || self.mode_max_tokens.values().any(|&tokens| tokens == 0) {
+            return Err("max_tokens must be at least 1".to_string());
+        }
+        if let Some(local) = &self.local {
+            if local.base_url.trim().is_empty() {
+                return Err("local.base_url is empty".to_string());
+            }
+            if local.models.is_empty() {
+                return Err("local.models needs at least one model name".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// The configured commit types, if any
+    pub fn commit_types(&self) -> Option<Vec<CommitType>> {
+        self.commit_types.as_ref().map(|types| types.iter().map(CommitTypeSetting::to_commit_type).collect())
+    }
+
+    /// The configured `default_mode`, if it names one
+    pub fn default_mode(&self) -> Option<DefaultMode> {
+        self.default_mode.as_deref().and_then(DefaultMode::parse)
+    }
+
+    /// The configured per-mode timeouts in seconds
+    pub fn mode_timeouts(&self) -> Vec<(Mode, u64)> {
+        self.mode_timeouts.iter().filter_map(|(name, &secs)| Some((parse_mode(name)?, secs))).collect()
+    }
+
+    /// The configured per-mode generation settings, combining `mode_temperatures` and `mode_max_tokens`
+    pub fn mode_generation(&self) -> Vec<(Mode, GenerationOptions)> {
+        let mut modes: HashMap<Mode, GenerationOptions> = HashMap::new();
+        for (name, &temperature) in &self.mode_temperatures {
+            if let Some(mode) = parse_mode(name) {
+                modes.entry(mode).or_default().temperature = Some(temperature);
+            }
+        }
+        for (name, &max_tokens) in &self.mode_max_tokens {
+            if let Some(mode) = parse_mode(name) {
+                modes.entry(mode).or_default().max_tokens = Some(max_tokens);
+            }
+        }
+        modes.into_iter().collect()
+    }
+
+    /// Combines two layers; values set in `other` win
+    fn merge(self, other: Self) -> Self {
+        let mut api_keys = self.api_keys;
+        api_keys.extend(other.api_keys);
+        let mut file_prompts = self.file_prompts;
+        file_prompts.extend(other.file_prompts);
+        let mut mode_timeouts = self.mode_timeouts;
+        mode_timeouts.extend(other.mode_timeouts);
+        let mut mode_temperatures = self.mode_temperatures;
+        mode_temperatures.extend(other.mode_temperatures);
+        let mut mode_max_tokens = self.mode_max_tokens;
+        mode_max_tokens.extend(other.mode_max_tokens);
+        Self {
+            provider: other.provider.or(self.provider),
+            model: other.model.or(self.model),
+            local: other.local.or(self.local),
+            exclude: other.exclude.or(self.exclude),
+            commit_types: other.commit_types.or(self.commit_types),
+            message_style: other.message_style.or(self.message_style),
+            body_wrap: other.body_wrap.or(self.body_wrap),
+            commit_template: other.commit_template.or(self.commit_template),
+            ticket_pattern: other.ticket_pattern.or(self.ticket_pattern),
+            co_authors: other.co_authors.or(self.co_authors),
+            spinner: other.spinner.or(self.spinner),
+            theme: other.theme.or(self.theme),
+            quiet: other.quiet.or(self.quiet),
+            default_mode: other.default_mode.or(self.default_mode),
+            usage_summary: other.usage_summary.or(self.usage_summary),
+            clear_between_contributors: other.clear_between_contributors.or(self.clear_between_contributors),
+            base_branch: other.base_branch.or(self.base_branch),
+            max_diff_bytes: other.max_diff_bytes.or(self.max_diff_bytes),
+            diff_context_lines: other.diff_context_lines.or(self.diff_context_lines),
+            token_threshold: other.token_threshold.or(self.token_threshold),
+            min_diff_lines: other.min_diff_lines.or(self.min_diff_lines),
+            cache: other.cache.or(self.cache),
+            cache_responses: other.cache_responses.or(self.cache_responses),
+            cache_ttl_hours: other.cache_ttl_hours.or(self.cache_ttl_hours),
+            scan_secrets: other.scan_secrets.or(self.scan_secrets),
+            system_prompt_prefix: other.system_prompt_prefix.or(self.system_prompt_prefix),
+            max_concurrency: other.max_concurrency.or(self.max_concurrency),
+            hunk_level_analysis: other.hunk_level_analysis.or(self.hunk_level_analysis),
+            candidates: other.candidates.or(self.candidates),
+            recent_commits_shown: other.recent_commits_shown.or(self.recent_commits_shown),
+            request_timeout: other.request_timeout.or(self.request_timeout),
+            temperature: other.temperature.or(self.temperature),
+            max_tokens: other.max_tokens.or(self.max_tokens),
+            last_commit_type: other.last_commit_type.or(self.last_commit_type),
+            retry: match (self.retry, other.retry) {
+                (Some(base), Some(over)) => Some(RetrySettings {
+                    max_attempts: over.max_attempts.or(base.max_attempts),
+                    base_delay_ms: over.base_delay_ms.or(base.base_delay_ms),
+                    jitter_ms: over.jitter_ms.or(base.jitter_ms),
+                }),
+                (base, over) => over.or(base),
+            },
+            file_prompts,
+            mode_timeouts,
+            mode_temperatures,
+            mode_max_tokens,
+            api_keys,
+        }
+    }
+
+    /// Hands the configured API keys to the providers. Variables set in the environment
+    /// still take precedence.
+    pub fn register_api_keys(&self) {
+        for (name, value) in &self.api_keys {
+            providers::set_api_key(name, value);
+        }
+    }
+}
+
+/// A mode by its `--mode` name, e.g. "contributors"
+fn parse_mode(name: &str) -> Option<Mode> {
+    <Mode as clap::ValueEnum>::from_str(name.trim(), true).ok()
+}
+
+/// The contents of a prompt prefix file, `None` when it is missing or blank
+fn read_prompt_prefix(path: &Path) -> Result<Option<String>, NoahError> {
+    match std::fs::read_to_string(path) {
+        Ok(content) if content.trim().is_empty() => Ok(None),
+        Ok(content) => Ok(Some(content.trim().to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e).into()),
+    }
+}
+
+/// Stores `last_commit_type` in the user config file, creating it if needed.
+/// The rest of the file, including comments, is left as it was.
+pub fn save_last_commit_type(commit_type: &str) -> Result<(), NoahError> {
+    update_user_config(|document| document["last_commit_type"] = toml_edit::value(commit_type))?;
+    Ok(())
+}
+
+/// Stores what the setup wizard asked for in the user config file: the provider, its
+/// model and, if one was entered, an API key under `[api_keys]`. Returns the file's path.
+pub fn save_setup(provider: &str, model: &str, api_key: Option<(&str, &str)>) -> Result<PathBuf, NoahError> {
+    update_user_config(|document| {
+        document["provider"] = toml_edit::value(provider);
+        document["model"] = toml_edit::value(model);
+        if let Some((name, key)) = api_key {
+            if !document.contains_table("api_keys") {
+                document["api_keys"] = toml_edit::table();
+            }
+            document["api_keys"][name] = toml_edit::value(key);
+        }
+    })
+}
+
+/// Whether neither the user config nor the repository's `noah.toml` exists yet
+pub fn is_first_run(repo_path: &Path) -> bool {
+    let has_user_config = user_config_path().is_some_and(|path| path.exists());
+    !has_user_config && !repo_path.join(REPO_CONFIG_FILE).exists()
+}
+
+/// Applies `edit` to the user config file, creating it if needed, and returns its path
+fn update_user_config(edit: impl FnOnce(&mut toml_edit::DocumentMut)) -> Result<PathBuf, NoahError> {
+    let path = user_config_path().ok_or("No home directory to store the config file in")?;
+    let mut document = match std::fs::read_to_string(&path) {
+        Ok(content) => content.parse::<toml_edit::DocumentMut>()
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml_edit::DocumentMut::new(),
+        Err(e) => return Err(e.into()),
+    };
+    edit(&mut document);
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, document.to_string())?;
+    Ok(path)
+}
+
+/// `$XDG_CONFIG_HOME/noah/config.toml`, falling back to `~/.config/noah/config.toml`
+fn user_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("noah").join("config.toml"))
+}
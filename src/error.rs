@@ -0,0 +1,112 @@
+use std::error::Error;
+
+use crate::providers::ProviderError;
+
+/// Error type of the library API: `Config`, the git helpers and the files noah keeps
+/// in a repository
+#[derive(Debug)]
+pub enum NoahError {
+    /// There is nothing to commit or analyze, e.g. an empty diff
+    NoChanges,
+    /// Reading the repository or changing its index failed
+    Git(git2::Error),
+    Io(std::io::Error),
+    /// A cache or history file couldn't be read or written
+    Json(serde_json::Error),
+    /// The AI request failed; a `providers::ProviderError` for HTTP errors, timeouts and
+    /// rate limits, so it can be told apart with `downcast_ref`
+    Ai(Box<dyn Error + Send + Sync>),
+    /// Anything else, with a message that says what went wrong
+    Other(String),
+}
+
+impl NoahError {
+    /// Wraps the error of an AI request. Provider and HTTP errors are kept as they are;
+    /// other errors needn't be `Send`, so only their message is kept.
+    pub fn ai(error: Box<dyn Error>) -> Self {
+        let error = match error.downcast::<ProviderError>() {
+            Ok(error) => return Self::Ai(error),
+            Err(error) => error,
+        };
+        match error.downcast::<reqwest::Error>() {
+            Ok(error) => Self::Ai(error),
+            Err(error) => Self::Ai(error.to_string().into()),
+        }
+    }
+}
+
+impl std::fmt::Display for NoahError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoChanges => write!(f, "No changes to commit"),
+            Self::Git(e) => write!(f, "{}", e.message()),
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Json(e) => write!(f, "{}", e),
+            Self::Ai(e) => write!(f, "{}", e),
+            Self::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for NoahError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Git(e) => Some(e),
+            Self::Io(e) => Some(e),
+            Self::Json(e) => Some(e),
+            Self::Ai(e) => Some(e.as_ref()),
+            Self::NoChanges | Self::Other(_) => None,
+        }
+    }
+}
+
+impl From<git2::Error> for NoahError {
+    fn from(error: git2::Error) -> Self {
+        Self::Git(error)
+    }
+}
+
+impl From<std::io::Error> for NoahError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for NoahError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+impl From<String> for NoahError {
+    fn from(message: String) -> Self {
+        Self::Other(message)
+    }
+}
+
+impl From<&str> for NoahError {
+    fn from(message: &str) -> Self {
+        Self::Other(message.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_can_cross_threads() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<NoahError>();
+    }
+
+    #[test]
+    fn ai_errors_keep_provider_errors_downcastable() {
+        let error = NoahError::ai(Box::new(ProviderError::TimedOut { seconds: 5 }));
+        let NoahError::Ai(inner) = &error else { panic!("expected an AI error, got {:?}", error) };
+        assert!(matches!(inner.downcast_ref::<ProviderError>(), Some(ProviderError::TimedOut { seconds: 5 })));
+
+        let error = NoahError::ai("the stream broke off".into());
+        assert_eq!(error.to_string(), "the stream broke off");
+    }
+}
@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::Path;
+
+use crate::{FileAnalysis, NoahError, ReviewFinding, Severity};
+
+/// File formats analysis results can be exported to
+#[derive(Debug, Clone, Copy)]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn default_filename(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "analysis.md",
+            ExportFormat::Json => "analysis.json",
+        }
+    }
+}
+
+/// Renders analyses as the same markdown that is printed to the terminal
+pub fn analyses_to_markdown(analyses: &[FileAnalysis]) -> String {
+    let sections: Vec<String> = analyses.iter().map(|a| a.to_markdown()).collect();
+    format!("# File Analysis Results\n\n{}\n", sections.join("\n\n"))
+}
+
+pub fn analyses_to_json(analyses: &[FileAnalysis]) -> Result<String, NoahError> {
+    Ok(serde_json::to_string_pretty(analyses)?)
+}
+
+/// Renders review findings as markdown under a "Review" header, see `review_sections`
+pub fn review_to_markdown(findings: &[ReviewFinding]) -> String {
+    format!("# Review\n\n{}\n", review_sections(findings))
+}
+
+/// A section per severity with its findings listed, most severe first
+pub fn review_sections(findings: &[ReviewFinding]) -> String {
+    let mut sections = Vec::new();
+    for severity in [Severity::High, Severity::Medium, Severity::Low] {
+        let items: Vec<String> = findings.iter().filter(|f| f.severity == severity).map(ReviewFinding::to_markdown).collect();
+        if !items.is_empty() {
+            sections.push(format!("## {} {} ({})\n{}", severity.marker(), severity.label(), items.len(), items.join("\n")));
+        }
+    }
+    if sections.is_empty() {
+        "No findings.".to_string()
+    } else {
+        sections.join("\n\n")
+    }
+}
+
+/// Writes the analyses to `path` in the given format
+pub fn export_analyses(analyses: &[FileAnalysis], format: ExportFormat, path: &Path) -> Result<(), NoahError> {
+    let contents = match format {
+        ExportFormat::Markdown => analyses_to_markdown(analyses),
+        ExportFormat::Json => analyses_to_json(analyses)?,
+    };
+    fs::write(path, contents)?;
+    Ok(())
+}
@@ -1,27 +1,82 @@
 use std::error::Error;
 use std::fmt::Debug;
+use std::ops::RangeInclusive;
 use async_trait::async_trait;
 
-use crate::providers::Provider;
+use crate::commitlint::CommitlintRules;
+use crate::providers::{GenerationOptions, Provider, TextStream};
 
-/// Trait for git-specific model behavior
+/// Trait for git-specific model behavior. `options` are the configured generation
+/// settings; unset ones use the defaults of each task.
 #[async_trait]
 pub trait GitAnalyzer: Debug {
     fn name(&self) -> &str;
-    async fn generate_commit_message(&self, diff: &str) -> Result<String, Box<dyn Error>>;
-    async fn analyze_file_changes(&self, diff: &str) -> Result<String, Box<dyn Error>>;
-    async fn analyze_contributor(&self, stats: &str) -> Result<String, Box<dyn Error>>;
+    fn available_models(&self) -> Vec<String>;
+    /// Temperatures the provider accepts
+    fn temperature_range(&self) -> RangeInclusive<f32>;
+    /// Text put before the system prompt of every request, e.g. a team's style guide
+    fn set_system_prompt_prefix(&mut self, prefix: Option<String>);
+    /// The commit types and subject line length the commit message prompts ask for
+    fn set_commit_rules(&mut self, rules: CommitlintRules);
+    /// `project` describes the repository, e.g. its main language, and is appended to the prompt
+    async fn generate_commit_message(&self, model: &str, diff: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>>;
+    async fn generate_detailed_commit_message(&self, model: &str, diff: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>>;
+    async fn stream_commit_message(&self, model: &str, diff: &str, detailed: bool, project: Option<&str>, options: GenerationOptions) -> Result<TextStream, Box<dyn Error>>;
+    /// `focus` is extra, file-type specific guidance appended to the analysis prompt
+    async fn analyze_file_changes(&self, model: &str, diff: &str, focus: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>>;
+    async fn analyze_contributor(&self, model: &str, stats: &str, options: GenerationOptions) -> Result<String, Box<dyn Error>>;
+    async fn generate_changelog(&self, model: &str, commits: &str, options: GenerationOptions) -> Result<String, Box<dyn Error>>;
+    /// `request` holds the commits of a branch and its diff; returns a title line and a
+    /// markdown body
+    async fn generate_pr_description(&self, model: &str, request: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>>;
+    async fn analyze_team(&self, model: &str, stats: &str, options: GenerationOptions) -> Result<String, Box<dyn Error>>;
+    /// `request` holds the message and its subject line problems; returns a new subject line
+    async fn fix_commit_subject(&self, model: &str, request: &str, options: GenerationOptions) -> Result<String, Box<dyn Error>>;
+    /// `request` holds the diff and the body to keep; returns a subject line for them
+    async fn generate_subject(&self, model: &str, request: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>>;
+    /// Returns a JSON array of `{"files": [...], "message": "..."}` objects, one per commit
+    async fn suggest_commit_groups(&self, model: &str, diff: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>>;
+    /// Returns a JSON array of `{"severity", "category", "file", "line", "comment"}` objects,
+    /// one per finding
+    async fn review_changes(&self, model: &str, diff: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>>;
+    /// `request` holds an error log and the diff of the changes it may come from; returns
+    /// an explanation with a suggested fix
+    async fn explain_failure(&self, model: &str, request: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>>;
+    /// Returns a short kebab-case branch name for the changes
+    async fn suggest_branch_name(&self, model: &str, diff: &str, options: GenerationOptions) -> Result<String, Box<dyn Error>>;
 }
 
 /// Implementation of GitAnalyzer that uses any Provider
 #[derive(Debug)]
 pub struct GitAnalyzerImpl {
     provider: Box<dyn Provider>,
+    /// Put before every system prompt, see `GitAnalyzer::set_system_prompt_prefix`
+    system_prompt_prefix: Option<String>,
+    /// Filled into the commit message prompts, see `GitAnalyzer::set_commit_rules`
+    commit_rules: CommitlintRules,
 }
 
 impl GitAnalyzerImpl {
     pub fn new(provider: Box<dyn Provider>) -> Self {
-        Self { provider }
+        Self { provider, system_prompt_prefix: None, commit_rules: CommitlintRules::default() }
+    }
+
+    /// `prompt` with its `{types}` and `{max_chars}` filled in from the commit rules
+    fn commit_prompt(&self, prompt: &str) -> String {
+        let types = match &self.commit_rules.types {
+            Some(types) => format!("Use only these types, no others: {}", types.join(", ")),
+            None => COMMON_TYPES.to_string(),
+        };
+        let max_chars = self.commit_rules.header_max_length.unwrap_or(crate::MAX_SUBJECT_CHARS);
+        prompt.replace("{types}", &types).replace("{max_chars}", &max_chars.to_string())
+    }
+
+    /// `prompt` behind the configured prefix, if there is one
+    fn system_prompt(&self, prompt: &str) -> String {
+        match &self.system_prompt_prefix {
+            Some(prefix) => format!("{}\n\n{}", prefix.trim_end(), prompt),
+            None => prompt.to_string(),
+        }
     }
 }
 
@@ -31,16 +86,91 @@ impl GitAnalyzer for GitAnalyzerImpl {
         self.provider.name()
     }
 
-    async fn generate_commit_message(&self, diff: &str) -> Result<String, Box<dyn Error>> {
-        self.provider.generate_text(SYSTEM_MESSAGE, diff, 0.7).await
+    fn available_models(&self) -> Vec<String> {
+        self.provider.available_models().iter().map(|m| m.to_string()).collect()
+    }
+
+    fn temperature_range(&self) -> RangeInclusive<f32> {
+        self.provider.temperature_range()
+    }
+
+    fn set_system_prompt_prefix(&mut self, prefix: Option<String>) {
+        self.system_prompt_prefix = prefix;
+    }
+
+    fn set_commit_rules(&mut self, rules: CommitlintRules) {
+        self.commit_rules = rules;
+    }
+
+    async fn generate_commit_message(&self, model: &str, diff: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>> {
+        self.provider.generate_text(model, &self.system_prompt(&with_project(&self.commit_prompt(SYSTEM_MESSAGE), project)), diff, options.sampling(0.7)).await
+    }
+
+    async fn generate_detailed_commit_message(&self, model: &str, diff: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>> {
+        self.provider.generate_text(model, &self.system_prompt(&with_project(&self.commit_prompt(DETAILED_COMMIT_PROMPT), project)), diff, options.sampling(0.7)).await
+    }
+
+    async fn stream_commit_message(&self, model: &str, diff: &str, detailed: bool, project: Option<&str>, options: GenerationOptions) -> Result<TextStream, Box<dyn Error>> {
+        let prompt = if detailed { DETAILED_COMMIT_PROMPT } else { SYSTEM_MESSAGE };
+        self.provider.generate_text_stream(model, &self.system_prompt(&with_project(&self.commit_prompt(prompt), project)), diff, options.sampling(0.7)).await
+    }
+
+    async fn analyze_file_changes(&self, model: &str, diff: &str, focus: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>> {
+        match focus {
+            Some(focus) => {
+                let prompt = format!("{}\n\nFor this kind of file: {}", FILE_ANALYSIS_PROMPT, focus);
+                self.provider.generate_text(model, &self.system_prompt(&prompt), diff, options.sampling(0.7)).await
+            }
+            None => self.provider.generate_text(model, &self.system_prompt(FILE_ANALYSIS_PROMPT), diff, options.sampling(0.7)).await,
+        }
+    }
+
+    async fn analyze_contributor(&self, model: &str, stats: &str, options: GenerationOptions) -> Result<String, Box<dyn Error>> {
+        self.provider.generate_text(model, &self.system_prompt(CONTRIBUTOR_ANALYSIS_PROMPT), stats, options.sampling(0.7)).await
+    }
+
+    async fn generate_changelog(&self, model: &str, commits: &str, options: GenerationOptions) -> Result<String, Box<dyn Error>> {
+        self.provider.generate_text(model, &self.system_prompt(CHANGELOG_PROMPT), commits, options.sampling(0.7)).await
+    }
+
+    async fn generate_pr_description(&self, model: &str, request: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>> {
+        self.provider.generate_text(model, &self.system_prompt(&with_project(PR_DESCRIPTION_PROMPT, project)), request, options.sampling(0.7)).await
     }
 
-    async fn analyze_file_changes(&self, diff: &str) -> Result<String, Box<dyn Error>> {
-        self.provider.generate_text(FILE_ANALYSIS_PROMPT, diff, 0.7).await
+    async fn explain_failure(&self, model: &str, request: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>> {
+        self.provider.generate_text(model, &self.system_prompt(&with_project(FAILURE_PROMPT, project)), request, options.sampling(0.3)).await
     }
 
-    async fn analyze_contributor(&self, stats: &str) -> Result<String, Box<dyn Error>> {
-        self.provider.generate_text(CONTRIBUTOR_ANALYSIS_PROMPT, stats, 0.7).await
+    async fn analyze_team(&self, model: &str, stats: &str, options: GenerationOptions) -> Result<String, Box<dyn Error>> {
+        self.provider.generate_text(model, &self.system_prompt(TEAM_ANALYSIS_PROMPT), stats, options.sampling(0.7)).await
+    }
+
+    async fn fix_commit_subject(&self, model: &str, request: &str, options: GenerationOptions) -> Result<String, Box<dyn Error>> {
+        self.provider.generate_text(model, &self.system_prompt(&self.commit_prompt(SUBJECT_FIX_PROMPT)), request, options.sampling(0.3)).await
+    }
+
+    async fn generate_subject(&self, model: &str, request: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>> {
+        self.provider.generate_text(model, &self.system_prompt(&with_project(&self.commit_prompt(SUBJECT_ONLY_PROMPT), project)), request, options.sampling(0.7)).await
+    }
+
+    async fn suggest_commit_groups(&self, model: &str, diff: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>> {
+        self.provider.generate_text(model, &self.system_prompt(&with_project(&self.commit_prompt(SPLIT_COMMITS_PROMPT), project)), diff, options.sampling(0.3)).await
+    }
+
+    async fn review_changes(&self, model: &str, diff: &str, project: Option<&str>, options: GenerationOptions) -> Result<String, Box<dyn Error>> {
+        self.provider.generate_text(model, &self.system_prompt(&with_project(REVIEW_PROMPT, project)), diff, options.sampling(0.3)).await
+    }
+
+    async fn suggest_branch_name(&self, model: &str, diff: &str, options: GenerationOptions) -> Result<String, Box<dyn Error>> {
+        self.provider.generate_text(model, &self.system_prompt(BRANCH_NAME_PROMPT), diff, options.sampling(0.5)).await
+    }
+}
+
+/// `prompt` followed by the description of the project, if there is one
+fn with_project(prompt: &str, project: Option<&str>) -> String {
+    match project {
+        Some(project) => format!("{}\n\n{} Use the vocabulary of that ecosystem.", prompt, project),
+        None => prompt.to_string(),
     }
 }
 
@@ -48,18 +178,50 @@ pub fn wrap_provider(provider: Box<dyn Provider>) -> Box<dyn GitAnalyzer> {
     Box::new(GitAnalyzerImpl::new(provider))
 }
 
+/// Part of the response cache key. Bump it whenever a prompt changes, so that answers
+/// to the old prompt, e.g. file analyses without a risk rating, aren't reused.
+pub const PROMPT_VERSION: &str = "3";
+
+/// Built-in file analysis guidance by file extension
+pub const FILE_PROMPTS: [(&str, &str); 10] = [
+    ("rs", "focus on correctness and ownership, error handling, and any unsafe code or panics."),
+    ("md", "focus on clarity, accuracy, and whether the documentation matches the code."),
+    ("py", "focus on correctness, exception handling, and type hints."),
+    ("js", "focus on correctness, async behavior, and browser or runtime compatibility."),
+    ("ts", "focus on type safety, correctness, and async behavior."),
+    ("go", "focus on error handling, goroutine safety, and idiomatic Go."),
+    ("sh", "focus on quoting, error handling, and portability."),
+    ("toml", "focus on what configuration or dependencies changed and their effect."),
+    ("json", "focus on what configuration or data changed and their effect."),
+    ("yml", "focus on what configuration or pipeline steps changed and their effect."),
+];
+
+/// The types the commit message prompts suggest when commitlint doesn't restrict them
+const COMMON_TYPES: &str = "Common types are: feat (new feature), fix (bug fix), docs (documentation), style (formatting), refactor, test, chore";
+
 const SYSTEM_MESSAGE: &str = r#"You are an expert software developer tasked with writing clear, concise, and informative git commit messages following the Conventional Commits specification. Given a git diff, you will:
 
 1. Analyze the changes to understand what was modified
 2. Create a commit message following these rules:
    - Use the conventional commit format: <type>: <description>
-   - Common types are: feat (new feature), fix (bug fix), docs (documentation), style (formatting), refactor, test, chore
+   - {types}
    - The description should use imperative mood ("Add feature" not "Added feature")
-   - The entire message should be 50 chars or less
+   - The entire message should be {max_chars} chars or less
    - Focus on the "why" and "what" rather than the "how"
 
 Please provide only the commit message without any additional commentary or markdown formatting."#;
 
+const DETAILED_COMMIT_PROMPT: &str = r#"You are an expert software developer tasked with writing clear, informative git commit messages following the Conventional Commits specification. Given a git diff, you will:
+
+1. Analyze the changes to understand what was modified and why
+2. Create a commit message with up to three parts, separated by blank lines:
+   - A summary line in the format <type>: <description>, {max_chars} chars or less, in imperative mood
+   - {types}
+   - A body of one or more short paragraphs explaining why the change was made and what it affects
+   - An optional footer of git trailers; use "BREAKING CHANGE: <explanation>" only if the change breaks existing behavior
+
+Please provide only the commit message without any additional commentary or markdown formatting."#;
+
 const FILE_ANALYSIS_PROMPT: &str = r#"You are an expert software developer tasked with analyzing changes to a file. Given a git diff or file content, you will:
 
 1. Analyze the changes to understand what was modified
@@ -72,7 +234,10 @@ const FILE_ANALYSIS_PROMPT: &str = r#"You are an expert software developer taske
 Format your response in markdown with appropriate headers, lists, and code blocks where relevant.
 Do not include ``` tags in your response unless you are explicitly using them to format code. Do not include ```markdown!
 Try to be as concise as possible while still providing meaningful insights.
-Please focus on providing meaningful insights rather than just describing the changes line by line."#;
+Please focus on providing meaningful insights rather than just describing the changes line by line.
+
+End your response with a line rating how risky the change is, for someone deciding where to look first in a review:
+Risk: low (formatting, docs, tests, small isolated changes), Risk: medium (changed behavior with limited reach) or Risk: high (security, data handling, concurrency, public APIs or wide-reaching changes)"#;
 
 const CONTRIBUTOR_ANALYSIS_PROMPT: &str = r#"You are an expert software developer tasked with analyzing a contributor's work in a repository. Given information about their commits, files changed, and overall impact, you will:
 
@@ -89,4 +254,120 @@ const CONTRIBUTOR_ANALYSIS_PROMPT: &str = r#"You are an expert software develope
    - Any notable patterns or specialties in their work
 
 Format your response in markdown with appropriate headers, lists, and emphasis where relevant.
-Please provide a clear, professional summary that helps understand the contributor's role and impact on the project."#; 
\ No newline at end of file
+Please provide a clear, professional summary that helps understand the contributor's role and impact on the project."#;
+
+const TEAM_ANALYSIS_PROMPT: &str = r#"You are an expert software developer tasked with analyzing how a team works on a repository. Given aggregate statistics about all contributors, you will:
+
+1. Analyze the team as a whole:
+   - How work is distributed across contributors
+   - Which areas of the codebase see the most activity
+   - Knowledge concentration risks, using the bus factor (the fewest contributors who made half of all commits)
+
+2. Provide a concise summary that covers:
+   - The overall shape and health of the team's contributions
+   - Hotspots that may deserve extra review or documentation
+   - Concrete suggestions for spreading knowledge where it is concentrated
+
+Format your response in markdown with appropriate headers, lists, and emphasis where relevant.
+Please provide a clear, professional summary that helps understand how the team works together."#;
+
+const CHANGELOG_PROMPT: &str = r#"You are an expert software developer tasked with writing release notes. Given a list of commit summaries grouped by Conventional Commit type, you will:
+
+1. Write a polished changelog section for the release:
+   - Start with a `##` header naming the release range you were given
+   - Use `###` subsections such as Features, Bug Fixes, Performance, Documentation and Other Changes, omitting empty ones
+   - Rewrite each change as a short, user-facing bullet point; merge duplicates and drop pure noise like formatting or merge commits
+   - List breaking changes first under `### ⚠️ Breaking Changes` if any commit is marked with "!" or mentions BREAKING CHANGE
+
+Format your response in markdown. Do not include ``` tags in your response. Do not include ```markdown!
+Please provide only the changelog without any additional commentary."#;
+
+const PR_DESCRIPTION_PROMPT: &str = r#"You are an expert software developer tasked with describing a pull request. Given the commits on a branch and the diff of everything the branch changes, you will:
+
+1. Write a title on the first line:
+   - A short, plain sentence of 72 chars or less saying what the pull request does
+   - No conventional commit prefix, no trailing period and no markdown
+2. After a blank line, write the description in markdown with these sections:
+   - `## Summary`: what the change does and why, in two or three sentences
+   - `## Changes`: a bullet point per notable change, grouping related commits
+   - `## Testing`: how the change can be verified, and anything reviewers should try or look at closely
+3. Describe the end result of the branch, not each commit in turn; skip fixups and merge commits
+
+Do not include ``` tags around your response. Do not include ```markdown!
+Please provide only the title and the description without any additional commentary."#;
+
+const FAILURE_PROMPT: &str = r#"You are an expert software developer tasked with explaining why a build, test run or program failed. Given the error log and the diff of the changes made just before, you will:
+
+1. Explain the failure:
+   - Start with a `## What went wrong` section saying, in plain words, what the error means
+   - Point out the change in the diff that most likely caused it, naming the file and line; say so if the log doesn't seem related to the diff at all
+2. Suggest a fix:
+   - Add a `## Suggested fix` section with the concrete change to make, as a short code block where it helps
+   - Mention other likely causes only if the log leaves real doubt
+
+Format your response in markdown. Be concise, and don't repeat the log back.
+Please provide only the explanation without any additional commentary."#;
+
+const SUBJECT_FIX_PROMPT: &str = r#"You are an expert software developer tasked with fixing the subject line of a git commit message. Given the full commit message and the problems found in its subject line, you will:
+
+1. Rewrite only the subject line so that it:
+   - Keeps the conventional commit prefix (<type>: or <type>(<scope>):) unchanged
+   - Is {max_chars} chars or less in total
+   - Uses imperative mood ("Add feature" not "Added feature")
+   - Does not end with a period
+2. Keep the meaning of the original subject, using the body for context if there is one
+
+Please provide only the new subject line without any additional commentary, quotes or markdown formatting."#;
+
+const SUBJECT_ONLY_PROMPT: &str = r#"You are an expert software developer tasked with writing the subject line of a git commit message. Given a git diff and the body the author wrote for it, you will:
+
+1. Understand the change from the diff, using the body for what the author wants to stress
+2. Write only a new subject line that:
+   - Uses the conventional commit format: <type>: <description>
+   - {types}
+   - Is {max_chars} chars or less in total
+   - Uses imperative mood ("Add feature" not "Added feature")
+   - Does not end with a period
+   - Summarizes the body rather than repeating its first sentence
+
+Please provide only the subject line without any additional commentary, quotes or markdown formatting."#;
+
+const SPLIT_COMMITS_PROMPT: &str = r#"You are an expert software developer tasked with splitting a large change into several focused git commits. Given a git diff, you will:
+
+1. Group the changed files into logical commits:
+   - Each group is one coherent change, such as a feature, a bug fix or a dependency update
+   - Every changed file belongs to exactly one group; use the paths exactly as they appear in the diff
+   - Order the groups so that each commit builds on the ones before it
+2. Write a commit message for each group:
+   - Use the conventional commit format: <type>: <description>
+   - {types}
+   - {max_chars} chars or less, in imperative mood
+
+Respond with only a JSON array, without markdown formatting, in this form:
+[{"files": ["src/main.rs", "README.md"], "message": "feat: Add verbose flag"}]"#;
+
+const REVIEW_PROMPT: &str = r#"You are an experienced software developer reviewing a colleague's uncommitted changes. Given a git diff, you will:
+
+1. Look for problems in the changed lines, as a careful code reviewer would:
+   - Potential bugs, such as logic errors, unhandled edge cases, panics, races or leaked resources
+   - Style issues, such as unclear names, duplicated code or comments that no longer match the code
+   - Suggestions that would make the change simpler, safer or easier to maintain
+2. Rate each finding:
+   - "high" for bugs and security problems that should be fixed before committing
+   - "medium" for problems worth fixing soon
+   - "low" for style nits and optional suggestions
+3. Only report real findings about this diff; an empty array is a fine answer for a clean change
+
+Respond with only a JSON array, without markdown formatting, in this form:
+[{"severity": "high", "category": "bug", "file": "src/main.rs", "line": 42, "comment": "The index can be out of bounds when the list is empty; check its length first."}]
+Use "bug", "style" or "suggestion" as the category, and the line number in the new version of the file, or null if the finding isn't about one line."#;
+
+const BRANCH_NAME_PROMPT: &str = r#"You are an expert software developer tasked with naming the git branch for a change. Given a git diff, you will:
+
+1. Work out the main purpose of the change
+2. Name a branch after it:
+   - Start with the kind of change, such as feat, fix, docs, refactor or chore
+   - Use 2 to 5 lowercase words separated by hyphens, e.g. fix-login-timeout
+   - Only use letters, digits and hyphens
+
+Please provide only the branch name without any additional commentary, quotes or markdown formatting."#;
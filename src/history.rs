@@ -0,0 +1,232 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{git, NoahError};
+
+/// Stored in the git directory, next to the contributor cache
+const HISTORY_FILE: &str = ".noah-history.json";
+
+/// Oldest messages are dropped beyond this many
+const MAX_ENTRIES: usize = 100;
+
+/// Share of changed lines two diffs must have in common for a message to be suggested again
+pub const SIMILARITY_THRESHOLD: f64 = 0.8;
+
+/// A commit message that was accepted for a diff
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HistoryEntry {
+    fingerprint: Vec<u64>,
+    message: String,
+}
+
+/// Commit messages accepted for earlier diffs, newest first, so that repetitive changes
+/// such as version bumps can reuse a message instead of asking the model again
+#[derive(Debug)]
+pub struct MessageHistory {
+    path: PathBuf,
+    entries: Vec<HistoryEntry>,
+}
+
+impl MessageHistory {
+    /// Reads the history kept in `git_dir`. A missing or unreadable file is an empty history.
+    pub fn open(git_dir: &Path) -> Self {
+        let path = git_dir.join(HISTORY_FILE);
+        let entries = fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self { path, entries }
+    }
+
+    /// The message whose diff is most similar to `diff`, if that is at least `threshold`,
+    /// with the similarity between 0 and 1. Of equally similar messages the newest wins.
+    pub fn best_match(&self, diff: &str, threshold: f64) -> Option<(&str, f64)> {
+        let fingerprint = fingerprint(diff);
+        self.entries
+            .iter()
+            .map(|entry| (entry.message.as_str(), similarity(&fingerprint, &entry.fingerprint)))
+            .filter(|(_, score)| *score >= threshold)
+            .fold(None, |best: Option<(&str, f64)>, candidate| match best {
+                Some(best) if best.1 >= candidate.1 => Some(best),
+                _ => Some(candidate),
+            })
+    }
+
+    /// Remembers `message` for `diff`, replacing what was stored for the same changes
+    pub fn record(&mut self, diff: &str, message: &str) -> Result<(), NoahError> {
+        let fingerprint = fingerprint(diff);
+        if fingerprint.is_empty() {
+            // Nothing to compare later diffs with, e.g. only binary files or mode changes
+            log::debug!("Not remembering the message for a diff without changed lines");
+            return Ok(());
+        }
+        self.entries.retain(|entry| entry.fingerprint != fingerprint);
+        self.entries.insert(0, HistoryEntry { fingerprint, message: message.trim().to_string() });
+        self.entries.truncate(MAX_ENTRIES);
+        fs::write(&self.path, serde_json::to_string(&self.entries)?)?;
+        Ok(())
+    }
+}
+
+/// Hashes of the changed lines and file headers of a diff, sorted and without duplicates.
+/// Digits are collapsed so that e.g. `1.2.3` and `1.2.4` count as the same line; hunk
+/// headers and blob ids are left out since they differ for every change. The content of
+/// a `New file:` section counts as added lines, as it is in a patch.
+fn fingerprint(diff: &str) -> Vec<u64> {
+    let mut in_new_file = false;
+    let mut hashes: Vec<u64> = diff
+        .lines()
+        .filter_map(|line| {
+            if git::file_header_path(line).is_some() {
+                in_new_file = line.starts_with("New file: ");
+                return Some(line.to_string());
+            }
+            if in_new_file {
+                return Some(format!("+{}", line));
+            }
+            let changed = line.starts_with('+') || line.starts_with('-');
+            (changed && !line.starts_with("@@")).then(|| line.to_string())
+        })
+        .map(|line| {
+            let mut normalized = String::with_capacity(line.len());
+            for c in line.trim_end().chars() {
+                if !(c.is_ascii_digit() && normalized.ends_with('0')) {
+                    normalized.push(if c.is_ascii_digit() { '0' } else { c });
+                }
+            }
+            fnv1a(&normalized)
+        })
+        .collect();
+    hashes.sort_unstable();
+    hashes.dedup();
+    hashes
+}
+
+/// Jaccard similarity of two sorted fingerprints
+fn similarity(a: &[u64], b: &[u64]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let (mut i, mut j, mut common) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                common += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    common as f64 / (a.len() + b.len() - common) as f64
+}
+
+/// A hash that stays the same across Rust versions, unlike `DefaultHasher`, since it is stored
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bump(version: &str) -> String {
+        format!("diff --git a/Cargo.toml b/Cargo.toml\nindex 1a2b3c..4d5e6f 100644\n@@ -3 +3 @@\n-version = \"0.1.0\"\n+version = \"{}\"\n", version)
+    }
+
+    #[test]
+    fn fingerprints_ignore_numbers_and_line_positions() {
+        assert_eq!(fingerprint(&bump("0.2.0")), fingerprint(&bump("10.20.300")));
+        let moved = bump("0.2.0").replace("@@ -3 +3 @@", "@@ -30 +30 @@").replace("index 1a2b3c..4d5e6f", "index 999999..888888");
+        assert_eq!(fingerprint(&moved), fingerprint(&bump("0.2.0")));
+        assert_ne!(fingerprint(&bump("0.2.0")), fingerprint(&bump("0.2.0-beta")));
+    }
+
+    #[test]
+    fn fingerprints_are_sorted_sets_of_changed_lines() {
+        let diff = "diff --git a/a b/a\n context\n+same\n+same\n-gone\n";
+        let hashes = fingerprint(diff);
+        // The header, one "+same" and "-gone"; context lines don't count
+        assert_eq!(hashes.len(), 3);
+        assert!(hashes.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(fingerprint("").is_empty());
+    }
+
+    #[test]
+    fn new_files_are_fingerprinted_by_their_content() {
+        let diff = "New file: a.txt\none\ntwo\nNew file: b.txt\none\n";
+        // Two headers and the lines "one" and "two", as if added in a patch
+        assert_eq!(fingerprint(diff).len(), 4);
+        let patch = "diff --git a/a.txt b/a.txt\n+one\n+two\n";
+        assert_eq!(similarity(&fingerprint("New file: a.txt\none\ntwo\n"), &fingerprint(patch)), 0.5);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = MessageHistory::open(dir.path());
+        history.record(diff, "feat: add a and b").unwrap();
+        assert_eq!(history.best_match(diff, 1.0), Some(("feat: add a and b", 1.0)));
+    }
+
+    #[test]
+    fn similarity_is_the_share_of_lines_in_common() {
+        assert_eq!(similarity(&[1, 2, 3], &[1, 2, 3]), 1.0);
+        assert_eq!(similarity(&[1, 2, 3], &[4, 5]), 0.0);
+        assert_eq!(similarity(&[1, 2, 3], &[2, 3, 4]), 0.5);
+        assert_eq!(similarity(&[], &[]), 0.0);
+        assert_eq!(similarity(&[1], &[]), 0.0);
+    }
+
+    #[test]
+    fn the_most_similar_message_above_the_threshold_is_suggested() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = MessageHistory::open(dir.path());
+        history.record(&bump("0.2.0"), "chore: release 0.2.0").unwrap();
+        history.record("diff --git a/a b/a\n+other\n", "feat: other").unwrap();
+
+        let (message, score) = history.best_match(&bump("0.3.0"), SIMILARITY_THRESHOLD).unwrap();
+        assert_eq!((message, score), ("chore: release 0.2.0", 1.0));
+        assert!(history.best_match("diff --git a/b b/b\n+unrelated\n", SIMILARITY_THRESHOLD).is_none());
+        // Kept in the git directory for the next session
+        assert!(MessageHistory::open(dir.path()).best_match(&bump("0.3.0"), SIMILARITY_THRESHOLD).is_some());
+    }
+
+    #[test]
+    fn equally_similar_messages_go_to_the_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = MessageHistory::open(dir.path());
+        history.record("diff --git a/a b/a\n+one\n", "feat: closest").unwrap();
+        history.record("diff --git a/a b/a\n+one\n+two\n", "feat: older").unwrap();
+        history.record("diff --git a/a b/a\n+one\n+three\n", "feat: newer").unwrap();
+
+        // The most similar message wins, however many were recorded after it
+        assert_eq!(history.best_match("diff --git a/a b/a\n+one\n", 0.5), Some(("feat: closest", 1.0)));
+        let (message, score) = history.best_match("diff --git a/a b/a\n+one\n+two\n+three\n", 0.5).unwrap();
+        assert_eq!((message, score), ("feat: newer", 0.75));
+    }
+
+    #[test]
+    fn recording_the_same_changes_again_replaces_the_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = MessageHistory::open(dir.path());
+        history.record(&bump("0.2.0"), "chore: release 0.2.0").unwrap();
+        history.record(&bump("0.3.0"), "  chore: bump version\n").unwrap();
+        assert_eq!(history.entries.len(), 1);
+        assert_eq!(history.best_match(&bump("0.4.0"), 1.0), Some(("chore: bump version", 1.0)));
+    }
+
+    #[test]
+    fn the_history_keeps_the_newest_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = MessageHistory::open(dir.path());
+        for n in 0..=MAX_ENTRIES {
+            let letter = |n: usize| char::from(b'a' + (n % 26) as u8);
+            let diff = format!("diff --git a/{0}{1} b/{0}{1}\n+{0}{1}\n", letter(n), letter(n / 26));
+            history.record(&diff, &format!("message {}", n)).unwrap();
+        }
+        assert_eq!(history.entries.len(), MAX_ENTRIES);
+        assert_eq!(history.entries[0].message, format!("message {}", MAX_ENTRIES));
+        assert!(history.entries.iter().all(|entry| entry.message != "message 0"));
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use git2::Repository;
+
+use crate::NoahError;
+
+const HOOK_NAME: &str = "prepare-commit-msg";
+
+/// Marks hooks written by `install`, so that only those are replaced or removed
+const HOOK_MARKER: &str = "# Installed by noah";
+
+/// What headless runs of the hook mode do, from `--force` and `--uninstall`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HookAction {
+    /// Install the hook unless another one is already there
+    #[default]
+    Install,
+    /// Install the hook, replacing whatever hook was there
+    Replace,
+    /// Remove the hook, if it's ours
+    Uninstall,
+}
+
+/// The `prepare-commit-msg` hook currently in place
+#[derive(Debug, Clone, PartialEq)]
+pub enum HookStatus {
+    Missing,
+    /// Written by `install`
+    Installed,
+    /// Some other hook, which is left alone unless replacing is asked for
+    Foreign,
+}
+
+/// Where git looks for the hook, honoring `core.hooksPath`
+pub fn hook_path(repo: &Repository) -> Result<PathBuf, NoahError> {
+    let hooks_dir = match repo.config()?.get_path("core.hooksPath") {
+        Ok(dir) if dir.is_relative() => repo.workdir().unwrap_or_else(|| repo.path()).join(dir),
+        Ok(dir) => dir,
+        Err(_) => repo.path().join("hooks"),
+    };
+    Ok(hooks_dir.join(HOOK_NAME))
+}
+
+pub fn status(repo: &Repository) -> Result<HookStatus, NoahError> {
+    match fs::read_to_string(hook_path(repo)?) {
+        Ok(script) if script.contains(HOOK_MARKER) => Ok(HookStatus::Installed),
+        Ok(_) => Ok(HookStatus::Foreign),
+        // A binary hook isn't ours either
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidData => Ok(HookStatus::Foreign),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HookStatus::Missing),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes a hook that fills in the message for a plain `git commit` by running this binary
+/// headless. Another hook already in place is only replaced with `replace` set.
+/// Returns where the hook was written.
+pub fn install(repo: &Repository, replace: bool) -> Result<PathBuf, NoahError> {
+    if repo.is_bare() {
+        return Err("A bare repository has no commits made in it, so it doesn't need a commit message hook".into());
+    }
+    let path = hook_path(repo)?;
+    if status(repo)? == HookStatus::Foreign && !replace {
+        return Err(format!(
+            "A {} hook already exists at {}. Pass --force to replace it.",
+            HOOK_NAME,
+            path.display()
+        ).into());
+    }
+
+    let binary = std::env::current_exe().map_err(|e| format!("Can't tell where noah is installed: {}", e))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, hook_script(&binary))?;
+    make_executable(&path)?;
+    Ok(path)
+}
+
+/// Removes the hook written by `install`. Any other hook is left alone.
+/// Returns where the hook was.
+pub fn uninstall(repo: &Repository) -> Result<PathBuf, NoahError> {
+    let path = hook_path(repo)?;
+    match status(repo)? {
+        HookStatus::Installed => {
+            fs::remove_file(&path)?;
+            Ok(path)
+        }
+        HookStatus::Foreign => Err(format!(
+            "The {} hook at {} wasn't installed by noah, so it was left alone",
+            HOOK_NAME,
+            path.display()
+        ).into()),
+        HookStatus::Missing => Err(format!("No {} hook is installed", HOOK_NAME).into()),
+    }
+}
+
+/// Only plain commits get a message, not `-m`, templates, merges, squashes or amends.
+/// The hook never fails the commit: without a message git simply opens the editor as usual,
+/// after the error is printed marked as the hook's.
+fn hook_script(binary: &Path) -> String {
+    format!(
+        r#"#!/bin/sh
+{marker}: suggests a commit message for `git commit`.
+# Remove it with: noah --headless --mode hook --uninstall
+[ -z "$2" ] || exit 0
+if ! message=$({binary} --headless --mode commit --quiet 2>"$1.noah-error"); then
+    sed 's/^/noah {hook} hook: /' "$1.noah-error" >&2
+    rm -f "$1.noah-error"
+    exit 0
+fi
+rm -f "$1.noah-error"
+[ -n "$message" ] || exit 0
+{{ printf '%s\n' "$message"; cat "$1"; }} > "$1.noah" && mv "$1.noah" "$1"
+exit 0
+"#,
+        hook = HOOK_NAME,
+        marker = HOOK_MARKER,
+        binary = shell_quote(&binary.to_string_lossy()),
+    )
+}
+
+/// Single-quotes `text` for `sh`
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), NoahError> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_mode(0o755);
+    fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<(), NoahError> {
+    Ok(())
+}
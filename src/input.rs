@@ -0,0 +1,139 @@
+use std::io::Read;
+
+use git2::Repository;
+
+use crate::{git, NoahError};
+
+/// Where the changes to work on come from
+pub enum DiffSource {
+    /// The working tree and index of an open repository
+    Repository(Repository),
+    /// A unified diff read once from a patch file or stdin; there is nothing to commit to
+    Patch(Patch),
+    /// Several repositories by path, each worked on in turn
+    Repositories(Vec<(String, Repository)>),
+}
+
+impl DiffSource {
+    /// The repository to commit to, if the changes come from one
+    pub fn repository(&self) -> Option<&Repository> {
+        match self {
+            DiffSource::Repository(repo) => Some(repo),
+            DiffSource::Patch(_) | DiffSource::Repositories(_) => None,
+        }
+    }
+}
+
+/// The repository paths given on the command line, with globs in their last part, e.g.
+/// `repos/*`, replaced by the matching directories in alphabetical order. No paths means
+/// the current directory.
+pub fn expand_repo_paths(paths: &[String]) -> Result<Vec<String>, NoahError> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        let path_ref = std::path::Path::new(path);
+        let name = path_ref.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if !name.contains(['*', '?', '[']) {
+            expanded.push(path.clone());
+            continue;
+        }
+        let matcher = globset::Glob::new(name)
+            .map_err(|e| format!("Invalid repository pattern {}: {}", path, e))?
+            .compile_matcher();
+        let parent = path_ref.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+        let mut matches: Vec<String> = std::fs::read_dir(parent)
+            .map_err(|e| format!("Failed to read {}: {}", parent.display(), e))?
+            .flatten()
+            .filter(|entry| entry.path().is_dir() && matcher.is_match(entry.file_name()))
+            .map(|entry| entry.path().display().to_string())
+            .collect();
+        if matches.is_empty() {
+            return Err(format!("No directories match {}", path).into());
+        }
+        matches.sort();
+        expanded.extend(matches);
+    }
+    if expanded.is_empty() {
+        expanded.push(".".to_string());
+    }
+    Ok(expanded)
+}
+
+/// A unified diff, e.g. from `git diff` or `git format-patch`
+#[derive(Debug, Clone)]
+pub struct Patch {
+    name: String,
+    diff: String,
+}
+
+impl Patch {
+    /// Reads the patch at `path`; `-` reads it from stdin
+    pub fn read(path: &str) -> Result<Self, NoahError> {
+        let (name, diff) = if path == "-" {
+            let mut diff = String::new();
+            std::io::stdin().read_to_string(&mut diff)
+                .map_err(|e| format!("Failed to read patch from stdin: {}", e))?;
+            ("stdin".to_string(), diff)
+        } else {
+            let diff = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read patch {}: {}", path, e))?;
+            (path.to_string(), diff)
+        };
+
+        if git::parse_patch(&diff).is_empty() {
+            return Err(format!("No file changes found in patch from {}", name).into());
+        }
+        Ok(Self { name, diff })
+    }
+
+    /// The file name the patch was read from, or "stdin"
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn diff(&self) -> &str {
+        &self.diff
+    }
+
+    /// `(path, diff)` pairs, one per changed file, in the same shape as `git::get_file_diffs`
+    pub fn file_diffs(&self) -> Vec<(String, String)> {
+        git::parse_patch(&self.diff)
+    }
+}
+
+/// Most of an error log that is sent along with the diff. Errors usually end a log, so
+/// longer ones keep their last lines.
+pub const MAX_LOG_BYTES: usize = 20_000;
+
+/// Reads the error log at `path`, or from stdin for `-`, cut with `log_tail`
+pub fn read_log(path: &str) -> Result<String, NoahError> {
+    let (name, log) = if path == "-" {
+        let mut log = String::new();
+        std::io::stdin().read_to_string(&mut log)
+            .map_err(|e| format!("Failed to read the error log from stdin: {}", e))?;
+        ("stdin", log)
+    } else {
+        let log = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read the error log {}: {}", path, e))?;
+        (path, log)
+    };
+    if log.trim().is_empty() {
+        return Err(format!("The error log from {} is empty", name).into());
+    }
+    Ok(log_tail(&log))
+}
+
+/// The last whole lines of `log` that fit in `MAX_LOG_BYTES`, with a note when earlier
+/// ones were left out
+pub fn log_tail(log: &str) -> String {
+    let log = log.trim();
+    if log.len() <= MAX_LOG_BYTES {
+        return log.to_string();
+    }
+    let mut start = log.len() - MAX_LOG_BYTES;
+    while !log.is_char_boundary(start) {
+        start += 1;
+    }
+    let tail = &log[start..];
+    let tail = tail.split_once('\n').map_or(tail, |(_, rest)| rest);
+    format!("[earlier lines of the log left out]\n{}", tail)
+}
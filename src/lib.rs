@@ -1,110 +1,2826 @@
+//! Commit message generation and change analysis for git repositories, usable on its own
+//! or through the `noah` binary.
+//!
+//! `Config` makes the AI requests, `git` reads diffs and commits, and errors are
+//! `NoahError`, which is `Send + Sync`. The interactive menus are internal; the binary
+//! only calls `run` with the parsed `Args`.
+//!
+//! ```no_run
+//! use merit_cli_demo::{git, Config, NoahError};
+//!
+//! # async fn suggest() -> Result<(), NoahError> {
+//! let config = Config::load(".")?;
+//! let repo = git2::Repository::open(".")?;
+//! let diff = git::get_diff_scoped(&repo, git::DiffScope::Staged, config.diff_context_lines())?;
+//! let message = config.generate_commit_message(&diff, false).await?;
+//! println!("{}", message);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
 use std::error::Error;
 use git2::Repository;
+use serde::{Deserialize, Serialize};
+
+pub(crate) mod cli;
+pub mod commitlint;
+pub mod config_file;
+pub mod error;
+pub mod export;
+pub mod providers;
+pub mod retry;
+pub(crate) mod setup;
+pub mod git_analysis;
+pub mod git;
+pub mod history;
+pub mod hook;
+pub mod input;
+pub mod response_cache;
+pub mod logging;
+pub(crate) mod shutdown;
+pub(crate) mod ui;
+pub(crate) mod modes;
+#[cfg(test)]
+mod test_util;
+
+pub use cli::Args;
+pub use error::NoahError;
+pub use modes::{DefaultMode, Mode};
+pub use shutdown::install_ctrl_c_handler;
+
+/// Roughly 25k tokens, which fits the context window of every supported model
+const DEFAULT_MAX_DIFF_BYTES: usize = 100_000;
+
+/// Default number of file analysis requests in flight at once
+const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// Estimated request size above which the user is asked before sending
+const DEFAULT_TOKEN_THRESHOLD: usize = 20_000;
+
+/// Commit messages generated at once for the user to pick from; one means no picking
+const DEFAULT_CANDIDATES: usize = 1;
+
+/// Recent commits listed per contributor unless configured otherwise
+const DEFAULT_RECENT_COMMITS_SHOWN: usize = 5;
+
+/// Most recent commits listed per contributor, so the statistics sent stay a sensible size
+const MAX_RECENT_COMMITS_SHOWN: usize = 50;
+
+/// How long a cached AI response is used before the model is asked again
+const DEFAULT_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// How long a single AI request may take before it is cancelled
+const DEFAULT_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Files bigger than this, e.g. lockfiles, are listed in file analysis but not sent
+const LARGE_FILE_BYTES: u64 = 500_000;
+
+/// Longest subject line Conventional Commits recommends, unless commitlint allows another
+const MAX_SUBJECT_CHARS: usize = 50;
+
+/// Width body lines should be wrapped at
+const MAX_BODY_LINE_CHARS: usize = 72;
+
+#[derive(Debug)]
+pub struct Config {
+    model: Box<dyn git_analysis::GitAnalyzer>,
+    model_name: String,
+    /// Models used instead of `model_name` for the requests of some modes, see `with_mode_model`
+    mode_models: HashMap<modes::Mode, String>,
+    repo_path: String,
+    dry_run: bool,
+    exclude_globs: Vec<String>,
+    retry: retry::RetryPolicy,
+    request_timeout: std::time::Duration,
+    /// Overrides of `request_timeout` for modes whose requests legitimately take longer
+    mode_timeouts: HashMap<modes::Mode, std::time::Duration>,
+    /// Temperature and response length for every request, unless the mode has its own
+    generation: providers::GenerationOptions,
+    mode_generation: HashMap<modes::Mode, providers::GenerationOptions>,
+    max_diff_bytes: usize,
+    /// Context around each change in the diffs sent to the model
+    diff_context_lines: u32,
+    use_cache: bool,
+    /// Whether AI responses are cached for every mode; `None` caches file analysis only
+    cache_responses: Option<bool>,
+    cache_ttl: std::time::Duration,
+    token_threshold: usize,
+    /// Diffs with fewer changed lines don't need the model, see `trivial_commit_message`
+    min_diff_lines: usize,
+    commit_types: Vec<CommitType>,
+    /// Set when the types come from the repository's commitlint config, which rejects others
+    types_enforced: bool,
+    /// Longest subject line the lint check lets through
+    max_subject_chars: usize,
+    message_style: MessageStyle,
+    /// Generated bodies are wrapped at this column, unless it is 0
+    body_wrap: usize,
+    commit_template: Option<CommitTemplate>,
+    co_authors: Vec<String>,
+    file_prompts: HashMap<String, String>,
+    scan_secrets: bool,
+    /// Put before every system prompt, from the config or `.noah-prompt.md`
+    system_prompt_prefix: Option<String>,
+    max_concurrency: usize,
+    /// Files with several hunks are analyzed hunk by hunk
+    hunk_level_analysis: bool,
+    candidates: usize,
+    recent_commits_shown: usize,
+    /// Whether the screen is cleared after each contributor's analysis, or a line printed
+    clear_between_contributors: bool,
+    /// Remembered for the session and persisted, so the commit type menu starts there
+    last_commit_type: std::sync::Mutex<Option<String>>,
+    /// The commit created most recently in this session, the only one that may be undone
+    session_commit: std::sync::Mutex<Option<git2::Oid>>,
+    output_format: cli::OutputFormat,
+    /// Commit or range to explain, from `--rev`
+    revision: Option<String>,
+    /// What `--base` compares with; the repository's default branch without one
+    base_branch: Option<String>,
+    /// Set by `--base`: commit messages and file analysis cover the current branch instead
+    /// of the uncommitted changes
+    against_base: bool,
+    /// The only file to analyze, from `--file`
+    only_file: Option<String>,
+    /// Where the error log to explain is read from, from `--log`
+    failure_log: Option<String>,
+    /// Review findings less severe than this are left out, from `--severity`
+    min_severity: Severity,
+    /// Analyzed files rated less risky than this are left out, from `--min-risk`
+    min_risk: RiskLevel,
+    /// What a headless run of the hook mode does, from `--force` and `--uninstall`
+    hook_action: hook::HookAction,
+    /// Replace existing files in headless runs, see `with_overwrite`
+    overwrite: bool,
+    quiet: bool,
+    /// Run instead of showing the main menu when an interactive session starts
+    default_mode: Option<modes::DefaultMode>,
+    /// What this session has asked of the model, kept across provider and repository switches
+    usage: std::sync::Arc<std::sync::Mutex<SessionUsage>>,
+    /// Whether an interactive session prints `usage` when it ends
+    usage_summary: bool,
+    /// False when no provider is set up, see `without_provider`
+    has_provider: bool,
+    /// Worked out once per repository, see `project_summary`
+    project_summary: std::sync::OnceLock<Option<String>>,
+}
+
+/// What a session has asked of the model, counted locally and never sent anywhere
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionUsage {
+    pub commit_messages: usize,
+    pub files_analyzed: usize,
+    /// Requests the model answered, not counting answers from the response cache
+    pub requests: usize,
+    /// Estimated with `Config::estimate_tokens`, from what was sent and received
+    pub tokens: usize,
+}
+
+impl SessionUsage {
+    /// A line such as "3 commit messages generated, 12 files analyzed, ~8,400 tokens
+    /// used", or `None` if nothing was asked of the model
+    pub fn summary(&self) -> Option<String> {
+        if self.requests == 0 && self.commit_messages == 0 && self.files_analyzed == 0 {
+            return None;
+        }
+        let mut parts = Vec::new();
+        if self.commit_messages > 0 {
+            parts.push(format!("{} commit message{} generated", self.commit_messages, if self.commit_messages == 1 { "" } else { "s" }));
+        }
+        if self.files_analyzed > 0 {
+            parts.push(format!("{} file{} analyzed", self.files_analyzed, if self.files_analyzed == 1 { "" } else { "s" }));
+        }
+        if self.requests > 0 {
+            parts.push(format!("{} request{}", self.requests, if self.requests == 1 { "" } else { "s" }));
+        }
+        parts.push(format!("~{} tokens used", ui::format_number(self.tokens)));
+        Some(parts.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileAnalysis {
+    pub path: String,
+    pub change: git::ChangeKind,
+    /// The explanation as markdown; with hunk-level analysis, a subsection per hunk
+    pub explanation: String,
+    /// One entry per hunk when the file was analyzed hunk by hunk, otherwise empty
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hunks: Vec<HunkAnalysis>,
+    /// Binary files are listed without asking the model about them
+    pub is_binary: bool,
+    /// Size of the file in bytes, the old version for a deleted file; `None` when it
+    /// can't be found, e.g. for a patch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub byte_size: Option<u64>,
+    /// How risky the model rates the change, the riskiest hunk's rating with hunk-level
+    /// analysis. `None` for files that weren't sent or that the model didn't rate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub risk: Option<RiskLevel>,
+}
+
+/// The explanation of one hunk of a file, see `Config::with_hunk_level_analysis`
+#[derive(Debug, Clone, Serialize)]
+pub struct HunkAnalysis {
+    /// The `@@ ... @@` line the hunk starts with
+    pub header: String,
+    pub explanation: String,
+}
+
+impl FileAnalysis {
+    pub fn to_markdown(&self) -> String {
+        let risk = self.risk.map(|risk| format!(" {} {} risk", risk.marker(), risk.label())).unwrap_or_default();
+        match &self.change {
+            git::ChangeKind::Renamed { from, to } => format!("## 📁 {} → {}{}\n{}", from, to, risk, self.explanation),
+            _ => format!("## 📁 {}{}\n{}", self.path, risk, self.explanation),
+        }
+    }
+}
+
+/// How much a review finding or a file's change matters, most serious first. Review
+/// findings call it `Severity` and file analyses `RiskLevel`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    /// Bugs, security, data handling, public APIs and other wide-reaching changes
+    #[serde(alias = "critical", alias = "major")]
+    High,
+    Medium,
+    /// Style nits, optional suggestions, formatting, docs, tests and small isolated changes
+    #[default]
+    #[serde(alias = "minor", alias = "info", alias = "nit")]
+    Low,
+}
+
+/// How much a review finding matters
+pub type Severity = Level;
+
+/// How risky the model rates a file's change in file analysis
+pub type RiskLevel = Level;
+
+impl Level {
+    pub fn marker(&self) -> &'static str {
+        match self {
+            Level::High => "🔴",
+            Level::Medium => "🟠",
+            Level::Low => "🟢",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Level::High => "High",
+            Level::Medium => "Medium",
+            Level::Low => "Low",
+        }
+    }
+
+    /// Whether something of this level is shown when only `min` and above are asked for
+    pub fn is_at_least(&self, min: Level) -> bool {
+        *self <= min
+    }
+
+    /// The `items` whose `level` is at least `min`. Unrated items only pass when
+    /// everything is asked for.
+    pub fn filter<T: Clone>(items: &[T], min: Level, level: impl Fn(&T) -> Option<Level>) -> Vec<T> {
+        items.iter().filter(|item| level(item).map_or(min == Level::Low, |level| level.is_at_least(min))).cloned().collect()
+    }
+
+    /// Splits the `Risk: high` line the model ends a file analysis with off `explanation`.
+    /// The rating is `None`, and the text unchanged, when there is no such line.
+    ///
+    /// ```
+    /// use merit_cli_demo::RiskLevel;
+    ///
+    /// let (text, risk) = RiskLevel::split_rating("Adds a login form.\n\n**Risk: High**");
+    /// assert_eq!((text.as_str(), risk), ("Adds a login form.", Some(RiskLevel::High)));
+    /// ```
+    pub fn split_rating(explanation: &str) -> (String, Option<RiskLevel>) {
+        let text = explanation.trim_end();
+        let (rest, last) = text.rsplit_once('\n').unwrap_or(("", text));
+        let rating = last.trim().trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+        let risk = rating
+            .strip_prefix("risk")
+            .map(|level| level.trim_start_matches(|c: char| !c.is_alphanumeric()))
+            .and_then(|level| match level.split_whitespace().next()? {
+                "high" => Some(RiskLevel::High),
+                "medium" => Some(RiskLevel::Medium),
+                "low" => Some(RiskLevel::Low),
+                _ => None,
+            });
+        match risk {
+            Some(risk) => (rest.trim_end().to_string(), Some(risk)),
+            None => (explanation.to_string(), None),
+        }
+    }
+}
+
+/// A file on its way through `Config::analyze_file_diffs`
+struct PendingAnalysis {
+    path: String,
+    change: git::ChangeKind,
+    hunks: Vec<git::Hunk>,
+    is_binary: bool,
+    byte_size: Option<u64>,
+    /// What to show instead of an explanation for a file that isn't sent
+    skipped: Option<String>,
+    /// The explanation of each hunk, or of the whole file, as they arrive
+    parts: Vec<Option<Result<String, NoahError>>>,
+}
+
+/// A file whose analysis failed; the other files of the batch are unaffected
+#[derive(Debug)]
+pub struct FileAnalysisError {
+    pub path: String,
+    pub error: NoahError,
+}
+
+impl std::fmt::Display for FileAnalysisError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.error)
+    }
+}
+
+impl Error for FileAnalysisError {}
+
+pub type FileAnalysisResult = Result<FileAnalysis, FileAnalysisError>;
+
+/// Files to commit together, with the message for that commit, see `Config::suggest_commit_groups`
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitGroup {
+    pub files: Vec<String>,
+    pub message: CommitMessage,
+}
+
+/// A problem or suggestion found by `Config::review_changes`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewFinding {
+    pub severity: Severity,
+    /// "bug", "style" or "suggestion"
+    #[serde(default)]
+    pub category: String,
+    #[serde(default)]
+    pub file: String,
+    /// Line in the new version of the file, if the finding is about one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    pub comment: String,
+}
+
+impl ReviewFinding {
+    /// One list item, e.g. "- **bug** `src/main.rs:42`: ..."
+    pub fn to_markdown(&self) -> String {
+        let location = match (self.file.trim(), self.line) {
+            ("", _) => String::new(),
+            (file, Some(line)) => format!(" `{}:{}`", file, line),
+            (file, None) => format!(" `{}`", file),
+        };
+        match self.category.trim() {
+            "" => format!("-{}: {}", location, self.comment.trim()),
+            category => format!("- **{}**{}: {}", category, location, self.comment.trim()),
+        }
+    }
+}
+
+/// A group as the model writes it
+#[derive(Deserialize)]
+struct SuggestedGroup {
+    files: Vec<String>,
+    message: String,
+}
+
+/// Built-in conventional commit types as (prefix, emoji, label)
+const DEFAULT_COMMIT_TYPES: [(&str, &str, &str); 7] = [
+    ("feat", "✨", "New feature"),
+    ("fix", "🐛", "Bug fix"),
+    ("docs", "📚", "Documentation"),
+    ("style", "💅", "Formatting"),
+    ("refactor", "♻️", "Code restructure"),
+    ("test", "🧪", "Testing"),
+    ("chore", "🔧", "Maintenance"),
+];
+
+/// A commit type offered when editing the commit type, e.g. `feat` or `PROJ`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitType {
+    pub prefix: String,
+    pub emoji: String,
+    pub label: String,
+}
+
+impl CommitType {
+    pub fn defaults() -> Vec<CommitType> {
+        DEFAULT_COMMIT_TYPES.iter().map(|(prefix, emoji, label)| CommitType {
+            prefix: prefix.to_string(),
+            emoji: emoji.to_string(),
+            label: label.to_string(),
+        }).collect()
+    }
+
+    /// A type with just a prefix, described like the built-in type of that name if there is one
+    pub fn from_prefix(prefix: &str) -> CommitType {
+        Self::defaults().into_iter().find(|t| t.prefix == prefix).unwrap_or_else(|| CommitType {
+            prefix: prefix.to_string(),
+            emoji: String::new(),
+            label: String::new(),
+        })
+    }
+
+    /// Menu entry like `feat: ✨ New feature`, without the emoji in the plain style
+    pub fn menu_label(&self, style: MessageStyle) -> String {
+        let emoji = if style == MessageStyle::Plain { "" } else { self.emoji.as_str() };
+        let description = [emoji, self.label.as_str()]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if description.is_empty() {
+            self.prefix.clone()
+        } else {
+            format!("{}: {}", self.prefix, description)
+        }
+    }
+}
+
+/// How commit types appear in the commit type menu and in commit messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageStyle {
+    /// No emoji anywhere, for commit lint rules that reject them
+    Plain,
+    /// The type's emoji in front of the message, e.g. `✨ feat: add login`
+    Gitmoji,
+    /// Just the type, e.g. `feat: add login`, with emoji only in the menu
+    #[default]
+    Conventional,
+}
+
+/// Ticket ids like `PROJ-123`, as found in branch names such as `PROJ-123/feature`
+pub const DEFAULT_TICKET_PATTERN: &str = r"[A-Z][A-Z0-9]+-[0-9]+";
+
+/// Separators left over around a `{ticket}` placeholder when there is no ticket
+const TEMPLATE_SEPARATORS: &[char] = &[' ', ':', '-', '|', '/', ',', '#', '(', ')', '[', ']', '\n'];
+
+/// Where the text introducing a `{ticket}` placeholder starts, looking back from it
+const TICKET_BOUNDARIES: &[char] = &[':', '|', ',', '/', '(', '['];
+
+/// A `commit_template` such as `"{ticket}: {message}"`, where `{message}` is the generated
+/// summary and `{ticket}` is found in the branch name with `ticket_pattern`
+#[derive(Debug, Clone)]
+pub struct CommitTemplate {
+    template: String,
+    ticket_pattern: regex::Regex,
+}
+
+impl CommitTemplate {
+    /// Uses `DEFAULT_TICKET_PATTERN` without a `ticket_pattern`. If the pattern has a
+    /// capture group, the ticket is what the first group matches.
+    pub fn new(template: &str, ticket_pattern: Option<&str>) -> Result<Self, regex::Error> {
+        Ok(Self {
+            template: template.to_string(),
+            ticket_pattern: regex::Regex::new(ticket_pattern.unwrap_or(DEFAULT_TICKET_PATTERN))?,
+        })
+    }
+
+    /// The ticket id in `branch`, if any
+    pub fn ticket(&self, branch: &str) -> Option<String> {
+        let captures = self.ticket_pattern.captures(branch)?;
+        captures.get(1).or_else(|| captures.get(0)).map(|ticket| ticket.as_str().to_string())
+    }
+
+    /// Puts `summary` into the template. Without a ticket the placeholder is left out along
+    /// with the text introducing it, back to the nearest `:`, `|`, `,`, `/` or opening
+    /// bracket, and then any empty brackets and leftover separators. So `"{ticket}: {message}"`
+    /// and `"Ticket {ticket}: {message}"` give just the summary, `"feat({ticket}): {message}"`
+    /// gives `feat: ` and the summary, and lines holding only the ticket, like
+    /// `"Refs: {ticket}"`, are dropped. A summary that already mentions the ticket is
+    /// returned as is.
+    pub fn apply(&self, summary: &str, ticket: Option<&str>) -> String {
+        if ticket.is_some_and(|ticket| summary.contains(ticket)) {
+            return summary.to_string();
+        }
+        let (before, after) = self.parts(ticket);
+        format!("{}{}{}", before, summary, after)
+    }
+
+    /// Undoes `apply` for the same ticket
+    pub fn strip<'a>(&self, summary: &'a str, ticket: Option<&str>) -> &'a str {
+        let (before, after) = self.parts(ticket);
+        let summary = summary.strip_prefix(before.as_str()).unwrap_or(summary);
+        summary.strip_suffix(after.as_str()).unwrap_or(summary)
+    }
+
+    /// The template before and after `{message}`, with the ticket filled in
+    fn parts(&self, ticket: Option<&str>) -> (String, String) {
+        let template = match ticket {
+            Some(ticket) => self.template.replace("{ticket}", ticket),
+            None => template_without_ticket(&self.template),
+        };
+        let (before, after) = template.split_once("{message}").unwrap_or((template.as_str(), ""));
+        let leftover = |part: &str| part.trim_matches(TEMPLATE_SEPARATORS).is_empty();
+        match ticket {
+            Some(_) => (before.to_string(), after.to_string()),
+            None => (
+                if leftover(before) { String::new() } else { before.trim_start().to_string() },
+                if leftover(after) { String::new() } else { after.trim_end().to_string() },
+            ),
+        }
+    }
+}
+
+/// `template` with its `{ticket}` placeholders left out, see `CommitTemplate::apply`
+fn template_without_ticket(template: &str) -> String {
+    let lines: Vec<String> = template
+        .split('\n')
+        .filter(|line| line.contains("{message}") || !line.contains("{ticket}"))
+        .map(|line| {
+            let mut line = line.to_string();
+            while let Some(at) = line.find("{ticket}") {
+                let message_end = line.find("{message}").map(|at| at + "{message}".len()).filter(|&end| end <= at).unwrap_or(0);
+                let start = line[..at].rfind(TICKET_BOUNDARIES).map(|boundary| boundary + 1).unwrap_or(0).max(message_end);
+                line.replace_range(start..at + "{ticket}".len(), "");
+                line = line.replace("[]", "").replace("()", "").replace("  ", " ");
+            }
+            line
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// A commit message split into its conventional parts
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CommitMessage {
+    pub summary: String,
+    pub body: Option<String>,
+    pub footer: Option<String>,
+}
+
+impl CommitMessage {
+    /// Parses raw model output into summary, body and footer.
+    /// The footer is the last paragraph when every line in it is a git trailer.
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        let (summary, rest) = text.split_once('\n').unwrap_or((text, ""));
+
+        let mut paragraphs: Vec<String> = rest
+            .split("\n\n")
+            .map(|p| p.trim_matches('\n').trim_end().to_string())
+            .filter(|p| !p.trim().is_empty())
+            .collect();
+
+        let footer = match paragraphs.last() {
+            Some(last) if last.lines().all(is_trailer) => paragraphs.pop(),
+            _ => None,
+        };
+        let body = if paragraphs.is_empty() { None } else { Some(paragraphs.join("\n\n")) };
+
+        Self {
+            summary: summary.trim().to_string(),
+            body,
+            footer,
+        }
+    }
+
+    /// Formats the summary in `style`: any emoji (or `:shortcode:`) the model put in front is
+    /// removed, and the gitmoji style puts the emoji of the summary's type there instead
+    pub fn with_style(self, style: MessageStyle, commit_types: &[CommitType]) -> Self {
+        let summary = strip_leading_emoji(&self.summary);
+        let emoji = match style {
+            MessageStyle::Gitmoji => commit_types
+                .iter()
+                .find(|t| !t.emoji.is_empty() && summary_type(summary) == Some(t.prefix.as_str()))
+                .map(|t| t.emoji.as_str()),
+            MessageStyle::Plain | MessageStyle::Conventional => None,
+        };
+        Self {
+            summary: match emoji {
+                Some(emoji) => format!("{} {}", emoji, summary),
+                None => summary.to_string(),
+            },
+            ..self
+        }
+    }
+
+    /// Replaces the `Co-authored-by:` trailers in the footer with one per entry of
+    /// `co_authors` (`"Name <email>"`), keeping every other trailer
+    pub fn with_co_authors(self, co_authors: &[String]) -> Self {
+        let mut trailers: Vec<String> = self.footer
+            .iter()
+            .flat_map(|footer| footer.lines())
+            .filter(|line| !line.to_lowercase().starts_with("co-authored-by:"))
+            .map(str::to_string)
+            .collect();
+        trailers.extend(co_authors.iter().map(|co_author| format!("Co-authored-by: {}", co_author)));
+        Self {
+            footer: if trailers.is_empty() { None } else { Some(trailers.join("\n")) },
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Display for CommitMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.summary)?;
+        if let Some(body) = &self.body {
+            write!(f, "\n\n{}", body)?;
+        }
+        if let Some(footer) = &self.footer {
+            write!(f, "\n\n{}", footer)?;
+        }
+        Ok(())
+    }
+}
+
+/// A pull request description, see `Config::generate_pr_description`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PullRequestDescription {
+    pub title: String,
+    /// Markdown with a summary, the changes and testing notes
+    pub body: String,
+}
+
+impl PullRequestDescription {
+    /// Parses raw model output: the first line is the title, without any `#` or `Title:`
+    /// the model put in front, and the rest is the body
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        let (title, body) = text.split_once('\n').unwrap_or((text, ""));
+        let title = title.trim().trim_start_matches('#').trim();
+        let title = title.strip_prefix("Title:").unwrap_or(title).trim();
+        Self {
+            title: title.trim_matches(|c| c == '*' || c == '`' || c == '"').trim().to_string(),
+            body: body.trim().to_string(),
+        }
+    }
+
+    /// The title as a heading above the body
+    pub fn to_markdown(&self) -> String {
+        format!("# {}\n\n{}\n", self.title, self.body)
+    }
+}
+
+/// Matches `Token: value` and `Token #value` trailers, including `BREAKING CHANGE: ...`
+fn is_trailer(line: &str) -> bool {
+    if line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:") {
+        return true;
+    }
+    let token_end = line.find(": ").or_else(|| line.find(" #"));
+    match token_end {
+        Some(end) => {
+            let token = &line[..end];
+            !token.is_empty() && token.chars().all(|c| c.is_alphanumeric() || c == '-')
+        }
+        None => false,
+    }
+}
+
+/// A style problem in a commit message. None of them stop the message from being committed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LintWarning {
+    SubjectTooLong { length: usize, max: usize },
+    /// The first word of the description, e.g. "Added"
+    NotImperative(String),
+    TrailingPeriod,
+    MissingBlankLine,
+    /// 1-based line number within the message
+    BodyLineTooLong { line: usize, length: usize },
+    /// A type the repository's commitlint config doesn't allow, e.g. "feature"
+    TypeNotAllowed(String),
+}
+
+impl LintWarning {
+    /// Whether the problem is in the subject line, which auto-fix rewrites
+    pub fn is_subject(&self) -> bool {
+        matches!(self, LintWarning::SubjectTooLong { .. } | LintWarning::NotImperative(_) | LintWarning::TrailingPeriod)
+    }
+}
+
+impl std::fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintWarning::SubjectTooLong { length, max } => write!(f, "Subject is {} characters long; keep it to {} or less", length, max),
+            LintWarning::NotImperative(word) => write!(f, "Subject should use imperative mood (\"{}\" reads like a description)", word),
+            LintWarning::TrailingPeriod => write!(f, "Subject shouldn't end with a period"),
+            LintWarning::MissingBlankLine => write!(f, "Leave a blank line between the subject and the body"),
+            LintWarning::BodyLineTooLong { line, length } => write!(f, "Line {} is {} characters long; wrap the body at {}", line, length, MAX_BODY_LINE_CHARS),
+            LintWarning::TypeNotAllowed(commit_type) => write!(f, "commitlint doesn't allow the type {}; pick another under \"Edit commit type\"", commit_type),
+        }
+    }
+}
+
+/// A commit summary naming the changed files, typed by what happened to them
+fn summary_from_files(files: &[(String, git::ChangeKind)]) -> String {
+    let file_name = |path: &str| std::path::Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path)
+        .to_string();
+    if let [(_, git::ChangeKind::Renamed { from, to })] = files {
+        return format!("refactor: rename {} to {}", file_name(from), file_name(to));
+    }
+    let all = |kind: fn(&git::ChangeKind) -> bool| files.iter().all(|(_, change)| kind(change));
+    let (commit_type, verb) = if all(|change| matches!(change, git::ChangeKind::Added)) {
+        ("feat", "add")
+    } else if all(|change| matches!(change, git::ChangeKind::Deleted)) {
+        ("chore", "remove")
+    } else if all(|change| matches!(change, git::ChangeKind::Modified)) {
+        ("fix", "update")
+    } else {
+        ("chore", "update")
+    };
+    let names: Vec<String> = files.iter().map(|(path, _)| file_name(path)).collect();
+    let listed = match names.as_slice() {
+        [name] => name.clone(),
+        [first, second] => format!("{} and {}", first, second),
+        [first, second, third] => format!("{}, {} and {}", first, second, third),
+        [first, second, rest @ ..] => format!("{}, {} and {} more files", first, second, rest.len()),
+        [] => String::new(),
+    };
+    format!("{}: {} {}", commit_type, verb, listed)
+}
+
+/// Puts the user's instruction ahead of the diff, where the model reads it as part of the task
+fn with_hint(diff: &str, hint: Option<&str>) -> String {
+    match hint.map(str::trim).filter(|hint| !hint.is_empty()) {
+        Some(hint) => format!("Instruction from the user for this commit message: {}\n\n{}", hint, diff),
+        None => diff.to_string(),
+    }
+}
+
+/// The bullet or number starting a list item, with the space after it, e.g. "- " or "2. "
+fn list_marker(line: &str) -> Option<&str> {
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    let len = match line.as_bytes() {
+        [b'-' | b'*' | b'+', b' ', ..] => 2,
+        bytes if digits > 0 && bytes.get(digits) == Some(&b'.') && bytes.get(digits + 1) == Some(&b' ') => digits + 2,
+        _ => return None,
+    };
+    Some(&line[..len])
+}
+
+/// `summary` without leading emoji, e.g. `✨ feat: add login` or `:sparkles: feat: add login`
+fn strip_leading_emoji(summary: &str) -> &str {
+    let mut rest = summary.trim_start();
+    loop {
+        let without_glyphs = rest.trim_start_matches(|c: char| !c.is_ascii() && !c.is_alphanumeric()).trim_start();
+        let without_shortcode = match without_glyphs.strip_prefix(':').and_then(|tail| tail.split_once(':')) {
+            Some((name, tail)) if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') => tail.trim_start(),
+            _ => without_glyphs,
+        };
+        if without_shortcode.len() == rest.len() {
+            return rest;
+        }
+        rest = without_shortcode;
+    }
+}
+
+/// The conventional commit type of a summary, e.g. `feat` for `feat(ui)!: add login`
+fn summary_type(summary: &str) -> Option<&str> {
+    let (prefix, _) = summary.split_once(':')?;
+    let commit_type = prefix.split(['(', '!']).next()?.trim();
+    (!commit_type.is_empty() && !commit_type.contains(char::is_whitespace)).then_some(commit_type)
+}
+
+/// Whether `word` looks like past tense, a gerund or third person ("Added", "Adding", "Adds")
+/// rather than imperative mood. Only a heuristic, so common exceptions are let through.
+fn looks_non_imperative(word: &str) -> bool {
+    let word = word.to_lowercase();
+    const EXCEPTIONS: [&str; 8] = ["need", "seed", "feed", "embed", "bring", "process", "focus", "bus"];
+    if word.len() < 4 || EXCEPTIONS.contains(&word.as_str()) {
+        return false;
+    }
+    word.ends_with("ed")
+        || word.ends_with("ing")
+        || (word.ends_with('s') && !word.ends_with("ss") && !word.ends_with("us") && !word.ends_with("is"))
+}
+
+impl Config {
+    pub fn new(model: Box<dyn git_analysis::GitAnalyzer>, repo_path: Option<String>) -> Self {
+        Self { 
+            model_name: default_model_name(model.as_ref()),
+            mode_models: HashMap::new(),
+            model,
+            repo_path: repo_path.unwrap_or_else(|| ".".to_string()),
+            dry_run: false,
+            exclude_globs: Vec::new(),
+            retry: retry::RetryPolicy::default(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            mode_timeouts: HashMap::new(),
+            generation: providers::GenerationOptions::default(),
+            mode_generation: HashMap::new(),
+            max_diff_bytes: DEFAULT_MAX_DIFF_BYTES,
+            diff_context_lines: git::DEFAULT_CONTEXT_LINES,
+            use_cache: true,
+            cache_responses: None,
+            cache_ttl: DEFAULT_CACHE_TTL,
+            token_threshold: DEFAULT_TOKEN_THRESHOLD,
+            min_diff_lines: 0,
+            commit_types: CommitType::defaults(),
+            types_enforced: false,
+            max_subject_chars: MAX_SUBJECT_CHARS,
+            message_style: MessageStyle::default(),
+            body_wrap: MAX_BODY_LINE_CHARS,
+            commit_template: None,
+            co_authors: Vec::new(),
+            scan_secrets: true,
+            system_prompt_prefix: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            hunk_level_analysis: false,
+            candidates: DEFAULT_CANDIDATES,
+            recent_commits_shown: DEFAULT_RECENT_COMMITS_SHOWN,
+            clear_between_contributors: true,
+            last_commit_type: std::sync::Mutex::new(None),
+            session_commit: std::sync::Mutex::new(None),
+            output_format: cli::OutputFormat::default(),
+            revision: None,
+            base_branch: None,
+            against_base: false,
+            only_file: None,
+            failure_log: None,
+            min_severity: Severity::default(),
+            min_risk: RiskLevel::default(),
+            hook_action: hook::HookAction::default(),
+            overwrite: false,
+            quiet: false,
+            default_mode: None,
+            usage: std::sync::Arc::new(std::sync::Mutex::new(SessionUsage::default())),
+            usage_summary: false,
+            has_provider: true,
+            project_summary: std::sync::OnceLock::new(),
+            file_prompts: git_analysis::FILE_PROMPTS.iter().map(|(ext, prompt)| (ext.to_string(), prompt.to_string())).collect(),
+        }
+    }
+
+    /// A config for when no provider is set up. Anything that doesn't need AI works;
+    /// AI requests fail with `ProviderError::NoProvidersAvailable`.
+    pub fn without_provider(repo_path: Option<String>) -> Self {
+        Self {
+            has_provider: false,
+            ..Self::new(git_analysis::wrap_provider(Box::new(providers::UnavailableProvider)), repo_path)
+        }
+    }
+
+    /// Builds a config for `repo_path` from the user and repo config files. The configured
+    /// provider is used if set, otherwise the first one with an API key. Without any, the
+    /// config is `without_provider`.
+    pub fn load(repo_path: &str) -> Result<Config, NoahError> {
+        let settings = load_settings(repo_path)?;
+        let provider = match &settings.provider {
+            Some(name) => Some(configured_provider(name)?),
+            None => providers::get_available_providers().into_iter().next(),
+        };
+        let config = match provider {
+            Some(provider) => Config::new(git_analysis::wrap_provider(provider), Some(repo_path.to_string())),
+            None => Config::without_provider(Some(repo_path.to_string())),
+        };
+        let config = with_commitlint(config).with_settings(&settings);
+        check_configured_model(&config, &settings)?;
+        with_env_models(config)
+    }
+
+    /// First run setup: picks a provider and model, asking for an API key if none is
+    /// set, checks that the provider answers and writes them to the user config file.
+    /// `load` picks the written settings up afterwards.
+    pub(crate) async fn run_setup_wizard(repo: &Repository) -> Result<(), Box<dyn Error>> {
+        setup::run_wizard(repo).await
+    }
+
+    /// Fails with a message saying how to set up a provider if there is none, so that
+    /// modes needing AI stop before doing any work
+    pub fn require_provider(&self) -> Result<(), NoahError> {
+        if self.has_provider {
+            Ok(())
+        } else {
+            Err(NoahError::Ai(Box::new(providers::ProviderError::NoProvidersAvailable)))
+        }
+    }
+
+    /// Applies the values set in a config file, keeping the current ones for everything else
+    pub fn with_settings(self, settings: &config_file::ConfigFile) -> Self {
+        let mut file_prompts = self.file_prompts.clone();
+        file_prompts.extend(settings.file_prompts.iter().map(|(ext, prompt)| (ext.to_lowercase(), prompt.clone())));
+        let last_commit_type = settings.last_commit_type.clone().or_else(|| self.last_commit_type());
+        let mut retry = self.retry.clone();
+        if let Some(file_retry) = &settings.retry {
+            retry.max_attempts = file_retry.max_attempts.unwrap_or(retry.max_attempts);
+            retry.base_delay = file_retry.base_delay_ms.map(std::time::Duration::from_millis).unwrap_or(retry.base_delay);
+            retry.jitter = file_retry.jitter_ms.map(std::time::Duration::from_millis).unwrap_or(retry.jitter);
+        }
+        let mut mode_timeouts = self.mode_timeouts.clone();
+        mode_timeouts.extend(settings.mode_timeouts().into_iter().map(|(mode, secs)| (mode, std::time::Duration::from_secs(secs))));
+        let mut mode_generation = self.mode_generation.clone();
+        for (mode, options) in settings.mode_generation() {
+            let current = mode_generation.get(&mode).copied().unwrap_or_default();
+            mode_generation.insert(mode, options.or(current));
+        }
+        let generation = providers::GenerationOptions {
+            temperature: settings.temperature,
+            max_tokens: settings.max_tokens,
+        };
+        let system_prompt_prefix = settings.system_prompt_prefix.clone().or_else(|| self.system_prompt_prefix.clone());
+        let config = Self {
+            model_name: settings.model.clone().unwrap_or(self.model_name),
+            exclude_globs: settings.exclude.clone().unwrap_or(self.exclude_globs),
+            commit_types: settings.commit_types().filter(|types| !types.is_empty()).unwrap_or(self.commit_types),
+            types_enforced: self.types_enforced && settings.commit_types.is_none(),
+            message_style: settings.message_style.unwrap_or(self.message_style),
+            body_wrap: settings.body_wrap.unwrap_or(self.body_wrap),
+            // The pattern was checked when the config file was read
+            commit_template: settings.commit_template.as_deref()
+                .and_then(|template| CommitTemplate::new(template, settings.ticket_pattern.as_deref()).ok())
+                .or(self.commit_template),
+            co_authors: settings.co_authors.clone().unwrap_or(self.co_authors),
+            max_diff_bytes: settings.max_diff_bytes.unwrap_or(self.max_diff_bytes),
+            diff_context_lines: settings.diff_context_lines.unwrap_or(self.diff_context_lines),
+            token_threshold: settings.token_threshold.unwrap_or(self.token_threshold),
+            min_diff_lines: settings.min_diff_lines.unwrap_or(self.min_diff_lines),
+            use_cache: settings.cache.unwrap_or(self.use_cache),
+            cache_responses: settings.cache_responses.or(self.cache_responses),
+            cache_ttl: settings.cache_ttl_hours.map(|hours| std::time::Duration::from_secs(hours * 60 * 60)).unwrap_or(self.cache_ttl),
+            scan_secrets: settings.scan_secrets.unwrap_or(self.scan_secrets),
+            max_concurrency: settings.max_concurrency.unwrap_or(self.max_concurrency),
+            hunk_level_analysis: settings.hunk_level_analysis.unwrap_or(self.hunk_level_analysis),
+            candidates: settings.candidates.unwrap_or(self.candidates),
+            recent_commits_shown: settings.recent_commits_shown.map_or(self.recent_commits_shown, |count| count.clamp(1, MAX_RECENT_COMMITS_SHOWN)),
+            clear_between_contributors: settings.clear_between_contributors.unwrap_or(self.clear_between_contributors),
+            quiet: settings.quiet.unwrap_or(self.quiet),
+            default_mode: settings.default_mode().or(self.default_mode),
+            base_branch: settings.base_branch.clone().or(self.base_branch),
+            usage_summary: settings.usage_summary.unwrap_or(self.usage_summary),
+            last_commit_type: std::sync::Mutex::new(last_commit_type),
+            request_timeout: settings.request_timeout.map(std::time::Duration::from_secs).unwrap_or(self.request_timeout),
+            file_prompts,
+            retry,
+            mode_timeouts,
+            generation: generation.or(self.generation),
+            mode_generation,
+            ..self
+        };
+        config.with_system_prompt_prefix(system_prompt_prefix).with_prompted_commit_rules()
+    }
+
+    pub fn with_new_model(self, mut model: Box<dyn git_analysis::GitAnalyzer>) -> Self {
+        model.set_system_prompt_prefix(self.system_prompt_prefix.clone());
+        model.set_commit_rules(self.commit_rules());
+        Self {
+            model_name: default_model_name(model.as_ref()),
+            mode_models: HashMap::new(),
+            model,
+            has_provider: true,
+            ..self
+        }
+    }
+
+    pub fn with_new_repo(self, repo_path: String) -> Self {
+        Self {
+            repo_path,
+            session_commit: std::sync::Mutex::new(None),
+            project_summary: std::sync::OnceLock::new(),
+            ..self
+        }
+    }
+
+    /// In dry-run mode nothing is ever staged or committed
+    pub fn with_dry_run(self, dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            ..self
+        }
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// How headless runs print their results
+    pub fn with_output_format(self, output_format: cli::OutputFormat) -> Self {
+        Self {
+            output_format,
+            ..self
+        }
+    }
+
+    pub fn output_format(&self) -> cli::OutputFormat {
+        self.output_format
+    }
+
+    /// Plain output without spinners or decorated headers; see `ui::set_quiet`
+    pub fn with_quiet(self, quiet: bool) -> Self {
+        Self {
+            quiet,
+            ..self
+        }
+    }
+
+    pub fn is_quiet(&self) -> bool {
+        self.quiet
+    }
+
+    /// Gitignore-style globs for files that file analysis should skip
+    pub fn with_exclude_globs(self, exclude_globs: Vec<String>) -> Self {
+        Self {
+            exclude_globs,
+            ..self
+        }
+    }
+
+    /// How transient AI request failures are retried
+    pub fn with_retry_policy(self, retry: retry::RetryPolicy) -> Self {
+        Self {
+            retry,
+            ..self
+        }
+    }
+
+    /// How long each AI request may take, unless the mode has its own timeout
+    pub fn with_request_timeout(self, request_timeout: std::time::Duration) -> Self {
+        Self {
+            request_timeout,
+            ..self
+        }
+    }
+
+    /// Gives the requests made by `mode` a timeout of their own
+    pub fn with_mode_timeout(self, mode: modes::Mode, timeout: std::time::Duration) -> Self {
+        let mut mode_timeouts = self.mode_timeouts;
+        mode_timeouts.insert(mode, timeout);
+        Self {
+            mode_timeouts,
+            ..self
+        }
+    }
+
+    pub fn request_timeout(&self, mode: modes::Mode) -> std::time::Duration {
+        self.mode_timeouts.get(&mode).copied().unwrap_or(self.request_timeout)
+    }
+
+    /// Temperature for every AI request, unless the mode has its own. Values the provider
+    /// doesn't accept are clamped to its range; see `generation_warnings`.
+    pub fn with_temperature(self, temperature: f32) -> Self {
+        Self {
+            generation: providers::GenerationOptions { temperature: Some(temperature), ..self.generation },
+            ..self
+        }
+    }
+
+    /// Most tokens a response may have, unless the mode has its own limit
+    pub fn with_max_tokens(self, max_tokens: u32) -> Self {
+        Self {
+            generation: providers::GenerationOptions { max_tokens: Some(max_tokens), ..self.generation },
+            ..self
+        }
+    }
+
+    /// Gives the requests made by `mode` generation settings of their own.
+    /// Whatever `options` leaves unset comes from the settings for every request.
+    pub fn with_mode_generation(self, mode: modes::Mode, options: providers::GenerationOptions) -> Self {
+        let mut mode_generation = self.mode_generation;
+        mode_generation.insert(mode, options);
+        Self {
+            mode_generation,
+            ..self
+        }
+    }
+
+    /// The generation settings for requests made by `mode`, with the temperature
+    /// clamped to the range the current provider accepts
+    pub fn generation_options(&self, mode: modes::Mode) -> providers::GenerationOptions {
+        let options = self.mode_generation.get(&mode).copied().unwrap_or_default().or(self.generation);
+        let range = self.model.temperature_range();
+        providers::GenerationOptions {
+            temperature: options.temperature.map(|temperature| temperature.clamp(*range.start(), *range.end())),
+            ..options
+        }
+    }
+
+    /// One line for each configured temperature the current provider doesn't accept,
+    /// saying what is sent instead
+    pub fn generation_warnings(&self) -> Vec<String> {
+        if !self.has_provider {
+            return Vec::new();
+        }
+        let range = self.model.temperature_range();
+        let (min, max) = (*range.start(), *range.end());
+        let mut configured: Vec<(String, f32)> = self.mode_generation.iter()
+            .filter_map(|(mode, options)| {
+                let name = clap::ValueEnum::to_possible_value(mode)?.get_name().to_string();
+                Some((format!("temperature for {}", name), options.temperature?))
+            })
+            .collect();
+        configured.sort_by(|a, b| a.0.cmp(&b.0));
+        configured.extend(self.generation.temperature.map(|temperature| ("temperature".to_string(), temperature)));
+
+        configured.into_iter()
+            .filter(|(_, temperature)| !range.contains(temperature))
+            .map(|(setting, temperature)| format!(
+                "The {} {} is outside the range {} accepts ({}–{}); using {} instead",
+                setting, temperature, self.model.name(), min, max, temperature.clamp(min, max)
+            ))
+            .collect()
+    }
+
+    /// Runs `operation` with the retry policy, cancelling each attempt after the timeout for `mode`
+    async fn request<T, F, Fut>(&self, mode: modes::Mode, mut operation: F) -> Result<T, NoahError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, Box<dyn Error>>>,
+    {
+        let timeout = self.request_timeout(mode);
+        log::info!("{:?} request to {} using {}", mode, self.model.name(), self.model_for(mode));
+        let started = std::time::Instant::now();
+        let provider = self.model.name();
+        let result = retry::with_retry(&self.retry, || {
+            let attempt = operation();
+            // Waiting for the rate limit doesn't count towards the timeout
+            async move {
+                let _permit = providers::rate_limit::acquire(provider).await?;
+                retry::with_timeout(timeout, attempt).await
+            }
+        }).await;
+        match &result {
+            Ok(_) => log::info!("{:?} request finished after {:.1?}", mode, started.elapsed()),
+            Err(e) => log::info!("{:?} request failed after {:.1?}: {}", mode, started.elapsed(), e),
+        }
+        result.map_err(NoahError::ai)
+    }
+
+    /// `request` for a response that may come from the response cache, see
+    /// `caches_responses`. `input` is everything the answer depends on besides the mode,
+    /// model and generation settings, starting with the kind of request when a mode makes
+    /// several.
+    async fn cached_request<F, Fut>(&self, mode: modes::Mode, input: &[&str], operation: F) -> Result<String, NoahError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<String, Box<dyn Error>>>,
+    {
+        self.request_through_cache(mode, input, false, operation).await
+    }
+
+    /// `cached_request` for an explicit "regenerate": the model is always asked, and its
+    /// answer replaces the cached one
+    async fn fresh_request<F, Fut>(&self, mode: modes::Mode, input: &[&str], operation: F) -> Result<String, NoahError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<String, Box<dyn Error>>>,
+    {
+        self.request_through_cache(mode, input, true, operation).await
+    }
+
+    /// Nothing is stored in dry-run mode, so a dry run leaves the repository untouched
+    async fn request_through_cache<F, Fut>(&self, mode: modes::Mode, input: &[&str], fresh: bool, operation: F) -> Result<String, NoahError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<String, Box<dyn Error>>>,
+    {
+        let Some((cache, key)) = self.response_cache(mode, input) else {
+            let response = self.request(mode, operation).await?;
+            self.record_request(input, &response);
+            return Ok(response);
+        };
+        if let Some(response) = cache.get(&key).filter(|_| !fresh) {
+            log::info!("{:?} request answered from the response cache", mode);
+            return Ok(response);
+        }
+        let response = self.request(mode, operation).await?;
+        self.record_request(input, &response);
+        if self.dry_run {
+            return Ok(response);
+        }
+        if let Err(e) = cache.put(&key, &response) {
+            log::info!("Couldn't cache the response: {}", e);
+        }
+        Ok(response)
+    }
+
+    /// The response cache of the repository and the key for a request of `mode` with
+    /// `input`, if `mode` is cached. A patch outside a repository has no cache.
+    fn response_cache(&self, mode: modes::Mode, input: &[&str]) -> Option<(response_cache::ResponseCache, String)> {
+        if !self.caches_responses(mode) {
+            return None;
+        }
+        let repo = Repository::open(&self.repo_path).ok()?;
+        let cache = response_cache::ResponseCache::open(repo.path(), self.cache_ttl);
+        let settings = format!("{:?} {:?} {:?}", mode, self.generation_options(mode), self.commit_rules());
+        let mut parts = vec![
+            env!("CARGO_PKG_VERSION"),
+            git_analysis::PROMPT_VERSION,
+            self.model.name(),
+            self.model_for(mode),
+            self.system_prompt_prefix.as_deref().unwrap_or(""),
+            &settings,
+        ];
+        parts.extend_from_slice(input);
+        Some((cache, response_cache::ResponseCache::key(&parts)))
+    }
+
+    /// The current provider's request quota, as far as its responses tell
+    pub fn rate_limit(&self) -> providers::RateLimitStatus {
+        providers::rate_limit::status(self.model.name())
+    }
+
+    /// How many commit messages to generate at once and pick from
+    pub fn with_candidates(self, candidates: usize) -> Self {
+        Self {
+            candidates: candidates.max(1),
+            ..self
+        }
+    }
+
+    pub fn candidates(&self) -> usize {
+        self.candidates
+    }
+
+    /// The mode an interactive session runs right away. With `DefaultMode::Auto` that is
+    /// the commit message mode when changes are staged; without one the menu is shown.
+    /// Later visits to the menu start on this mode.
+    pub fn with_default_mode(self, default_mode: Option<modes::DefaultMode>) -> Self {
+        Self { default_mode, ..self }
+    }
+
+    pub fn default_mode(&self) -> Option<modes::DefaultMode> {
+        self.default_mode
+    }
+
+    /// Whether an interactive session ends with a line counting what was asked of the
+    /// model, see `usage`
+    pub fn with_usage_summary(self, usage_summary: bool) -> Self {
+        Self { usage_summary, ..self }
+    }
+
+    pub fn usage_summary(&self) -> bool {
+        self.usage_summary
+    }
+
+    /// What this session has asked of the model so far
+    pub fn usage(&self) -> SessionUsage {
+        self.usage.lock().map(|usage| usage.clone()).unwrap_or_default()
+    }
+
+    fn record_usage(&self, record: impl FnOnce(&mut SessionUsage)) {
+        if let Ok(mut usage) = self.usage.lock() {
+            record(&mut usage);
+        }
+    }
+
+    /// Counts a request the model answered, with the tokens of what was sent and received
+    fn record_request(&self, sent: &[&str], received: &str) {
+        let tokens = sent.iter().chain([&received]).map(|text| Self::estimate_tokens(text)).sum::<usize>();
+        self.record_usage(|usage| {
+            usage.requests += 1;
+            usage.tokens += tokens;
+        });
+    }
+
+    /// How many of a contributor's latest commits are listed and sent with their
+    /// statistics, from 1 to `MAX_RECENT_COMMITS_SHOWN`
+    pub fn with_recent_commits_shown(self, recent_commits_shown: usize) -> Self {
+        Self {
+            recent_commits_shown: recent_commits_shown.clamp(1, MAX_RECENT_COMMITS_SHOWN),
+            ..self
+        }
+    }
+
+    pub fn recent_commits_shown(&self) -> usize {
+        self.recent_commits_shown
+    }
+
+    /// Whether the screen is cleared after each contributor's analysis; otherwise a line
+    /// separates it from the next, keeping earlier ones in the scrollback
+    pub fn with_clear_between_contributors(self, clear_between_contributors: bool) -> Self {
+        Self {
+            clear_between_contributors,
+            ..self
+        }
+    }
+
+    pub fn clear_between_contributors(&self) -> bool {
+        self.clear_between_contributors
+    }
+
+    /// Largest diff, in bytes, sent to the model in a single request
+    pub fn with_max_diff_bytes(self, max_diff_bytes: usize) -> Self {
+        Self {
+            max_diff_bytes,
+            ..self
+        }
+    }
+
+    pub fn max_diff_bytes(&self) -> usize {
+        self.max_diff_bytes
+    }
+
+    /// Unchanged lines shown around each change in the diffs sent to the model, 3 like git
+    /// by default. A wider window, e.g. 10, lets the model follow a refactoring, but every
+    /// hunk grows by up to twice as many lines, and so does the token count.
+    pub fn with_diff_context_lines(self, diff_context_lines: u32) -> Self {
+        Self {
+            diff_context_lines,
+            ..self
+        }
+    }
+
+    pub fn diff_context_lines(&self) -> u32 {
+        self.diff_context_lines
+    }
+
+    /// Whether contributor statistics and AI responses may be read from and written to the
+    /// on-disk caches
+    pub fn with_cache(self, use_cache: bool) -> Self {
+        Self {
+            use_cache,
+            ..self
+        }
+    }
+
+    pub fn uses_cache(&self) -> bool {
+        self.use_cache
+    }
+
+    /// Whether identical AI requests are answered from the response cache: `Some(true)` for
+    /// every mode, `Some(false)` for none. By default only file analysis is cached, since a
+    /// commit message should be fresh each time it is asked for.
+    pub fn with_cache_responses(self, cache_responses: Option<bool>) -> Self {
+        Self {
+            cache_responses,
+            ..self
+        }
+    }
+
+    /// Whether requests made by `mode` are answered from the response cache
+    pub fn caches_responses(&self, mode: modes::Mode) -> bool {
+        self.use_cache && self.cache_responses.unwrap_or(mode == modes::Mode::FileAnalysis)
+    }
+
+    /// How long a cached response is used before the model is asked again
+    pub fn with_cache_ttl(self, cache_ttl: std::time::Duration) -> Self {
+        Self {
+            cache_ttl,
+            ..self
+        }
+    }
+
+    pub fn cache_ttl(&self) -> std::time::Duration {
+        self.cache_ttl
+    }
+
+    /// Requests estimated above this many tokens need confirmation before they are sent
+    pub fn with_token_threshold(self, token_threshold: usize) -> Self {
+        Self {
+            token_threshold,
+            ..self
+        }
+    }
+
+    pub fn token_threshold(&self) -> usize {
+        self.token_threshold
+    }
+
+    /// How many file analysis requests may run at the same time
+    pub fn with_max_concurrency(self, max_concurrency: usize) -> Self {
+        Self {
+            max_concurrency,
+            ..self
+        }
+    }
+
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+
+    /// Whether files with several hunks get an explanation per hunk, at the cost of a
+    /// request for each
+    pub fn with_hunk_level_analysis(self, hunk_level_analysis: bool) -> Self {
+        Self {
+            hunk_level_analysis,
+            ..self
+        }
+    }
+
+    pub fn hunk_level_analysis(&self) -> bool {
+        self.hunk_level_analysis
+    }
+
+    /// Whether diffs are checked for likely secrets before they are sent to the model
+    pub fn with_secret_scan(self, scan_secrets: bool) -> Self {
+        Self {
+            scan_secrets,
+            ..self
+        }
+    }
+
+    pub fn scans_secrets(&self) -> bool {
+        self.scan_secrets
+    }
+
+    /// Text put before the system prompt of every AI request, e.g. a team's conventions
+    pub fn with_system_prompt_prefix(self, system_prompt_prefix: Option<String>) -> Self {
+        let mut model = self.model;
+        model.set_system_prompt_prefix(system_prompt_prefix.clone());
+        Self {
+            model,
+            system_prompt_prefix,
+            ..self
+        }
+    }
+
+    pub fn system_prompt_prefix(&self) -> Option<&str> {
+        self.system_prompt_prefix.as_deref()
+    }
+
+    /// Conventional commit types offered when editing the commit type
+    pub fn with_commit_types(self, commit_types: Vec<CommitType>) -> Self {
+        Self {
+            commit_types,
+            ..self
+        }
+    }
+
+    pub fn commit_types(&self) -> &[CommitType] {
+        &self.commit_types
+    }
+
+    /// Takes the allowed types and the longest subject line from a commitlint config, so
+    /// generated messages pass the repository's own commit checks. Types configured in
+    /// `commit_types` still win when the settings are applied afterwards.
+    pub fn with_commitlint_rules(self, rules: &commitlint::CommitlintRules) -> Self {
+        let commit_types = rules.types.as_ref().map(|types| types.iter().map(|prefix| CommitType::from_prefix(prefix)).collect());
+        Self {
+            types_enforced: commit_types.is_some() || self.types_enforced,
+            commit_types: commit_types.unwrap_or(self.commit_types),
+            max_subject_chars: rules.header_max_length.unwrap_or(self.max_subject_chars),
+            ..self
+        }
+        .with_prompted_commit_rules()
+    }
+
+    /// Longest subject line the lint check lets through, 50 unless commitlint says otherwise
+    pub fn with_max_subject_chars(self, max_subject_chars: usize) -> Self {
+        Self {
+            max_subject_chars,
+            ..self
+        }
+        .with_prompted_commit_rules()
+    }
+
+    /// The types and subject line length the lint check enforces, as commitlint rules.
+    /// Types are only listed when they come from commitlint.
+    fn commit_rules(&self) -> commitlint::CommitlintRules {
+        commitlint::CommitlintRules {
+            types: self.types_enforced.then(|| self.commit_types.iter().map(|t| t.prefix.clone()).collect()),
+            header_max_length: Some(self.max_subject_chars),
+        }
+    }
+
+    /// Tells the model about `commit_rules`, so generated messages pass the lint check
+    fn with_prompted_commit_rules(self) -> Self {
+        let rules = self.commit_rules();
+        let mut model = self.model;
+        model.set_commit_rules(rules);
+        Self {
+            model,
+            ..self
+        }
+    }
+
+    pub fn max_subject_chars(&self) -> usize {
+        self.max_subject_chars
+    }
+
+    /// Whether commit types carry their emoji, in the commit type menu and in messages
+    pub fn with_message_style(self, message_style: MessageStyle) -> Self {
+        Self {
+            message_style,
+            ..self
+        }
+    }
+
+    pub fn message_style(&self) -> MessageStyle {
+        self.message_style
+    }
+
+    /// Column generated bodies are wrapped at, 72 by default; 0 leaves them unwrapped
+    pub fn with_body_wrap(self, body_wrap: usize) -> Self {
+        Self {
+            body_wrap,
+            ..self
+        }
+    }
+
+    pub fn body_wrap(&self) -> usize {
+        self.body_wrap
+    }
+
+    /// Hard-wraps each line of `text` longer than `width` at word boundaries, keeping
+    /// blank lines, the indentation of list items and everything inside code fences as
+    /// it is. A word longer than `width` gets a line of its own instead of being split.
+    ///
+    /// ```
+    /// use merit_cli_demo::Config;
+    ///
+    /// let body = "Parse the header once instead of per request.\n\n```\nlet header = parse(request.headers(), Options::default());\n```";
+    /// let wrapped = Config::wrap_body(body, 24);
+    /// assert_eq!(wrapped, "Parse the header once\ninstead of per request.\n\n```\nlet header = parse(request.headers(), Options::default());\n```");
+    ///
+    /// let item = Config::wrap_body("- Retry failed uploads with exponential backoff", 24);
+    /// assert_eq!(item, "- Retry failed uploads\n  with exponential\n  backoff");
+    /// ```
+    pub fn wrap_body(text: &str, width: usize) -> String {
+        let mut lines = Vec::new();
+        let mut in_fence = false;
+        for line in text.lines() {
+            let is_fence = line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~");
+            if is_fence {
+                in_fence = !in_fence;
+            }
+            // Indented lines are code too
+            if is_fence || in_fence || width == 0 || line.chars().count() <= width || line.starts_with("    ") || line.starts_with('\t') {
+                lines.push(line.to_string());
+                continue;
+            }
+
+            let content = line.trim_start();
+            let leading = &line[..line.len() - content.len()];
+            // Continuation lines line up with the text after a list marker
+            let marker = list_marker(content).unwrap_or("");
+            let indent = format!("{}{}", leading, " ".repeat(marker.chars().count()));
+            let mut current = format!("{}{}", leading, marker);
+            let mut has_word = false;
+            for word in content[marker.len()..].split_whitespace() {
+                if has_word && current.chars().count() + 1 + word.chars().count() > width {
+                    lines.push(std::mem::replace(&mut current, indent.clone()));
+                    has_word = false;
+                }
+                if has_word {
+                    current.push(' ');
+                }
+                current.push_str(word);
+                has_word = true;
+            }
+            lines.push(current);
+        }
+        lines.join("\n")
+    }
+
+    /// Wraps generated summaries, e.g. in `"{ticket}: {message}"`
+    pub fn with_commit_template(self, commit_template: Option<CommitTemplate>) -> Self {
+        Self {
+            commit_template,
+            ..self
+        }
+    }
+
+    /// Parses a generated message and formats it, see `format_commit_message`
+    pub fn parse_commit_message(&self, text: &str) -> CommitMessage {
+        self.format_commit_message(CommitMessage::parse(text))
+    }
+
+    /// Formats the summary in the configured `MessageStyle` and `commit_template`, and
+    /// wraps the body at `body_wrap`
+    pub fn format_commit_message(&self, message: CommitMessage) -> CommitMessage {
+        let message = message.with_style(self.message_style, &self.commit_types);
+        let message = CommitMessage {
+            body: message.body.map(|body| Self::wrap_body(&body, self.body_wrap)),
+            ..message
+        };
+        match &self.commit_template {
+            Some(template) => CommitMessage {
+                summary: template.apply(&message.summary, self.branch_ticket(template).as_deref()),
+                ..message
+            },
+            None => message,
+        }
+    }
+
+    /// `summary` without what `commit_template` put around it
+    pub fn without_template<'a>(&self, summary: &'a str) -> &'a str {
+        match &self.commit_template {
+            Some(template) => template.strip(summary, self.branch_ticket(template).as_deref()),
+            None => summary,
+        }
+    }
+
+    /// The ticket id in the name of the current branch, if there is one
+    fn branch_ticket(&self, template: &CommitTemplate) -> Option<String> {
+        let repo = Repository::open(&self.repo_path).ok()?;
+        template.ticket(&git::current_branch(&repo)?)
+    }
+
+    /// People offered as commit co-authors, as `"Name <email>"`
+    pub fn with_co_authors(self, co_authors: Vec<String>) -> Self {
+        Self {
+            co_authors,
+            ..self
+        }
+    }
+
+    pub fn co_authors(&self) -> &[String] {
+        &self.co_authors
+    }
+
+    /// The commit type the user picked most recently, in this or an earlier session
+    pub fn last_commit_type(&self) -> Option<String> {
+        self.last_commit_type.lock().ok().and_then(|last| last.clone())
+    }
+
+    /// Remembers `commit_type` for the rest of the session, and in the user config file unless
+    /// this is a dry run
+    pub fn remember_commit_type(&self, commit_type: &str) {
+        if let Ok(mut last) = self.last_commit_type.lock() {
+            *last = Some(commit_type.to_string());
+        }
+        if self.dry_run {
+            return;
+        }
+        // Failing to persist only means the menu starts at the top next time
+        if let Err(e) = config_file::save_last_commit_type(commit_type) {
+            log::info!("Couldn't remember the commit type: {}", e);
+        }
+    }
+
+    /// The commit created most recently in this session, if it hasn't been undone
+    pub fn session_commit(&self) -> Option<git2::Oid> {
+        self.session_commit.lock().ok().and_then(|commit| *commit)
+    }
+
+    /// Records (or with `None`, forgets) the commit created in this session
+    pub fn set_session_commit(&self, commit: Option<git2::Oid>) {
+        if let Ok(mut current) = self.session_commit.lock() {
+            *current = commit;
+        }
+    }
+
+    /// File analysis guidance by lowercase file extension
+    pub fn with_file_prompts(self, file_prompts: HashMap<String, String>) -> Self {
+        Self {
+            file_prompts,
+            ..self
+        }
+    }
+
+    pub fn file_prompts(&self) -> &HashMap<String, String> {
+        &self.file_prompts
+    }
+
+    /// Guidance for analyzing `path`, if there is any for its extension
+    pub fn file_prompt(&self, path: &str) -> Option<&str> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?.to_lowercase();
+        self.file_prompts.get(&extension).map(String::as_str)
+    }
+
+    /// Rough token count for `text`, assuming about four characters per token
+    pub fn estimate_tokens(text: &str) -> usize {
+        text.chars().count().div_ceil(4)
+    }
+
+    /// Estimated input cost in US dollars of sending `tokens` to the selected model,
+    /// if its price is known
+    pub fn estimate_cost(&self, tokens: usize) -> Option<f64> {
+        providers::input_price_per_million(&self.model_name).map(|price| tokens as f64 * price / 1_000_000.0)
+    }
+
+    /// Models offered by the current provider
+    pub fn available_models(&self) -> Vec<String> {
+        self.model.available_models()
+    }
+
+    /// Selects which of the provider's models is used for subsequent requests, also by
+    /// the modes that had a model of their own
+    pub fn with_model(self, model_name: &str) -> Self {
+        Self {
+            model_name: model_name.to_string(),
+            mode_models: HashMap::new(),
+            ..self
+        }
+    }
+
+    pub fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    /// Uses `model_name` for the requests made by `mode`, e.g. a cheaper model for
+    /// commit messages than for file analysis
+    pub fn with_mode_model(self, mode: modes::Mode, model_name: &str) -> Self {
+        let mut mode_models = self.mode_models;
+        mode_models.insert(mode, model_name.to_string());
+        Self {
+            mode_models,
+            ..self
+        }
+    }
+
+    /// The model requests made by `mode` are sent to
+    pub fn model_for(&self, mode: modes::Mode) -> &str {
+        self.mode_models.get(&mode).unwrap_or(&self.model_name)
+    }
+
+    /// Directory the repository (or, for a patch, the settings) were loaded from
+    pub fn repo_path(&self) -> &str {
+        &self.repo_path
+    }
+
+    /// A sentence naming the repository's main language for the commit message prompt,
+    /// e.g. "This is primarily a Rust project (80% of source files)." `None` outside of a
+    /// repository or without recognizable source files.
+    pub fn project_summary(&self) -> Option<&str> {
+        self.project_summary
+            .get_or_init(|| {
+                let repo = Repository::open(&self.repo_path).ok()?;
+                let languages = git::detect_languages(&repo).ok()?;
+                let (language, share) = languages.first()?;
+                Some(format!("This is primarily a {} project ({:.0}% of source files).", language, share))
+            })
+            .as_deref()
+    }
+
+    /// A message committed before for changes much like `diff`, e.g. an earlier version bump,
+    /// so that such commits don't need a request. `None` outside of a repository or when
+    /// nothing in the history is close enough.
+    pub fn suggest_from_history(&self, diff: &str) -> Option<String> {
+        let repo = Repository::open(&self.repo_path).ok()?;
+        let history = history::MessageHistory::open(repo.path());
+        history.best_match(diff, history::SIMILARITY_THRESHOLD).map(|(message, _)| message.to_string())
+    }
+
+    /// Diffs changing fewer lines than this get a message made from the file names instead
+    /// of a request; 0, the default, always asks the model
+    pub fn with_min_diff_lines(self, min_diff_lines: usize) -> Self {
+        Self {
+            min_diff_lines,
+            ..self
+        }
+    }
+
+    pub fn min_diff_lines(&self) -> usize {
+        self.min_diff_lines
+    }
+
+    /// A simple message such as "fix: update main.rs" for a diff below `min_diff_lines`,
+    /// which isn't worth a request. `None` for bigger diffs.
+    pub fn trivial_commit_message(&self, diff: &str) -> Option<CommitMessage> {
+        if git::count_changed_lines(diff) >= self.min_diff_lines {
+            return None;
+        }
+        let files: Vec<(String, git::ChangeKind)> = git::split_diff(diff)
+            .into_iter()
+            .filter(|(path, _)| !path.is_empty())
+            .map(|(path, file_diff)| (path, git::ChangeKind::from_diff(&file_diff)))
+            .collect();
+        if files.is_empty() {
+            return None;
+        }
+        Some(self.parse_commit_message(&summary_from_files(&files)))
+    }
+
+    /// Remembers the message `diff` was committed with, for `suggest_from_history`
+    pub fn remember_message(&self, repo: &Repository, diff: &str, message: &CommitMessage) -> Result<(), NoahError> {
+        history::MessageHistory::open(repo.path()).record(diff, &message.to_string())
+    }
+
+    /// Generates a commit message for the diff. When `detailed` is set the model is asked
+    /// for a body and footer as well as the summary line.
+    pub async fn generate_commit_message(&self, diff: &str, detailed: bool) -> Result<CommitMessage, NoahError> {
+        Ok(self.parse_commit_message(&self.generate_commit_text(diff, detailed, true).await?))
+    }
+
+    /// The model's answer for `generate_commit_message`, before parsing. Candidates are
+    /// never `cached`, or they would all be the same message.
+    async fn generate_commit_text(&self, diff: &str, detailed: bool, cached: bool) -> Result<String, NoahError> {
+        let operation = || async {
+            if detailed {
+                self.model.generate_detailed_commit_message(self.model_for(modes::Mode::CommitMessage), diff, self.project_summary(), self.generation_options(modes::Mode::CommitMessage)).await
+            } else {
+                self.model.generate_commit_message(self.model_for(modes::Mode::CommitMessage), diff, self.project_summary(), self.generation_options(modes::Mode::CommitMessage)).await
+            }
+        };
+        let text = if cached {
+            let kind = if detailed { "detailed commit message" } else { "commit message" };
+            self.cached_request(modes::Mode::CommitMessage, &[kind, diff, self.project_summary().unwrap_or("")], operation).await?
+        } else {
+            let text = self.request(modes::Mode::CommitMessage, operation).await?;
+            self.record_request(&[diff, self.project_summary().unwrap_or("")], &text);
+            text
+        };
+        self.record_usage(|usage| usage.commit_messages += 1);
+        Ok(text)
+    }
+
+    /// Generates `n` commit messages for the diff to pick from, with up to `max_concurrency`
+    /// requests in flight. Duplicates are dropped, so there may be fewer. Only fails if every
+    /// request does.
+    pub async fn generate_commit_message_candidates(&self, diff: &str, n: usize, detailed: bool, hint: Option<&str>) -> Result<Vec<String>, NoahError> {
+        use futures::StreamExt;
+
+        let input = with_hint(diff, hint);
+        let results: Vec<Result<String, NoahError>> = futures::stream::iter((0..n.max(1)).map(|_| self.generate_commit_text(&input, detailed, n <= 1)))
+            .buffered(self.max_concurrency.max(1))
+            .collect()
+            .await;
+
+        let mut candidates: Vec<String> = Vec::new();
+        let mut first_error = None;
+        for result in results {
+            match result {
+                Ok(text) if !candidates.iter().any(|candidate| candidate.trim() == text.trim()) => candidates.push(text),
+                Ok(_) => (),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) if candidates.is_empty() => Err(e),
+            _ => Ok(candidates),
+        }
+    }
+
+    /// Checks a commit message against the usual git conventions: subject length, imperative
+    /// mood, no trailing period, a blank line before the body, and body lines wrapped at 72
+    pub fn lint_commit_message(&self, message: &str) -> Vec<LintWarning> {
+        let mut warnings = Vec::new();
+        let mut lines = message.trim().lines();
+        let subject = lines.next().unwrap_or("").trim();
+
+        let length = subject.chars().count();
+        if length > self.max_subject_chars {
+            warnings.push(LintWarning::SubjectTooLong { length, max: self.max_subject_chars });
+        }
+        if let Some(commit_type) = conventional_type(subject).filter(|_| self.types_enforced) {
+            if !self.commit_types.iter().any(|known| known.prefix == commit_type) {
+                warnings.push(LintWarning::TypeNotAllowed(commit_type.to_string()));
+            }
+        }
+        let description = match conventional_type(subject) {
+            Some(_) => subject.split_once(':').map(|(_, rest)| rest.trim()).unwrap_or(subject),
+            None => subject,
+        };
+        if let Some(word) = description.split_whitespace().next().filter(|word| looks_non_imperative(word)) {
+            warnings.push(LintWarning::NotImperative(word.to_string()));
+        }
+        if subject.ends_with('.') {
+            warnings.push(LintWarning::TrailingPeriod);
+        }
+
+        if lines.next().is_some_and(|line| !line.trim().is_empty()) {
+            warnings.push(LintWarning::MissingBlankLine);
+        }
+        for (idx, line) in message.trim().lines().enumerate().skip(1) {
+            let length = line.chars().count();
+            // Long URLs and the like can't be wrapped
+            if length > MAX_BODY_LINE_CHARS && line.contains(' ') {
+                warnings.push(LintWarning::BodyLineTooLong { line: idx + 1, length });
+            }
+        }
+        warnings
+    }
+
+    /// `lint_commit_message` for raw model output, whose body is wrapped at `body_wrap`
+    /// the way `parse_commit_message` wraps it. The raw text still shows a missing blank
+    /// line before the body, which parsing adds.
+    pub fn lint_generated_message(&self, text: &str) -> Vec<LintWarning> {
+        let (subject, rest) = text.trim().split_once('\n').unwrap_or((text.trim(), ""));
+        self.lint_commit_message(&format!("{}\n{}", subject, Self::wrap_body(rest, self.body_wrap)))
+    }
+
+    /// Asks the model for a subject line without the subject `warnings`, keeping the rest
+    /// of the message. Never answered from the response cache, since asking again is the point.
+    pub async fn fix_commit_subject(&self, message: &CommitMessage, warnings: &[LintWarning]) -> Result<CommitMessage, NoahError> {
+        let problems: Vec<String> = warnings.iter().filter(|w| w.is_subject()).map(|w| format!("- {}", w)).collect();
+        let request = format!("Commit message:\n{}\n\nProblems with the subject line:\n{}", message, problems.join("\n"));
+        let text = self.fresh_request(modes::Mode::CommitMessage, &["fix subject", &request], || self.model.fix_commit_subject(self.model_for(modes::Mode::CommitMessage), &request, self.generation_options(modes::Mode::CommitMessage))).await?;
+
+        let summary = text
+            .lines()
+            .map(|line| line.trim().trim_matches(|c| c == '"' || c == '`').trim())
+            .find(|line| !line.is_empty())
+            .ok_or("The model returned an empty subject line")?;
+        Ok(CommitMessage {
+            summary: summary.to_string(),
+            ..message.clone()
+        })
+    }
+
+    /// Asks the model for a new subject line for `diff`, keeping the body and footer of
+    /// `message` exactly as they are, e.g. after editing the body by hand. Never answered
+    /// from the response cache, since asking again is the point.
+    pub async fn generate_subject_only(&self, diff: &str, message: &CommitMessage) -> Result<CommitMessage, NoahError> {
+        let rest = CommitMessage { summary: String::new(), ..message.clone() }.to_string();
+        let request = format!("{}\n\nBody of the commit message:\n{}", diff, rest.trim());
+        let text = self.fresh_request(modes::Mode::CommitMessage, &["subject", &request, self.project_summary().unwrap_or("")], || self.model.generate_subject(self.model_for(modes::Mode::CommitMessage), &request, self.project_summary(), self.generation_options(modes::Mode::CommitMessage))).await?;
 
-pub mod providers;
-pub mod git_analysis;
-pub mod git;
-pub mod ui;
-pub mod modes;
+        let summary = text
+            .lines()
+            .map(|line| line.trim().trim_matches(|c| c == '"' || c == '`').trim())
+            .find(|line| !line.is_empty())
+            .ok_or("The model returned an empty subject line")?;
+        // Only the new summary is formatted, so the body isn't rewrapped
+        let formatted = self.format_commit_message(CommitMessage { summary: summary.to_string(), body: None, footer: None });
+        Ok(CommitMessage {
+            summary: formatted.summary,
+            ..message.clone()
+        })
+    }
 
-#[derive(Debug)]
-pub struct Config {
-    model: Box<dyn git_analysis::GitAnalyzer>,
-    repo_path: String,
-}
+    /// Asks the model how to split `diff` into several commits. Files the model made up or
+    /// listed twice are dropped, and files it left out are added to the last group, so every
+    /// file in `diff` is in exactly one group.
+    pub async fn suggest_commit_groups(&self, diff: &str) -> Result<Vec<CommitGroup>, NoahError> {
+        let text = self.cached_request(modes::Mode::SplitCommits, &[diff, self.project_summary().unwrap_or("")], || self.model.suggest_commit_groups(self.model_for(modes::Mode::SplitCommits), diff, self.project_summary(), self.generation_options(modes::Mode::SplitCommits))).await?;
+        // Tolerates a code fence or a sentence around the array
+        let json = match (text.find('['), text.rfind(']')) {
+            (Some(start), Some(end)) if start < end => &text[start..=end],
+            _ => return Err("The model didn't return a list of commits".into()),
+        };
+        let suggested: Vec<SuggestedGroup> = serde_json::from_str(json)
+            .map_err(|e| format!("The model returned commit groups that couldn't be read: {}", e))?;
 
-#[derive(Debug)]
-pub struct FileAnalysis {
-    pub path: String,
-    pub explanation: String,
-}
+        let mut remaining = git::changed_paths(diff);
+        let mut groups: Vec<CommitGroup> = Vec::new();
+        for group in suggested {
+            let files: Vec<String> = group.files
+                .into_iter()
+                .filter(|file| match remaining.iter().position(|path| path == file.trim()) {
+                    Some(idx) => {
+                        remaining.remove(idx);
+                        true
+                    }
+                    None => false,
+                })
+                .map(|file| file.trim().to_string())
+                .collect();
+            if !files.is_empty() {
+                groups.push(CommitGroup { files, message: self.parse_commit_message(&group.message) });
+            }
+        }
+        match groups.last_mut() {
+            Some(last) => last.files.append(&mut remaining),
+            None => return Err("The model didn't put any of the changed files in a commit".into()),
+        }
+        Ok(groups)
+    }
 
-impl Config {
-    pub fn new(model: Box<dyn git_analysis::GitAnalyzer>, repo_path: Option<String>) -> Self {
-        Self { 
-            model,
-            repo_path: repo_path.unwrap_or_else(|| ".".to_string())
+    /// Asks the model to review `diff` like a code reviewer would. Findings come back most
+    /// severe first, in the model's order otherwise.
+    pub async fn review_changes(&self, diff: &str) -> Result<Vec<ReviewFinding>, NoahError> {
+        let text = self.cached_request(modes::Mode::Review, &[diff, self.project_summary().unwrap_or("")], || self.model.review_changes(self.model_for(modes::Mode::Review), diff, self.project_summary(), self.generation_options(modes::Mode::Review))).await?;
+        // Tolerates a code fence or a sentence around the array
+        let json = match (text.find('['), text.rfind(']')) {
+            (Some(start), Some(end)) if start < end => &text[start..=end],
+            _ => return Err("The model didn't return a list of findings".into()),
+        };
+        let mut findings: Vec<ReviewFinding> = serde_json::from_str(json)
+            .map_err(|e| format!("The model returned review findings that couldn't be read: {}", e))?;
+        findings.sort_by_key(|finding| finding.severity);
+        Ok(findings)
+    }
+
+    /// Suggests a kebab-case name for a branch with the changes in `diff`, e.g.
+    /// `fix-login-timeout`. Whatever the model answers is cleaned up with `to_branch_name`,
+    /// so the name is always a valid git branch name.
+    pub async fn suggest_branch_name(&self, diff: &str) -> Result<String, NoahError> {
+        self.branch_name(diff, false).await
+    }
+
+    /// Like `suggest_branch_name`, but always asks the model instead of using a cached name
+    pub async fn suggest_another_branch_name(&self, diff: &str) -> Result<String, NoahError> {
+        self.branch_name(diff, true).await
+    }
+
+    async fn branch_name(&self, diff: &str, fresh: bool) -> Result<String, NoahError> {
+        let text = self.request_through_cache(modes::Mode::BranchName, &[diff], fresh, || self.model.suggest_branch_name(self.model_for(modes::Mode::BranchName), diff, self.generation_options(modes::Mode::BranchName))).await?;
+        let line = text.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default();
+        to_branch_name(line).ok_or_else(|| "The model didn't suggest a branch name".into())
+    }
+
+    /// Like `generate_commit_message`, with a short instruction from the user for this
+    /// message, e.g. "make it shorter" or "mention the bug number"
+    pub async fn generate_commit_message_with_hint(&self, diff: &str, hint: &str) -> Result<CommitMessage, NoahError> {
+        self.generate_commit_message(&with_hint(diff, Some(hint)), false).await
+    }
+
+    /// Streaming variant of `generate_commit_message` and `generate_commit_message_with_hint`.
+    /// The chunks concatenate to the same text that `CommitMessage::parse` receives in the
+    /// non-streaming call. A `fresh` message, e.g. for "Regenerate message", is never read
+    /// from the response cache but replaces what it holds.
+    pub async fn generate_commit_message_stream(&self, diff: &str, detailed: bool, hint: Option<&str>, fresh: bool) -> Result<providers::TextStream, NoahError> {
+        use futures::StreamExt;
+
+        let input = with_hint(diff, hint);
+        let kind = if detailed { "detailed commit message" } else { "commit message" };
+        let cache = self.response_cache(modes::Mode::CommitMessage, &[kind, &input, self.project_summary().unwrap_or("")]);
+        if let Some(response) = cache.as_ref().filter(|_| !fresh).and_then(|(cache, key)| cache.get(key)) {
+            log::info!("CommitMessage request answered from the response cache");
+            self.record_usage(|usage| usage.commit_messages += 1);
+            return Ok(futures::stream::once(async move { Ok(response) }).boxed_local());
         }
+        // Only establishing the stream is retried; a failure mid-stream is reported as is
+        let stream = self.request(modes::Mode::CommitMessage, || self.model.stream_commit_message(self.model_for(modes::Mode::CommitMessage), &input, detailed, self.project_summary(), self.generation_options(modes::Mode::CommitMessage))).await?;
+        let stream = providers::stream_with_timeout(stream, self.request_timeout(modes::Mode::CommitMessage));
+        self.record_request(&[&input, self.project_summary().unwrap_or("")], "");
+        self.record_usage(|usage| usage.commit_messages += 1);
+        let usage = self.usage.clone();
+        let stream = stream.inspect(move |chunk| {
+            if let (Ok(chunk), Ok(mut usage)) = (chunk, usage.lock()) {
+                usage.tokens += Self::estimate_tokens(chunk);
+            }
+        }).boxed_local();
+        Ok(match cache {
+            Some((cache, key)) if !self.dry_run => cache.record_stream(key, stream),
+            _ => stream,
+        })
     }
 
-    pub fn with_new_model(self, model: Box<dyn git_analysis::GitAnalyzer>) -> Self {
+    /// The commit or range to explain; asked for interactively when not given
+    pub fn with_revision(self, revision: Option<String>) -> Self {
         Self {
-            model,
-            repo_path: self.repo_path,
+            revision,
+            ..self
         }
     }
 
-    pub fn with_new_repo(self, repo_path: String) -> Self {
+    pub fn revision(&self) -> Option<&str> {
+        self.revision.as_deref()
+    }
+
+    /// Makes commit messages and file analysis cover what the current branch changed
+    /// since it left `base_branch`, e.g. for a pull request description
+    pub fn with_against_base(self, against_base: bool) -> Self {
         Self {
-            model: self.model,
-            repo_path,
+            against_base,
+            ..self
+        }
+    }
+
+    pub fn against_base(&self) -> bool {
+        self.against_base
+    }
+
+    /// The branch `against_base` compares with; `None` uses the repository's default branch
+    pub fn with_base_branch(self, base_branch: Option<String>) -> Self {
+        Self {
+            base_branch,
+            ..self
+        }
+    }
+
+    /// The branch `against_base` compares with in `repo`, see `git::default_branch`
+    pub fn base_branch(&self, repo: &Repository) -> Result<String, NoahError> {
+        self.base_branch.clone()
+            .or_else(|| git::default_branch(repo))
+            .ok_or_else(|| "Can't tell which branch to compare with; pass --base <branch> or set base_branch in the config".into())
+    }
+
+    /// What the current branch changed since it left `base_branch`, see
+    /// `git::diff_against_branch`. Use `revision_file_diffs` for the files to send.
+    pub fn branch_changes(&self, repo: &Repository) -> Result<git::RevisionChanges, NoahError> {
+        git::diff_against_branch(repo, &self.base_branch(repo)?, self.diff_context_lines)
+    }
+
+    /// Restricts file analysis to one file, given relative to the repository root or the current directory
+    pub fn with_only_file(self, only_file: Option<String>) -> Self {
+        Self {
+            only_file,
+            ..self
+        }
+    }
+
+    pub fn only_file(&self) -> Option<&str> {
+        self.only_file.as_deref()
+    }
+
+    /// The file (`-` for stdin) to read the error log for explaining a failure from;
+    /// pasted into the editor when not given
+    pub fn with_failure_log(self, failure_log: Option<String>) -> Self {
+        Self {
+            failure_log,
+            ..self
+        }
+    }
+
+    pub fn failure_log(&self) -> Option<&str> {
+        self.failure_log.as_deref()
+    }
+
+    /// The least severe review findings that are still shown; `Low` shows all of them
+    pub fn with_min_severity(self, min_severity: Severity) -> Self {
+        Self {
+            min_severity,
+            ..self
+        }
+    }
+
+    pub fn min_severity(&self) -> Severity {
+        self.min_severity
+    }
+
+    /// The least risky analyzed files that are still shown; `Low` shows all of them
+    pub fn with_min_risk(self, min_risk: RiskLevel) -> Self {
+        Self {
+            min_risk,
+            ..self
+        }
+    }
+
+    pub fn min_risk(&self) -> RiskLevel {
+        self.min_risk
+    }
+
+    /// Whether headless runs of the hook mode install, replace or remove the hook
+    pub fn with_hook_action(self, hook_action: hook::HookAction) -> Self {
+        Self {
+            hook_action,
+            ..self
+        }
+    }
+
+    pub fn hook_action(&self) -> hook::HookAction {
+        self.hook_action
+    }
+
+    /// Whether headless runs replace a file they write that already exists, from `--force`
+    pub fn with_overwrite(self, overwrite: bool) -> Self {
+        Self {
+            overwrite,
+            ..self
         }
     }
 
-    pub async fn generate_commit_message(&self, diff: &str) -> Result<String, Box<dyn Error>> {
-        self.model.generate_commit_message(diff).await
+    pub fn overwrites(&self) -> bool {
+        self.overwrite
+    }
+
+    /// Like `file_diffs`, but for the files changed by a commit or range, see `git::revision_changes`
+    pub fn revision_file_diffs(&self, repo: &Repository, changes: &git::RevisionChanges) -> Result<Vec<(String, String)>, NoahError> {
+        let exclude = git::ExcludeFilter::new(repo, &self.exclude_globs)?;
+        Ok(changes.file_diffs
+            .iter()
+            .filter(|(path, _)| !exclude.is_excluded(path))
+            .map(|(path, diff)| (path.clone(), git::truncate_diff(diff, self.max_diff_bytes)))
+            .collect())
+    }
+
+    /// Paths of the changed files that file analysis would look at
+    pub fn changed_files(&self, repo: &Repository) -> Result<Vec<String>, NoahError> {
+        let exclude = git::ExcludeFilter::new(repo, &self.exclude_globs)?;
+        Ok(git::get_file_diffs(repo, self.diff_context_lines)?
+            .into_iter()
+            .map(|(path, _)| path)
+            .filter(|path| !exclude.is_excluded(path))
+            .collect())
+    }
+
+    /// The truncated diff of each changed, non-excluded file, as sent for file analysis.
+    /// When `paths` is given only those files are included.
+    pub fn file_diffs(&self, repo: &Repository, paths: Option<&[String]>) -> Result<Vec<(String, String)>, NoahError> {
+        let exclude = git::ExcludeFilter::new(repo, &self.exclude_globs)?;
+        Ok(git::get_file_diffs(repo, self.diff_context_lines)?
+            .into_iter()
+            .filter(|(path, _)| !exclude.is_excluded(path))
+            .filter(|(path, _)| paths.is_none_or(|paths| paths.contains(path)))
+            .map(|(path, diff)| {
+                let diff = git::truncate_diff(&diff, self.max_diff_bytes);
+                (path, diff)
+            })
+            .collect())
+    }
+
+    /// Like `file_diffs`, but for the files of a patch. Only the exclude globs apply,
+    /// since there is no repository to take ignore rules from.
+    pub fn patch_file_diffs(&self, patch: &input::Patch) -> Result<Vec<(String, String)>, NoahError> {
+        let exclude = git::ExcludeFilter::globs_only(&self.exclude_globs)?;
+        Ok(patch.file_diffs()
+            .into_iter()
+            .filter(|(path, _)| !exclude.is_excluded(path))
+            .map(|(path, diff)| {
+                let diff = git::truncate_diff(&diff, self.max_diff_bytes);
+                (path, diff)
+            })
+            .collect())
+    }
+
+    /// Analyzes each changed file. When `paths` is given only those files are analyzed.
+    pub async fn analyze_changes(&self, repo: &Repository, paths: Option<&[String]>) -> Result<Vec<FileAnalysisResult>, NoahError> {
+        Ok(self.analyze_file_diffs(self.file_diffs(repo, paths)?).await)
     }
 
-    pub async fn analyze_changes(&self, repo: &Repository) -> Result<Vec<FileAnalysis>, Box<dyn Error>> {
-        let file_diffs = git::get_file_diffs(repo)?;
-        
-        let analysis_futures: Vec<_> = file_diffs.into_iter().map(|(path, diff)| {
+    /// Analyzes already collected `(path, diff)` pairs, e.g. from `file_diffs`, with up to
+    /// `max_concurrency` requests in flight. Results are in the order of `file_diffs`.
+    /// With `hunk_level_analysis` each hunk of a file with several is a request of its own.
+    pub async fn analyze_file_diffs(&self, file_diffs: Vec<(String, String)>) -> Vec<FileAnalysisResult> {
+        use futures::StreamExt;
+
+        let options = self.generation_options(modes::Mode::FileAnalysis);
+        let repo = Repository::open(&self.repo_path).ok();
+        let mut files = Vec::new();
+        let mut jobs = Vec::new();
+        for (idx, (path, diff)) in file_diffs.into_iter().enumerate() {
+            let change = git::ChangeKind::from_diff(&diff);
+            let info = repo.as_ref().and_then(|repo| git::file_info(repo, &path, &diff));
+            let is_binary = git::is_binary_diff(&diff) || info.is_some_and(|info| info.is_binary);
+            let byte_size = info.map(|info| info.byte_size);
+            // There's nothing in these for the model to explain, so they get a note instead
+            let skipped = match (change.note(), byte_size) {
+                (Some(note), _) => Some(format!("{}.", note)),
+                _ if is_binary => Some("(binary, not analyzed)".to_string()),
+                (None, Some(size)) if size > LARGE_FILE_BYTES => Some(format!("(large file of {}, not analyzed)", ui::format_bytes(size))),
+                _ => None,
+            };
+            // Without this the model tends to describe a rename as a deleted and a new file
+            let context = match &change {
+                git::ChangeKind::Renamed { from, to } => format!("This file was renamed from {} to {}.\n\n", from, to),
+                _ => String::new(),
+            };
+            let hunks = match (self.hunk_level_analysis, &skipped) {
+                (true, None) => git::parse_file_hunks(&diff),
+                _ => Vec::new(),
+            };
+            if hunks.len() > 1 {
+                for (part, hunk) in hunks.iter().enumerate() {
+                    let input = format!(
+                        "{}This is hunk {} of {} in {}; explain only this part.\n\n{}\n{}",
+                        context, part + 1, hunks.len(), path, hunk.header, hunk.content
+                    );
+                    jobs.push((idx, part, Some(input)));
+                }
+            } else {
+                jobs.push((idx, 0, skipped.is_none().then(|| format!("{}{}", context, diff))));
+            }
+            let parts = (0..hunks.len().max(1)).map(|_| None).collect::<Vec<Option<Result<String, NoahError>>>>();
+            files.push(PendingAnalysis { path, change, hunks, is_binary, byte_size, skipped, parts });
+        }
+
+        let total = jobs.len();
+        let requests = jobs.into_iter().map(|(idx, part, input)| {
             let model = &self.model;
+            let focus = self.file_prompt(&files[idx].path);
+            let skipped = files[idx].skipped.clone();
             async move {
-                let explanation = model.analyze_file_changes(&diff).await?;
-                Ok::<FileAnalysis, Box<dyn Error>>(FileAnalysis {
-                    path,
-                    explanation,
-                })
+                let explanation = match input {
+                    Some(input) => self.cached_request(modes::Mode::FileAnalysis, &[&input, focus.unwrap_or("")], || model.analyze_file_changes(self.model_for(modes::Mode::FileAnalysis), &input, focus, options)).await,
+                    None => Ok(skipped.unwrap_or_default()),
+                };
+                (idx, part, explanation)
             }
-        }).collect();
+        });
+        let mut responses = futures::stream::iter(requests).buffer_unordered(self.max_concurrency.max(1));
 
-        futures::future::join_all(analysis_futures)
-            .await
-            .into_iter()
-            .collect()
+        let mut explained = Vec::new();
+        let mut done = 0;
+        while let Some((idx, part, explanation)) = responses.next().await {
+            done += 1;
+            ui::set_spinner_message(&format!("Analyzing {}/{}", done, total));
+            explained.push((idx, part, explanation));
+        }
+        drop(responses);
+        for (idx, part, explanation) in explained {
+            files[idx].parts[part] = Some(explanation);
+        }
+
+        files.into_iter().map(|PendingAnalysis { path, change, hunks, is_binary, byte_size, skipped, parts }| {
+            let parts: Result<Vec<String>, NoahError> = parts.into_iter().flatten().collect();
+            let parts = match parts {
+                Ok(parts) => parts,
+                Err(error) => return Err(FileAnalysisError { path, error }),
+            };
+            if skipped.is_none() {
+                self.record_usage(|usage| usage.files_analyzed += 1);
+            }
+            let (parts, risks): (Vec<String>, Vec<Option<RiskLevel>>) = parts.iter().map(|part| RiskLevel::split_rating(part)).unzip();
+            // Ordered riskiest first
+            let risk = risks.into_iter().flatten().min();
+            if hunks.len() <= 1 {
+                let explanation = parts.into_iter().next().unwrap_or_default();
+                return Ok(FileAnalysis { path, change, explanation, hunks: Vec::new(), is_binary, byte_size, risk });
+            }
+            let hunks: Vec<HunkAnalysis> = hunks.into_iter().zip(parts)
+                .map(|(hunk, explanation)| HunkAnalysis { header: hunk.header, explanation })
+                .collect();
+            let explanation = hunks.iter()
+                .map(|hunk| format!("### `{}`\n{}", hunk.header, hunk.explanation))
+                .collect::<Vec<String>>()
+                .join("\n\n");
+            Ok(FileAnalysis { path, change, explanation, hunks, is_binary, byte_size, risk })
+        }).collect()
+    }
+
+    pub async fn analyze_contributor(&self, stats: &str) -> Result<String, NoahError> {
+        self.cached_request(modes::Mode::ContributorAnalysis, &["contributor", stats], || self.model.analyze_contributor(self.model_for(modes::Mode::ContributorAnalysis), stats, self.generation_options(modes::Mode::ContributorAnalysis))).await
+    }
+
+    /// `analyze_contributor` for each of `stats`, with up to `max_concurrency` requests in
+    /// flight. Results are in the order of `stats`.
+    pub async fn analyze_contributors(&self, stats: &[String]) -> Vec<Result<String, NoahError>> {
+        use futures::StreamExt;
+
+        let total = stats.len();
+        let mut responses = futures::stream::iter(stats.iter().enumerate().map(|(idx, stats)| async move {
+            (idx, self.analyze_contributor(stats).await)
+        }))
+        .buffer_unordered(self.max_concurrency.max(1));
+
+        let mut results: Vec<Option<Result<String, NoahError>>> = (0..total).map(|_| None).collect();
+        let mut done = 0;
+        while let Some((idx, summary)) = responses.next().await {
+            done += 1;
+            ui::set_spinner_message(&format!("Analyzing contributors {}/{}", done, total));
+            results[idx] = Some(summary);
+        }
+        results.into_iter().flatten().collect()
+    }
+
+    /// Summarizes the whole team from aggregate statistics, see `git::TeamStats`
+    pub async fn analyze_team(&self, stats: &str) -> Result<String, NoahError> {
+        self.cached_request(modes::Mode::ContributorAnalysis, &["team", stats], || self.model.analyze_team(self.model_for(modes::Mode::ContributorAnalysis), stats, self.generation_options(modes::Mode::ContributorAnalysis))).await
+    }
+
+    /// Explains the error `log`, e.g. of a failed build, in the light of the changes in
+    /// `diff`, with a suggested fix, as markdown
+    pub async fn explain_failure(&self, diff: &str, log: &str) -> Result<String, NoahError> {
+        let input = format!("Error log:\n{}\n\nDiff of the changes:\n{}", log, diff);
+        self.cached_request(modes::Mode::ExplainFailure, &["failure", &input, self.project_summary().unwrap_or("")], || {
+            self.model.explain_failure(self.model_for(modes::Mode::ExplainFailure), &input, self.project_summary(), self.generation_options(modes::Mode::ExplainFailure))
+        }).await
+    }
+
+    /// Writes a title and description for a pull request of the changes in `diff`, with
+    /// `commits` (short ids and summary lines of the branch, newest first) as context
+    pub async fn generate_pr_description(&self, diff: &str, commits: &[String]) -> Result<PullRequestDescription, NoahError> {
+        let commit_list = commits.iter().map(|commit| format!("- {}", commit)).collect::<Vec<String>>().join("\n");
+        let input = format!("Commits on the branch, newest first:\n{}\n\nDiff:\n{}", commit_list, diff);
+        let text = self.cached_request(modes::Mode::PullRequest, &["pull request", &input, self.project_summary().unwrap_or("")], || {
+            self.model.generate_pr_description(self.model_for(modes::Mode::PullRequest), &input, self.project_summary(), self.generation_options(modes::Mode::PullRequest))
+        }).await?;
+        Ok(PullRequestDescription::parse(&text))
+    }
+
+    /// Writes a changelog section for `commits` (summary lines, newest first), which are
+    /// grouped by Conventional Commit type before being sent. `range` names the release,
+    /// e.g. "Changes since v1.2.0".
+    pub async fn generate_changelog(&self, range: &str, commits: &[String]) -> Result<String, NoahError> {
+        let input = format!("{}\n\n{}", range, group_by_commit_type(commits, &self.commit_types));
+        self.cached_request(modes::Mode::Changelog, &[&input], || self.model.generate_changelog(self.model_for(modes::Mode::Changelog), &input, self.generation_options(modes::Mode::Changelog))).await
+    }
+}
+
+/// Longest branch name `to_branch_name` makes
+const MAX_BRANCH_NAME_CHARS: usize = 50;
+
+/// `text` as a kebab-case branch name: lowercase ASCII letters and digits, with runs of
+/// anything else turned into a single hyphen, cut at a word boundary after 50 characters.
+/// `None` if nothing is left.
+///
+/// ```
+/// use merit_cli_demo::to_branch_name;
+///
+/// assert_eq!(to_branch_name("`Fix: login timeout!`").as_deref(), Some("fix-login-timeout"));
+/// assert_eq!(to_branch_name("🚀"), None);
+/// ```
+pub fn to_branch_name(text: &str) -> Option<String> {
+    let mut name = String::new();
+    for word in text.split(|c: char| !c.is_ascii_alphanumeric()).filter(|word| !word.is_empty()) {
+        if !name.is_empty() && name.len() + 1 + word.len() > MAX_BRANCH_NAME_CHARS {
+            break;
+        }
+        if !name.is_empty() {
+            name.push('-');
+        }
+        name.push_str(&word.to_ascii_lowercase());
+    }
+    name.truncate(MAX_BRANCH_NAME_CHARS);
+    (!name.is_empty()).then_some(name)
+}
+
+/// The Conventional Commit type of a summary line, e.g. `feat` for `feat(ui)!: Add theme`
+pub fn conventional_type(summary: &str) -> Option<&str> {
+    let (prefix, _) = summary.split_once(':')?;
+    let commit_type = prefix.split('(').next()?.trim_end_matches('!');
+    let valid = !commit_type.is_empty() && commit_type.chars().all(|c| c.is_ascii_lowercase());
+    valid.then_some(commit_type)
+}
+
+/// Renders commit summaries as one list per commit type, configured types first
+fn group_by_commit_type(commits: &[String], commit_types: &[CommitType]) -> String {
+    let mut groups: Vec<(&str, Vec<&str>)> = Vec::new();
+    for summary in commits {
+        let commit_type = conventional_type(summary).unwrap_or("other");
+        match groups.iter_mut().find(|(t, _)| *t == commit_type) {
+            Some((_, summaries)) => summaries.push(summary),
+            None => groups.push((commit_type, vec![summary])),
+        }
+    }
+    let rank = |commit_type: &str| {
+        commit_types.iter().position(|known| known.prefix == commit_type).unwrap_or(usize::MAX)
+    };
+    // Stable, so unknown types keep the order they first appeared in
+    groups.sort_by_key(|(commit_type, _)| (*commit_type == "other", rank(commit_type)));
+
+    groups
+        .into_iter()
+        .map(|(commit_type, summaries)| {
+            let items: Vec<String> = summaries.iter().map(|s| format!("- {}", s)).collect();
+            format!("{}:\n{}", commit_type, items.join("\n"))
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn default_model_name(model: &dyn git_analysis::GitAnalyzer) -> String {
+    model.available_models().into_iter().next().unwrap_or_default()
+}
+
+/// Prompts for one of the current provider's models, keeping the current one preselected
+fn select_model(config: Config) -> Result<Config, Box<dyn Error>> {
+    let models = config.available_models();
+    if models.len() < 2 {
+        return Ok(config);
+    }
+
+    let current = models.iter().position(|m| m == config.model_name()).unwrap_or(0);
+    let selected = ui::show_selection_menu(&format!("Select a {} model", config.model.name()), &models, current)?;
+    Ok(config.with_model(&models[selected]))
+}
+
+/// Reads the config files for `repo_path` and applies their process-wide settings
+fn load_settings(repo_path: &str) -> Result<config_file::ConfigFile, NoahError> {
+    let settings = config_file::ConfigFile::load(std::path::Path::new(repo_path))?;
+    settings.register_api_keys();
+    if let Some(style) = settings.spinner {
+        ui::set_spinner_style(style);
+    }
+    if let Some(theme) = settings.theme {
+        ui::set_theme(theme);
+    }
+    if let Some(endpoint) = &settings.local {
+        providers::set_local_endpoint(endpoint.clone());
+    }
+    Ok(settings)
+}
+
+/// The environment variables choosing the model for the requests of a mode. File analysis
+/// also covers explaining commits, and contributor analysis the contributor report.
+const MODE_MODEL_VARS: [(&str, modes::Mode); 9] = [
+    ("NOAH_MODEL_COMMIT", modes::Mode::CommitMessage),
+    ("NOAH_MODEL_SPLIT", modes::Mode::SplitCommits),
+    ("NOAH_MODEL_ANALYSIS", modes::Mode::FileAnalysis),
+    ("NOAH_MODEL_REVIEW", modes::Mode::Review),
+    ("NOAH_MODEL_FAILURE", modes::Mode::ExplainFailure),
+    ("NOAH_MODEL_BRANCH", modes::Mode::BranchName),
+    ("NOAH_MODEL_CONTRIBUTORS", modes::Mode::ContributorAnalysis),
+    ("NOAH_MODEL_CHANGELOG", modes::Mode::Changelog),
+    ("NOAH_MODEL_PR", modes::Mode::PullRequest),
+];
+
+/// Applies `NOAH_MODEL` and the per-mode `MODE_MODEL_VARS`, which take precedence over
+/// the `model` of the config files. Fails if one names a model the provider doesn't offer.
+fn with_env_models(config: Config) -> Result<Config, NoahError> {
+    let env_model = |name: &str| std::env::var(name).ok().map(|model| model.trim().to_string()).filter(|model| !model.is_empty());
+    let mut config = match env_model("NOAH_MODEL") {
+        Some(model) => {
+            check_model(&config, &model, "NOAH_MODEL")?;
+            config.with_model(&model)
+        }
+        None => config,
+    };
+    for (name, mode) in MODE_MODEL_VARS {
+        if let Some(model) = env_model(name) {
+            check_model(&config, &model, name)?;
+            config = config.with_mode_model(mode, &model);
+        }
+    }
+    Ok(config)
+}
+
+/// Applies the commitlint config of the config's repository, if it has one
+fn with_commitlint(config: Config) -> Config {
+    match commitlint::CommitlintRules::find(std::path::Path::new(config.repo_path())) {
+        Some((path, rules)) => {
+            log::info!("Following the commit rules in {}", path.display());
+            config.with_commitlint_rules(&rules)
+        }
+        None => config,
+    }
+}
+
+/// Fails unless the provider offers `model`, naming `setting` as where it was set.
+/// Without a provider there are no models to check against.
+fn check_model(config: &Config, model: &str, setting: &str) -> Result<(), NoahError> {
+    let models = config.available_models();
+    if models.is_empty() || models.iter().any(|m| m == model) {
+        return Ok(());
+    }
+    Err(format!("{} doesn't offer the model {} set in {}; choose one of {}", config.model.name(), model, setting, models.join(", ")).into())
+}
+
+/// Checks the `model` of the config files against the provider they were applied to
+fn check_configured_model(config: &Config, settings: &config_file::ConfigFile) -> Result<(), NoahError> {
+    match &settings.model {
+        Some(model) => check_model(config, model, "the config file's `model`"),
+        None => Ok(()),
+    }
+}
+
+/// The available provider called `name`, ignoring case
+fn configured_provider(name: &str) -> Result<Box<dyn providers::Provider>, NoahError> {
+    providers::get_available_providers()
+        .into_iter()
+        .find(|p| p.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("Configured provider {} is not available. Is its API key set?", name).into())
+}
+
+/// Command line flags take precedence over the config files
+fn apply_args(config: Config, args: &cli::Args) -> Config {
+    let mut exclude_globs = config.exclude_globs.clone();
+    exclude_globs.extend(args.exclude.iter().cloned());
+    let max_diff_bytes = args.max_diff_bytes.unwrap_or(config.max_diff_bytes);
+    let diff_context_lines = args.diff_context_lines.unwrap_or(config.diff_context_lines);
+    let token_threshold = args.token_threshold.unwrap_or(config.token_threshold);
+    let use_cache = config.use_cache && !args.no_cache;
+    let scan_secrets = config.scan_secrets && !args.no_secret_scan;
+    let candidates = args.candidates.map(usize::from).unwrap_or(config.candidates);
+    let hook_action = if args.uninstall {
+        hook::HookAction::Uninstall
+    } else if args.force {
+        hook::HookAction::Replace
+    } else {
+        hook::HookAction::Install
+    };
+    let quiet = config.quiet || args.quiet;
+    let base_branch = args.base.clone().flatten().or_else(|| config.base_branch.clone());
+
+    config
+        .with_dry_run(args.dry_run)
+        .with_exclude_globs(exclude_globs)
+        .with_max_diff_bytes(max_diff_bytes)
+        .with_diff_context_lines(diff_context_lines)
+        .with_cache(use_cache)
+        .with_token_threshold(token_threshold)
+        .with_secret_scan(scan_secrets)
+        .with_output_format(args.format.unwrap_or_default())
+        .with_revision(args.rev.clone())
+        .with_base_branch(base_branch)
+        .with_against_base(args.base.is_some())
+        .with_only_file(args.file.clone())
+        .with_failure_log(args.log.clone())
+        .with_min_severity(args.severity.unwrap_or_default())
+        .with_min_risk(args.min_risk.unwrap_or_default())
+        .with_hook_action(hook_action)
+        .with_overwrite(args.force)
+        .with_candidates(candidates)
+        .with_quiet(quiet)
+}
+
+/// Runs a single mode without user interaction, using the configured or first available provider
+async fn run_headless(args: cli::Args) -> Result<(), Box<dyn Error>> {
+    let mode = args.mode.ok_or("--headless requires --mode")?;
+    let repo_paths = repository_paths(&args)?;
+    let repo_path = repo_paths[0].clone();
+    if let Some(path) = &args.patch {
+        let patch = input::Patch::read(path)?;
+        let config = apply_args(Config::load(&repo_path)?, &args);
+        ui::set_quiet(config.is_quiet());
+        print_generation_warnings(&config);
+        return mode.execute_patch_headless(&config, &patch).await;
+    }
+    if repo_paths.len() > 1 {
+        if args.format == Some(cli::OutputFormat::Json) {
+            return Err("--format json prints one document, so it takes a single repository".into());
+        }
+        let config = apply_args(Config::load(&repo_path)?, &args);
+        ui::set_quiet(config.is_quiet());
+        print_generation_warnings(&config);
+        let repos = open_repositories(&repo_paths)?;
+        let (_, failed) = mode.execute_in_each(config, &repos, true).await;
+        return match failed {
+            0 => Ok(()),
+            failed => Err(format!("{} of {} repositories failed", failed, repos.len()).into()),
+        };
+    }
+    let repo = Repository::open(&repo_path)
+        .map_err(|e| format!("Invalid git repository path {}: {}", repo_path, e.message()))?;
+
+    let config = apply_args(Config::load(&repo_path)?, &args);
+    ui::set_quiet(config.is_quiet());
+    print_generation_warnings(&config);
+
+    mode.execute_headless(&config, &repo).await
+}
+
+/// `--clear-cache`: empties the response cache of each repository on the command line
+fn clear_response_caches(args: &cli::Args) -> Result<(), Box<dyn Error>> {
+    for (path, repo) in open_repositories(&input::expand_repo_paths(&args.repo_paths)?)? {
+        let removed = response_cache::clear(repo.path())?;
+        println!("🧹 Removed {} cached response{} from {}", removed, if removed == 1 { "" } else { "s" }, path);
+    }
+    Ok(())
+}
+
+/// The repository paths from the command line, globs expanded. A patch only needs one,
+/// to read the settings from.
+fn repository_paths(args: &cli::Args) -> Result<Vec<String>, Box<dyn Error>> {
+    let paths = input::expand_repo_paths(&args.repo_paths)?;
+    if args.patch.is_some() && paths.len() > 1 {
+        return Err("--patch works on one set of changes, so it takes a single repository path".into());
+    }
+    Ok(paths)
+}
+
+/// Opens each of `paths`, skipping with a warning those that aren't git repositories.
+/// Fails if none of them is.
+fn open_repositories(paths: &[String]) -> Result<Vec<(String, Repository)>, Box<dyn Error>> {
+    let mut repos = Vec::new();
+    for path in paths {
+        match Repository::open(path) {
+            Ok(repo) => repos.push((path.clone(), repo)),
+            Err(e) => eprintln!("⚠️ Skipping {}: {}", path, e.message()),
+        }
     }
+    if repos.is_empty() {
+        return Err("None of the given paths is a git repository".into());
+    }
+    Ok(repos)
+}
 
-    pub async fn analyze_contributor(&self, stats: &str) -> Result<String, Box<dyn Error>> {
-        self.model.analyze_contributor(stats).await
+/// Configured temperatures can be valid for one provider and not another, so this runs
+/// whenever the provider changes
+fn print_generation_warnings(config: &Config) {
+    for warning in config.generation_warnings() {
+        eprintln!("⚠️ {}", warning);
     }
 }
 
-pub async fn run(_repo_path: Option<String>) -> Result<(), Box<dyn Error>> {
-    let repo_path = loop {
-        let path = ui::get_repository_path(".")?;
-        match Repository::open(&path) {
-            Ok(_) => break path,
-            Err(_) => println!("Invalid git repository path. Please try again."),
+/// Says which mode runs instead of the menu, so it's clear where it came from
+fn announce_default_mode(mode: modes::Mode) -> modes::Mode {
+    println!("▶️ {} (default_mode in the config)\n", mode.description());
+    mode
+}
+
+/// Choices in the menu shown after each mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NextStep {
+    Continue,
+    UndoCommit,
+    SwitchProvider,
+    SwitchModel,
+    SwitchRepository,
+    Exit,
+}
+
+pub async fn run(args: cli::Args) -> Result<(), Box<dyn Error>> {
+    logging::init(args.verbose);
+    if args.clear_cache {
+        return clear_response_caches(&args);
+    }
+    if args.headless {
+        return run_headless(args).await;
+    }
+
+    let repo_paths = repository_paths(&args)?;
+    let default_path = repo_paths[0].clone();
+    // The repository prompt comes before the settings are loaded, so it would have the default colors
+    if let Some(theme) = config_file::ConfigFile::load(std::path::Path::new(&default_path)).ok().and_then(|settings| settings.theme) {
+        ui::set_theme(theme);
+    }
+    let (mut source, repo_path) = match &args.patch {
+        // Settings are still read from the current (or given) directory
+        Some(path) => (input::DiffSource::Patch(input::Patch::read(path)?), default_path),
+        // Settings are read from the first one and apply to all of them
+        None if repo_paths.len() > 1 => {
+            let repos = open_repositories(&repo_paths)?;
+            let first = repos[0].0.clone();
+            (input::DiffSource::Repositories(repos), first)
         }
+        None => loop {
+            let path = ui::get_repository_path(&default_path)?;
+            match Repository::open(&path) {
+                Ok(repo) => break (input::DiffSource::Repository(repo), path),
+                Err(_) => println!("Invalid git repository path. Please try again."),
+            }
+        },
     };
 
-    let mut config = {
-        let providers = providers::get_available_providers();
-        let selected_idx = providers::select_provider(&providers)?;
-        Config::new(git_analysis::wrap_provider(providers.into_iter().nth(selected_idx).unwrap()), Some(repo_path))
+    if let (Some(repo), false) = (source.repository(), args.no_setup) {
+        if config_file::is_first_run(std::path::Path::new(&repo_path)) {
+            Config::run_setup_wizard(repo).await?;
+        }
+    }
+
+    let settings = load_settings(&repo_path)?;
+    let provider = match &settings.provider {
+        Some(name) => Some(configured_provider(name)?),
+        None => {
+            let providers = providers::get_available_providers();
+            match providers.len() {
+                0 => None,
+                _ => {
+                    let selected_idx = providers::select_provider(&providers)?;
+                    providers.into_iter().nth(selected_idx)
+                }
+            }
+        }
     };
+    let config = match provider {
+        Some(provider) => Config::new(git_analysis::wrap_provider(provider), Some(repo_path)),
+        None => {
+            eprintln!("⚠️ {}. Until then only contributor statistics can be browsed.\n", providers::ProviderError::NoProvidersAvailable);
+            Config::without_provider(Some(repo_path))
+        }
+    };
+    let mut config = apply_args(with_commitlint(config).with_settings(&settings), &args);
+    check_configured_model(&config, &settings)?;
+    ui::set_quiet(config.is_quiet());
+    print_generation_warnings(&config);
     
-    let mut repo = Repository::open(&config.repo_path)?;
+    // Ask once per provider; the choice is kept for the rest of the session
+    if settings.model.is_none() && std::env::var_os("NOAH_MODEL").is_none() {
+        config = select_model(config)?;
+    }
+    config = with_env_models(config)?;
+    // Quitting a menu exits right away, without coming back here
+    if config.usage_summary() {
+        let usage = config.usage.clone();
+        shutdown::on_exit(move || {
+            if let Ok(usage) = usage.lock() {
+                print_usage_summary(&usage);
+            }
+        });
+    }
 
+    // Only the first time round; "Do something else" always shows the menu
+    let mut first_round = true;
     loop {
-        let mode = ui::select_mode().await?;
-        mode.execute(&config, &repo).await?;
+        let preselected = config.default_mode().map(|default| default.mode());
+        let default_mode = config.default_mode().filter(|_| first_round);
+        first_round = false;
+        let result = match &source {
+            input::DiffSource::Repository(repo) => {
+                let mode = match default_mode.and_then(|default| default.pick(repo)) {
+                    Some(mode) => announce_default_mode(mode),
+                    None => ui::select_mode(modes::Mode::all(), preselected).await?,
+                };
+                mode.execute(&config, repo).await
+            }
+            input::DiffSource::Patch(patch) => {
+                println!("📄 Using the changes from {}\n", patch.name());
+                let mode = ui::select_mode(modes::Mode::for_patch(), preselected).await?;
+                mode.execute_patch(&config, patch).await
+            }
+            input::DiffSource::Repositories(repos) => {
+                println!("📚 Working in {} repositories\n", repos.len());
+                let mode = match default_mode.and_then(|default| repos.iter().find_map(|(_, repo)| default.pick(repo))) {
+                    Some(mode) => announce_default_mode(mode),
+                    None => ui::select_mode(modes::Mode::all(), preselected).await?,
+                };
+                // Failures were reported per repository already
+                (config, _) = mode.execute_in_each(config, repos, false).await;
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            // Keep the session alive so a failed request doesn't lose the user's place
+            ui::clear_spinner();
+            eprintln!("\n❌ {}\n", e);
+        }
 
-        let options = ["✨ Do something else", "🤖 Switch AI model", "📁 Switch repository", "❌ Exit"];
-        match ui::show_selection_menu("What would you like to do next?", &options, 0)? {
-            0 => (),  // Continue loop
-            1 => {
+        let mut steps = vec![
+            ("✨ Do something else", NextStep::Continue),
+            ("🤖 Switch AI provider", NextStep::SwitchProvider),
+            ("🧠 Switch model", NextStep::SwitchModel),
+            ("📁 Switch repository", NextStep::SwitchRepository),
+            ("❌ Exit", NextStep::Exit),
+        ];
+        if config.session_commit().is_some() && source.repository().is_some() {
+            steps.insert(1, ("↩️ Undo last commit", NextStep::UndoCommit));
+        }
+        let labels: Vec<&str> = steps.iter().map(|(label, _)| *label).collect();
+        match steps[ui::show_selection_menu("What would you like to do next?", &labels, 0)?].1 {
+            NextStep::Continue => (),
+            NextStep::UndoCommit => {
+                if let Some(repo) = source.repository() {
+                    if let Err(e) = modes::undo_session_commit(&config, repo) {
+                        eprintln!("\n❌ {}\n", e);
+                    }
+                }
+                continue;
+            }
+            NextStep::SwitchProvider => {
                 let providers = providers::get_available_providers();
+                if providers.is_empty() {
+                    eprintln!("\n❌ {}\n", providers::ProviderError::NoProvidersAvailable);
+                    continue;
+                }
                 let selected_idx = providers::select_provider(&providers)?;
                 config = config.with_new_model(git_analysis::wrap_provider(providers.into_iter().nth(selected_idx).unwrap()));
+                print_generation_warnings(&config);
+                config = select_model(config)?;
             }
-            2 => {
+            NextStep::SwitchModel => config = select_model(config)?,
+            NextStep::SwitchRepository => {
                 let new_path = loop {
                     let path = ui::get_repository_path(".")?;
                     match Repository::open(&path) {
                         Ok(new_repo) => {
-                            repo = new_repo;
+                            source = input::DiffSource::Repository(new_repo);
                             break path;
                         }
                         Err(_) => println!("Invalid git repository path. Please try again."),
@@ -112,10 +2828,133 @@ pub async fn run(_repo_path: Option<String>) -> Result<(), Box<dyn Error>> {
                 };
                 config = config.with_new_repo(new_path);
             }
-            _ => break,
+            NextStep::Exit => break,
         }
-        println!("\x1B[2J\x1B[1;1H"); // Clear screen
+        ui::clear_screen();
+    }
+    if config.usage_summary() {
+        print_usage_summary(&config.usage());
     }
     
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// Prints the `usage_summary` line, unless nothing was asked of the model
+fn print_usage_summary(usage: &SessionUsage) {
+    if let Some(summary) = usage.summary() {
+        println!("\n📊 This session: {} (estimated, counted locally)", summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(message: &CommitMessage) -> (&str, Option<&str>, Option<&str>) {
+        (&message.summary, message.body.as_deref(), message.footer.as_deref())
+    }
+
+    #[test]
+    fn a_single_line_is_only_a_summary() {
+        let message = CommitMessage::parse("\n  feat: add login  \n\n");
+        assert_eq!(parts(&message), ("feat: add login", None, None));
+        assert_eq!(message.to_string(), "feat: add login");
+    }
+
+    #[test]
+    fn paragraphs_after_the_summary_are_the_body() {
+        let text = "feat: add login\n\nAdds a login form.\n\nSessions last a day.";
+        let message = CommitMessage::parse(text);
+        assert_eq!(parts(&message), ("feat: add login", Some("Adds a login form.\n\nSessions last a day."), None));
+        assert_eq!(message.to_string(), text);
+        // Models don't always leave the blank line
+        assert_eq!(parts(&CommitMessage::parse("fix: typo\nIn the README.")), ("fix: typo", Some("In the README."), None));
+    }
+
+    #[test]
+    fn a_last_paragraph_of_trailers_is_the_footer() {
+        let text = "fix: keep sessions\n\nRefs #12\nBREAKING CHANGE: sessions are kept\nCo-authored-by: Grace Hopper <grace@example.com>";
+        let message = CommitMessage::parse(text);
+        assert_eq!(parts(&message).1, None);
+        assert_eq!(parts(&message).2, Some(&text["fix: keep sessions\n\n".len()..]));
+        assert_eq!(message.to_string(), text);
+
+        let message = CommitMessage::parse("fix: keep sessions\n\nThey expired early.\n\nRefs #12");
+        assert_eq!(parts(&message), ("fix: keep sessions", Some("They expired early."), Some("Refs #12")));
+    }
+
+    #[test]
+    fn a_last_paragraph_that_only_looks_like_trailers_stays_in_the_body() {
+        let message = CommitMessage::parse("fix: keep sessions\n\nNote: they expired early\nbecause of the clock.");
+        assert_eq!(parts(&message), ("fix: keep sessions", Some("Note: they expired early\nbecause of the clock."), None));
+
+        let message = CommitMessage::parse("fix: keep sessions\n\nFixed the bug: sessions expired early");
+        assert_eq!(parts(&message).2, None);
+        assert!(!is_trailer("Reviewed by: Grace") && is_trailer("Reviewed-by: Grace"));
+    }
+
+    fn template(template: &str) -> CommitTemplate {
+        CommitTemplate::new(template, None).unwrap()
+    }
+
+    #[test]
+    fn tickets_are_found_in_branch_names() {
+        assert_eq!(template("{ticket}: {message}").ticket("feature/ABC-123-login").as_deref(), Some("ABC-123"));
+        assert_eq!(template("{ticket}: {message}").ticket("main"), None);
+        let numbered = CommitTemplate::new("{message} (#{ticket})", Some(r"issue-(\d+)")).unwrap();
+        assert_eq!(numbered.ticket("fix/issue-42-typo").as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn templates_round_trip_with_a_ticket() {
+        for (text, applied) in [
+            ("{ticket}: {message}", "ABC-1: add login"),
+            ("[{ticket}] {message}", "[ABC-1] add login"),
+            ("{message} ({ticket})", "add login (ABC-1)"),
+        ] {
+            let template = template(text);
+            assert_eq!(template.apply("add login", Some("ABC-1")), applied);
+            assert_eq!(template.strip(applied, Some("ABC-1")), "add login");
+        }
+    }
+
+    #[test]
+    fn templates_round_trip_without_a_ticket() {
+        for text in ["{ticket}: {message}", "[{ticket}] {message}", "{message} ({ticket})", "{message}"] {
+            let template = template(text);
+            assert_eq!(template.apply("add login", None), "add login", "{}", text);
+            assert_eq!(template.strip("add login", None), "add login");
+        }
+        let prefixed = template("feat: {message}");
+        assert_eq!(prefixed.apply("add login", None), "feat: add login");
+        assert_eq!(prefixed.strip("feat: add login", None), "add login");
+    }
+
+    #[test]
+    fn text_introducing_a_missing_ticket_is_left_out() {
+        for (text, applied) in [
+            ("Ticket {ticket}: {message}", "add login"),
+            ("feat({ticket}): {message}", "feat: add login"),
+            ("[{ticket}] feat: {message}", "feat: add login"),
+            ("feat: [{ticket}] {message}", "feat: add login"),
+            ("feat: #{ticket} {message}", "feat: add login"),
+            ("{message} (closes #{ticket})", "add login"),
+            ("{message} | {ticket}", "add login"),
+            ("{message}\n\nRefs: {ticket}", "add login"),
+        ] {
+            let template = template(text);
+            assert_eq!(template.apply("add login", None), applied, "{}", text);
+            assert_eq!(template.strip(applied, None), "add login", "{}", text);
+        }
+        // With the ticket every bit of the template is kept
+        assert_eq!(template("Ticket {ticket}: {message}").apply("add login", Some("ABC-1")), "Ticket ABC-1: add login");
+        assert_eq!(template("{message}\n\nRefs: {ticket}").apply("add login", Some("ABC-1")), "add login\n\nRefs: ABC-1");
+    }
+
+    #[test]
+    fn summaries_that_mention_the_ticket_are_left_alone() {
+        let template = template("{ticket}: {message}");
+        assert_eq!(template.apply("fix ABC-1 crash", Some("ABC-1")), "fix ABC-1 crash");
+        assert_eq!(template.strip("unrelated summary", Some("ABC-1")), "unrelated summary");
+    }
+}
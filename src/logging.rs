@@ -0,0 +1,73 @@
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::{providers, ui};
+
+/// Writes noah's own log records to stderr, leaving out those of its dependencies
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = redact(&format!("[{} {}] {}", record.level(), record.target(), record.args()));
+        ui::suspend_spinner(|| eprintln!("{}", line));
+    }
+
+    fn flush(&self) {}
+}
+
+/// Turns on logging of AI requests when `verbose` is set or `RUST_LOG` asks for it,
+/// e.g. `RUST_LOG=debug` or `RUST_LOG=merit_cli_demo=info`. Without either nothing is logged.
+pub fn init(verbose: bool) {
+    let level = if verbose {
+        LevelFilter::Debug
+    } else {
+        std::env::var("RUST_LOG").ok().and_then(|spec| level_from_spec(&spec)).unwrap_or(LevelFilter::Off)
+    };
+    if level != LevelFilter::Off && log::set_logger(&StderrLogger).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// The level a `RUST_LOG` value sets for this crate: a bare level, or one given for
+/// the crate by name. The last matching directive wins, like in `env_logger`.
+fn level_from_spec(spec: &str) -> Option<LevelFilter> {
+    spec.split(',')
+        .filter_map(|directive| match directive.split_once('=') {
+            Some((target, level)) if target.trim() == env!("CARGO_CRATE_NAME") => level.trim().parse().ok(),
+            Some(_) => None,
+            None => directive.trim().parse().ok(),
+        })
+        .next_back()
+}
+
+/// `text` with every API key the providers may use replaced, whether it came from the
+/// environment, the config files or the setup wizard
+fn redact(text: &str) -> String {
+    providers::known_api_keys()
+        .into_iter()
+        .fold(text.to_string(), |text, secret| text.replace(&secret, "[redacted]"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_keys_are_redacted() {
+        providers::set_api_key("NOAH_TEST_LOGGED_KEY", "sk-logged-secret");
+        assert_eq!(redact("Authorization: Bearer sk-logged-secret"), "Authorization: Bearer [redacted]");
+    }
+
+    #[test]
+    fn keys_containing_others_are_redacted_whole() {
+        providers::set_api_key("NOAH_TEST_SHORT_KEY", "sk-nested");
+        providers::set_api_key("NOAH_TEST_LONG_KEY", "sk-nested-longer");
+        assert_eq!(redact("sk-nested-longer and sk-nested"), "[redacted] and [redacted]");
+    }
+}
@@ -1,11 +1,14 @@
-use dotenv;
-use merit_cli_demo::run;
-use std::env;
+use clap::Parser;
+use merit_cli_demo::{install_ctrl_c_handler, run, Args};
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
     dotenv::dotenv().ok();
+    install_ctrl_c_handler();
     
-    let repo_path = env::args().nth(1);
-    run(repo_path).await
-}
\ No newline at end of file
+    // The message, not the Debug output `main` would print for a returned error
+    if let Err(e) = run(Args::parse()).await {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    }
+}
@@ -1,196 +1,2436 @@
 use std::error::Error;
+use std::path::Path;
+use futures::StreamExt;
 use git2::Repository;
 
+use crate::cli::OutputFormat;
+use crate::export::{self, ExportFormat};
 use crate::git;
+use crate::hook::{self, HookAction, HookStatus};
+use crate::input::{self, Patch};
+use crate::shutdown;
 use crate::ui;
-use crate::Config;
+use crate::{CommitGroup, CommitMessage, Config, FileAnalysis, FileAnalysisError, FileAnalysisResult, Level, LintWarning, NoahError, ReviewFinding, RiskLevel};
 
-#[derive(Debug)]
+/// What noah does in a run, picked with `--mode` or from the main menu. Settings such as
+/// `Config::with_mode_timeout` can differ per mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
 pub enum Mode {
+    #[value(name = "commit")]
     CommitMessage,
+    #[value(name = "stage")]
+    InteractiveStage,
+    #[value(name = "split")]
+    SplitCommits,
+    #[value(name = "files")]
     FileAnalysis,
+    #[value(name = "review")]
+    Review,
+    #[value(name = "failure")]
+    ExplainFailure,
+    #[value(name = "branch")]
+    BranchName,
+    #[value(name = "explain")]
+    ExplainCommit,
+    #[value(name = "reword")]
+    RewordCommit,
+    #[value(name = "contributors")]
     ContributorAnalysis,
+    #[value(name = "report")]
+    ContributorReport,
+    #[value(name = "changelog")]
+    Changelog,
+    #[value(name = "pr")]
+    PullRequest,
+    #[value(name = "hook")]
+    InstallHook,
+}
+
+/// The mode an interactive session starts with instead of the main menu, see
+/// `Config::with_default_mode`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefaultMode {
+    /// Generate a commit message when something is staged, otherwise show the menu
+    Auto,
+    Mode(Mode),
+}
+
+impl DefaultMode {
+    /// "auto" or a mode by its `--mode` name, e.g. "commit"
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            name if name.eq_ignore_ascii_case("auto") => Some(DefaultMode::Auto),
+            name => <Mode as clap::ValueEnum>::from_str(name, true).ok().map(DefaultMode::Mode),
+        }
+    }
+
+    /// The mode to run in `repo` without showing the menu, if any
+    pub fn pick(&self, repo: &Repository) -> Option<Mode> {
+        match self {
+            DefaultMode::Auto => git::diff_stat(repo, git::DiffScope::Staged).is_ok_and(|stat| stat.files_changed > 0).then_some(Mode::CommitMessage),
+            DefaultMode::Mode(mode) => Some(*mode),
+        }
+    }
+
+    /// The mode to preselect in the menu
+    pub fn mode(&self) -> Mode {
+        match self {
+            DefaultMode::Auto => Mode::CommitMessage,
+            DefaultMode::Mode(mode) => *mode,
+        }
+    }
 }
 
 impl Mode {
+    /// All modes, in the order they are offered in the main menu
+    pub fn all() -> Vec<Mode> {
+        vec![
+            Mode::CommitMessage,
+            Mode::InteractiveStage,
+            Mode::SplitCommits,
+            Mode::FileAnalysis,
+            Mode::Review,
+            Mode::ExplainFailure,
+            Mode::BranchName,
+            Mode::ExplainCommit,
+            Mode::RewordCommit,
+            Mode::ContributorAnalysis,
+            Mode::ContributorReport,
+            Mode::Changelog,
+            Mode::PullRequest,
+            Mode::InstallHook,
+        ]
+    }
+
+    /// Modes that can work on a patch instead of a repository
+    pub fn for_patch() -> Vec<Mode> {
+        vec![Mode::CommitMessage, Mode::FileAnalysis, Mode::Review, Mode::ExplainFailure]
+    }
+
     pub fn description(&self) -> &'static str {
         match self {
             Mode::CommitMessage => "📝 Generate commit message",
+            Mode::InteractiveStage => "🧩 Stage hunks interactively",
+            Mode::SplitCommits => "🪓 Split into several commits",
             Mode::FileAnalysis => "🔍 Analyze file changes", 
+            Mode::Review => "🧐 Review changes",
+            Mode::ExplainFailure => "🚨 Explain a failure",
+            Mode::BranchName => "🌿 Suggest a branch name",
+            Mode::ExplainCommit => "🕰️ Explain a commit",
+            Mode::RewordCommit => "✏️ Reword an older commit",
             Mode::ContributorAnalysis => "👥 Analyze contributors",
+            Mode::ContributorReport => "📒 Write contributor report",
+            Mode::Changelog => "📜 Generate changelog",
+            Mode::PullRequest => "🔀 Describe a pull request",
+            Mode::InstallHook => "🪝 Set up git commit hook",
+        }
+    }
+
+    /// What the mode does, in a sentence, for the help screen
+    pub fn help(&self) -> &'static str {
+        match self {
+            Mode::CommitMessage => "Suggests a commit message for your changes, which you can regenerate, edit and commit.",
+            Mode::InteractiveStage => "Walks through each changed hunk so you can pick what to stage, then suggests a message for just those changes.",
+            Mode::SplitCommits => "Suggests how to split all your changes into several focused commits, which you can merge or skip before they are committed one by one.",
+            Mode::FileAnalysis => "Explains the changes to each file you select, grouped by directory, and can export the results.",
+            Mode::Review => "Reviews your uncommitted changes the way a code reviewer would, listing likely bugs, style issues and suggestions by severity.",
+            Mode::ExplainFailure => "Explains an error log you paste, e.g. of a failed build or test run, in the light of your changes, with a suggested fix.",
+            Mode::BranchName => "Suggests a short branch name for your changes, staged ones first, and creates and checks out the branch so you can commit there.",
+            Mode::ExplainCommit => "Explains what an existing commit or range of commits (e.g. HEAD~2 or main..feature) changed, file by file.",
+            Mode::RewordCommit => "Writes a new message for an earlier commit on the current branch from its diff and rewrites it and the commits after it.",
+            Mode::ContributorAnalysis => "Summarizes who works on what, per contributor or for the whole team, optionally for a date range or path.",
+            Mode::ContributorReport => "Analyzes every contributor at once and writes the results, with a table of contents, to contributors.md.",
+            Mode::Changelog => "Writes release notes for the commits since the last tag and can add them to CHANGELOG.md.",
+            Mode::PullRequest => "Writes a title and description (summary, changes, testing notes) for what the current branch changed since it left the base branch, to copy or save.",
+            Mode::InstallHook => "Installs (or removes) a prepare-commit-msg hook so that a plain `git commit` starts with a suggested message.",
+        }
+    }
+
+    /// Whether the mode looks at or commits working tree changes, or hooks into committing them
+    fn needs_worktree(&self) -> bool {
+        matches!(self, Mode::CommitMessage | Mode::InteractiveStage | Mode::SplitCommits | Mode::FileAnalysis | Mode::Review | Mode::ExplainFailure | Mode::BranchName | Mode::InstallHook)
+    }
+
+    /// Whether there is nothing for the mode to do in a repository without changes
+    fn works_on_changes(&self) -> bool {
+        matches!(self, Mode::CommitMessage | Mode::InteractiveStage | Mode::SplitCommits | Mode::FileAnalysis | Mode::Review | Mode::BranchName)
+    }
+
+    /// Whether the mode works on the current branch since it left the base branch
+    /// instead, see `Config::against_base`
+    fn compares_with_base(&self, config: &Config) -> bool {
+        config.against_base() && matches!(self, Mode::CommitMessage | Mode::FileAnalysis | Mode::ExplainFailure)
+    }
+
+    /// Whether the mode only looks at existing commits
+    fn needs_history(&self) -> bool {
+        matches!(self, Mode::ExplainCommit | Mode::RewordCommit | Mode::ContributorAnalysis | Mode::ContributorReport | Mode::Changelog | Mode::PullRequest)
+    }
+
+    /// Fails before any work is done if the mode needs AI but no provider is set up.
+    /// Contributor statistics can be browsed without one; only their AI summary fails.
+    fn check_provider(&self, config: &Config) -> Result<(), Box<dyn Error>> {
+        match self {
+            // The hook checks for a provider every time it runs
+            Mode::ContributorAnalysis | Mode::InstallHook => Ok(()),
+            _ => config.require_provider().map_err(Into::into),
+        }
+    }
+
+    /// Rejects repositories the mode can't work with, with a message saying what to do instead
+    fn check_repository(&self, repo: &Repository) -> Result<(), Box<dyn Error>> {
+        if self.needs_worktree() && repo.is_bare() {
+            return Err(format!(
+                "This command needs a working tree, but {} is a bare repository. Run it in a clone with checked-out files.",
+                repo.path().display()
+            ).into());
+        }
+        if self.needs_history() && repo.head().is_err() {
+            return Err("This repository has no commits yet, so there is no history to analyze.".into());
+        }
+        Ok(())
+    }
+
+    /// Warning for modes that commit while HEAD isn't on a branch
+    fn detached_head_warning(&self, repo: &Repository) -> Option<&'static str> {
+        let commits = matches!(self, Mode::CommitMessage | Mode::InteractiveStage | Mode::SplitCommits);
+        (commits && repo.head_detached().unwrap_or(false))
+            .then_some("HEAD is detached, so new commits won't be on any branch. Run `git switch -c <branch>` first to keep them.")
+    }
+
+    pub async fn execute(&self, config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+        self.check_provider(config)?;
+        self.check_repository(repo)?;
+        if let Some(warning) = self.detached_head_warning(repo) {
+            println!("⚠️ {}\n", warning);
+        }
+
+        match self {
+            Mode::CommitMessage => handle_commit_message(config, repo).await,
+            Mode::InteractiveStage => handle_interactive_stage(config, repo).await,
+            Mode::SplitCommits => handle_split_commits(config, repo).await,
+            Mode::FileAnalysis => handle_file_analysis(config, repo).await,
+            Mode::Review => handle_review(config, repo).await,
+            Mode::ExplainFailure => handle_explain_failure(config, repo).await,
+            Mode::BranchName => handle_branch_name(config, repo).await,
+            Mode::ExplainCommit => handle_explain_commit(config, repo).await,
+            Mode::RewordCommit => handle_reword_commit(config, repo).await,
+            Mode::ContributorAnalysis => handle_contributor_analysis(config, repo).await,
+            Mode::ContributorReport => handle_contributor_report(config, repo).await,
+            Mode::Changelog => handle_changelog(config, repo).await,
+            Mode::PullRequest => handle_pull_request(config, repo).await,
+            Mode::InstallHook => handle_install_hook(repo),
+        }
+    }
+
+    /// Runs the mode without any menus or prompts, writing plain output to stdout
+    /// so it can be piped or used from scripts and CI
+    pub async fn execute_headless(&self, config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+        self.check_provider(config)?;
+        self.check_repository(repo)?;
+        if let Some(warning) = self.detached_head_warning(repo) {
+            eprintln!("{}", warning);
+        }
+        // Set when run from the commit message hook
+        git::use_index_from_env(repo)?;
+
+        match self {
+            Mode::CommitMessage => headless_commit_message(config, repo).await,
+            Mode::InteractiveStage => Err("Interactive staging needs a terminal and can't run headless".into()),
+            Mode::SplitCommits => headless_split_commits(config, repo).await,
+            Mode::FileAnalysis => headless_file_analysis(config, repo).await,
+            Mode::Review => headless_review(config, repo).await,
+            Mode::ExplainFailure => headless_explain_failure(config, repo).await,
+            Mode::BranchName => headless_branch_name(config, repo).await,
+            Mode::ExplainCommit => headless_explain_commit(config, repo).await,
+            Mode::RewordCommit => headless_reword_commit(config, repo).await,
+            Mode::ContributorAnalysis => headless_contributor_analysis(config, repo).await,
+            Mode::ContributorReport => headless_contributor_report(config, repo).await,
+            Mode::Changelog => headless_changelog(config, repo).await,
+            Mode::PullRequest => headless_pull_request(config, repo).await,
+            Mode::InstallHook => headless_install_hook(config, repo),
+        }
+    }
+
+    /// Runs the mode in each of `repos` in turn, under a header naming the repository.
+    /// Repositories without changes are skipped by the modes that work on changes, and a
+    /// failure in one repository doesn't stop the others. Returns the config, now for the
+    /// last repository, and how many repositories failed.
+    pub async fn execute_in_each(&self, mut config: Config, repos: &[(String, Repository)], headless: bool) -> (Config, usize) {
+        let mut failed = 0;
+        for (path, repo) in repos {
+            config = config.with_new_repo(path.clone());
+            ui::print_section(&format!("📁 {}", path));
+            let works_on_changes = self.works_on_changes() && !self.compares_with_base(&config);
+            let result = match git::has_changes(repo) {
+                Ok(false) if works_on_changes => {
+                    println!("No changes, skipping.\n");
+                    continue;
+                }
+                Err(e) if works_on_changes => Err(e.into()),
+                _ if headless => self.execute_headless(&config, repo).await,
+                _ => self.execute(&config, repo).await,
+            };
+            if let Err(e) = result {
+                ui::clear_spinner();
+                eprintln!("❌ {}: {}\n", path, e);
+                failed += 1;
+            }
+        }
+        (config, failed)
+    }
+
+    /// Runs the mode on the changes in `patch`. Nothing can be committed, so only the
+    /// message or analysis is shown.
+    pub async fn execute_patch(&self, config: &Config, patch: &Patch) -> Result<(), Box<dyn Error>> {
+        self.check_provider(config)?;
+        match self {
+            Mode::CommitMessage => {
+                let diff = git::truncate_diff(patch.diff(), config.max_diff_bytes());
+                review_and_commit(config, None, diff, git::DiffScope::All, false).await
+            }
+            Mode::FileAnalysis => analyze_selected_files(config, None, config.patch_file_diffs(patch)?).await,
+            Mode::Review => review_diff(config, &git::truncate_diff(patch.diff(), config.max_diff_bytes())).await,
+            Mode::ExplainFailure => explain_failure(config, &git::truncate_diff(patch.diff(), config.max_diff_bytes())).await,
+            _ => Err(self.patch_unsupported(patch)),
+        }
+    }
+
+    /// `execute_patch` without any menus or prompts
+    pub async fn execute_patch_headless(&self, config: &Config, patch: &Patch) -> Result<(), Box<dyn Error>> {
+        self.check_provider(config)?;
+        match self {
+            Mode::CommitMessage => {
+                headless_message_for(config, &git::truncate_diff(patch.diff(), config.max_diff_bytes())).await
+            }
+            Mode::FileAnalysis => headless_analyze_files(config, config.patch_file_diffs(patch)?).await,
+            Mode::Review => headless_review_diff(config, &git::truncate_diff(patch.diff(), config.max_diff_bytes())).await,
+            // `--log` can't be combined with `--patch`, and headless runs have no editor to paste into
+            Mode::ExplainFailure => Err("Headless runs read the error log from --log, which can't be combined with --patch".into()),
+            _ => Err(self.patch_unsupported(patch)),
+        }
+    }
+
+    fn patch_unsupported(&self, patch: &Patch) -> Box<dyn Error> {
+        format!("This mode works on the repository history and can't use the patch from {}", patch.name()).into()
+    }
+}
+
+async fn headless_commit_message(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    if config.against_base() {
+        return match branch_diffs(config, repo, |notice| eprintln!("{}", notice)) {
+            Ok((_, file_diffs)) => headless_message_for(config, &join_file_diffs(config, file_diffs)).await,
+            Err(NoahError::NoChanges) => {
+                eprintln!("No commits on this branch since {}.", config.base_branch(repo)?);
+                print_json_if_requested(config, &None::<CommitMessage>)
+            }
+            Err(e) => Err(e.into()),
+        };
+    }
+    match initial_commit_diff(config, repo, |notice| eprintln!("{}", notice)) {
+        Ok((_, diff)) => headless_message_for(config, &diff).await,
+        Err(NoahError::NoChanges) => {
+            eprintln!("No changes to commit.");
+            print_json_if_requested(config, &None::<CommitMessage>)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Prints the suggested commits without committing anything: each message followed by its
+/// files, or a JSON array of `files` and `message`
+async fn headless_split_commits(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let full_diff = match git::get_diff_scoped(repo, git::DiffScope::All, config.diff_context_lines()) {
+        Ok(diff) => diff,
+        Err(NoahError::NoChanges) => {
+            eprintln!("No changes to commit.");
+            return print_json_if_requested(config, &Vec::<CommitGroup>::new());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let Some(diff) = guard_diff(config, &git::truncate_diff(&full_diff, config.max_diff_bytes()), false)? else {
+        return Ok(());
+    };
+    let groups = config.suggest_commit_groups(&diff).await?;
+    let ungrouped = ungrouped_files(&full_diff, &groups);
+    if !ungrouped.is_empty() {
+        eprintln!("These files are in no group and would be left uncommitted: {}", ungrouped.join(", "));
+    }
+    match config.output_format() {
+        OutputFormat::Text => {
+            for group in &groups {
+                println!("{}\n", group.message);
+                for file in &group.files {
+                    println!("- {}", file);
+                }
+                println!();
+            }
+        }
+        OutputFormat::Json => print_json(&groups)?,
+    }
+    Ok(())
+}
+
+/// Prints a commit message generated for `diff`
+async fn headless_message_for(config: &Config, diff: &str) -> Result<(), Box<dyn Error>> {
+    let Some(diff) = guard_diff(config, diff, false)? else {
+        return Ok(());
+    };
+    let message = match config.trivial_commit_message(&diff) {
+        Some(message) => {
+            eprintln!("{}", trivial_diff_note(config));
+            message
+        }
+        None => config.generate_commit_message(&diff, false).await?,
+    };
+    match config.output_format() {
+        OutputFormat::Text => println!("{}", message),
+        OutputFormat::Json => print_json(&message)?,
+    }
+    Ok(())
+}
+
+fn print_json(value: &impl serde::Serialize) -> Result<(), Box<dyn Error>> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// For JSON output, prints `value` so there is a document even when nothing was found;
+/// text output has already said so on stderr
+fn print_json_if_requested(config: &Config, value: &impl serde::Serialize) -> Result<(), Box<dyn Error>> {
+    match config.output_format() {
+        OutputFormat::Text => Ok(()),
+        OutputFormat::Json => print_json(value),
+    }
+}
+
+async fn headless_file_analysis(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    if config.against_base() {
+        return match branch_diffs(config, repo, |notice| eprintln!("{}", notice)) {
+            Ok((_, file_diffs)) => headless_analyze_files(config, file_diffs).await,
+            Err(NoahError::NoChanges) => {
+                eprintln!("No commits on this branch since {}.", config.base_branch(repo)?);
+                print_json_if_requested(config, &Vec::<FileAnalysis>::new())
+            }
+            Err(e) => Err(e.into()),
+        };
+    }
+    if let Some(path) = config.only_file() {
+        let path = git::repo_relative_path(repo, path);
+        let file_diffs = single_file_diffs(config, repo, &path)?;
+        if file_diffs.is_empty() {
+            eprintln!("No changes in {}.", path);
+            return print_json_if_requested(config, &Vec::<FileAnalysis>::new());
+        }
+        return headless_analyze_files(config, file_diffs).await;
+    }
+    match config.file_diffs(repo, None) {
+        Ok(file_diffs) => headless_analyze_files(config, file_diffs).await,
+        Err(NoahError::NoChanges) => {
+            eprintln!("No changes to analyze.");
+            print_json_if_requested(config, &Vec::<FileAnalysis>::new())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Analyzes every file in `file_diffs` and prints the results as markdown, or as a JSON array
+async fn headless_analyze_files(config: &Config, file_diffs: FileDiffs) -> Result<(), Box<dyn Error>> {
+    if file_diffs.is_empty() {
+        eprintln!("All changed files were excluded from analysis.");
+        return print_json_if_requested(config, &Vec::<FileAnalysis>::new());
+    }
+    let file_diffs = guard_secrets(config, file_diffs, false)?.unwrap_or_default();
+    let (analyses, failures) = split_analyses(config.analyze_file_diffs(file_diffs).await);
+    let shown = Level::filter(&analyses, config.min_risk(), |analysis| analysis.risk);
+    if shown.len() < analyses.len() {
+        eprintln!("{} less risky file(s) left out.", analyses.len() - shown.len());
+    }
+    match config.output_format() {
+        OutputFormat::Text if !shown.is_empty() => print!("{}", export::analyses_to_markdown(&shown)),
+        OutputFormat::Text => (),
+        OutputFormat::Json => println!("{}", export::analyses_to_json(&shown)?),
+    }
+    for failure in &failures {
+        eprintln!("Failed to analyze {}", failure);
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Analysis failed for {} of {} files", failures.len(), failures.len() + analyses.len()).into())
+    }
+}
+
+async fn headless_review(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    match git::get_diff_scoped(repo, git::DiffScope::All, config.diff_context_lines()) {
+        Ok(diff) => headless_review_diff(config, &git::truncate_diff(&diff, config.max_diff_bytes())).await,
+        Err(NoahError::NoChanges) => {
+            eprintln!("No changes to review.");
+            print_json_if_requested(config, &Vec::<ReviewFinding>::new())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Prints the findings for `diff` of at least `--severity`, as markdown or a JSON array
+async fn headless_review_diff(config: &Config, diff: &str) -> Result<(), Box<dyn Error>> {
+    let Some(diff) = guard_diff(config, diff, false)? else {
+        return Ok(());
+    };
+    let findings = Level::filter(&config.review_changes(&diff).await?, config.min_severity(), |finding| Some(finding.severity));
+    match config.output_format() {
+        OutputFormat::Text => print!("{}", export::review_to_markdown(&findings)),
+        OutputFormat::Json => print_json(&findings)?,
+    }
+    Ok(())
+}
+
+/// Prints the explanation of the error log from `--log` for the changes, as markdown or a JSON
+/// object with the `explanation`. The log is read first, so a missing one fails fast.
+async fn headless_explain_failure(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let path = config.failure_log().ok_or("--mode failure needs the error log, e.g. --log build.log or --log - for stdin")?;
+    let log = input::read_log(path)?;
+    let diff = failure_diff(config, repo, |notice| eprintln!("{}", notice))?;
+    let Some((diff, log)) = guard_failure(config, &diff, &log, false)? else {
+        return Ok(());
+    };
+    let explanation = config.explain_failure(&diff, &log).await?;
+    match config.output_format() {
+        OutputFormat::Text => println!("{}", explanation),
+        OutputFormat::Json => print_json(&serde_json::json!({ "explanation": explanation }))?,
+    }
+    Ok(())
+}
+
+/// Prints a branch name for the changes, staged ones first. Nothing is created, since
+/// scripts can run `git switch -c` themselves.
+async fn headless_branch_name(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let diff = match initial_commit_diff(config, repo, |_| eprintln!("Nothing is staged, so the name covers all changes.")) {
+        Ok((_, diff)) => diff,
+        Err(NoahError::NoChanges) => {
+            eprintln!("No changes to name a branch after.");
+            return print_json_if_requested(config, &None::<String>);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let Some(diff) = guard_diff(config, &diff, false)? else {
+        return Ok(());
+    };
+    let name = config.suggest_branch_name(&diff).await?;
+    match config.output_format() {
+        OutputFormat::Text => println!("{}", name),
+        OutputFormat::Json => print_json(&name)?,
+    }
+    Ok(())
+}
+
+/// Explains `--rev`, or HEAD without it. Text output starts with the analyzed commits;
+/// JSON output is an object with the `revision`, its `commits` and the `files`.
+async fn headless_explain_commit(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let changes = git::revision_changes(repo, config.revision().unwrap_or("HEAD"), config.diff_context_lines())?;
+    let file_diffs = config.revision_file_diffs(repo, &changes)?;
+    if file_diffs.is_empty() {
+        eprintln!("{} changes no files that can be analyzed.", changes.revision);
+    }
+    let file_diffs = guard_secrets(config, file_diffs, false)?.unwrap_or_default();
+    let (analyses, failures) = split_analyses(config.analyze_file_diffs(file_diffs).await);
+    let shown = Level::filter(&analyses, config.min_risk(), |analysis| analysis.risk);
+    if shown.len() < analyses.len() {
+        eprintln!("{} less risky file(s) left out.", analyses.len() - shown.len());
+    }
+    match config.output_format() {
+        OutputFormat::Text => {
+            println!("# {}\n", changes.revision);
+            for commit in &changes.commits {
+                println!("- {}", commit);
+            }
+            println!();
+            if !shown.is_empty() {
+                print!("{}", export::analyses_to_markdown(&shown));
+            }
+        }
+        OutputFormat::Json => print_json(&serde_json::json!({
+            "revision": changes.revision,
+            "commits": changes.commits,
+            "files": shown,
+        }))?,
+    }
+    for failure in &failures {
+        eprintln!("Failed to analyze {}", failure);
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Analysis failed for {} of {} files", failures.len(), failures.len() + analyses.len()).into())
+    }
+}
+
+/// Prints a new message for `--rev`, or HEAD without it. Rewriting history needs a
+/// confirmation, so nothing is reworded.
+async fn headless_reword_commit(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let plan = git::plan_reword(repo, config.revision().unwrap_or("HEAD"))?;
+    let diff = git::truncate_diff(&git::commit_patch(repo, plan.commit, config.diff_context_lines())?, config.max_diff_bytes());
+    headless_message_for(config, &diff).await?;
+    eprintln!("Nothing was rewritten; run noah in a terminal to reword the commit.");
+    Ok(())
+}
+
+fn headless_install_hook(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    match config.hook_action() {
+        HookAction::Install | HookAction::Replace => {
+            let path = hook::install(repo, config.hook_action() == HookAction::Replace)?;
+            println!("Installed the commit message hook at {}", path.display());
+        }
+        HookAction::Uninstall => {
+            let path = hook::uninstall(repo)?;
+            println!("Removed the commit message hook from {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+async fn headless_changelog(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let (range, commits) = changelog_commits(repo, |notice| eprintln!("{}", notice))?;
+    if commits.is_empty() {
+        eprintln!("No commits in {}.", changelog_range_label(&range));
+        return print_json_if_requested(config, &serde_json::json!({ "range": range, "changelog": null }));
+    }
+    let changelog = config.generate_changelog(&range, &commits).await?;
+    match config.output_format() {
+        OutputFormat::Text => println!("{}", changelog),
+        OutputFormat::Json => print_json(&serde_json::json!({ "range": range, "changelog": changelog }))?,
+    }
+    Ok(())
+}
+
+/// Prints the pull request description for the current branch as markdown, or a JSON
+/// object with the `base`, its `commits` and the `description` (`title`, `body`)
+async fn headless_pull_request(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let base = config.base_branch(repo)?;
+    let (commits, file_diffs) = match branch_diffs(config, repo, |notice| eprintln!("{}", notice)) {
+        Ok(changes) => changes,
+        Err(NoahError::NoChanges) => {
+            eprintln!("No commits on this branch since {}.", base);
+            return print_json_if_requested(config, &serde_json::json!({ "base": base, "commits": [], "description": null }));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let Some(diff) = guard_diff(config, &join_file_diffs(config, file_diffs), false)? else {
+        return Ok(());
+    };
+    let description = config.generate_pr_description(&diff, &commits).await?;
+    match config.output_format() {
+        OutputFormat::Text => print!("{}", description.to_markdown()),
+        OutputFormat::Json => print_json(&serde_json::json!({ "base": base, "commits": commits, "description": description }))?,
+    }
+    Ok(())
+}
+
+async fn headless_contributor_analysis(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let filter = git::ContributorFilter::default();
+    let mut contributors = load_contributors(config, repo, &filter)?;
+    contributors.sort_by(|a, b| b.commit_count.cmp(&a.commit_count).then_with(|| a.name.cmp(&b.name)));
+
+    // JSON output is collected into one array
+    let mut reports = Vec::new();
+    for contributor in &contributors {
+        let commits = git::get_contributor_commits(repo, &contributor.name, &contributor.email, &filter)?;
+        let stats = format_contributor_stats(contributor, &commits, config.recent_commits_shown());
+        let summary = config.analyze_contributor(&stats).await?;
+        match config.output_format() {
+            OutputFormat::Text => println!("{}\n\n### AI Analysis\n{}\n", stats, summary),
+            OutputFormat::Json => reports.push(serde_json::json!({ "stats": contributor, "summary": summary })),
+        }
+    }
+    print_json_if_requested(config, &reports)
+}
+
+/// Writes the contributor report and prints where, or a JSON object with the `path`, the
+/// number of `contributors` and the names whose analysis `failed`
+async fn headless_contributor_report(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let contributors = report_contributors(config, repo)?;
+    if contributors.is_empty() {
+        eprintln!("No commits found.");
+        return print_json_if_requested(config, &serde_json::json!({ "path": null, "contributors": 0, "failed": [] }));
+    }
+    let path = contributor_report_path(repo)?;
+    if path.exists() && !config.is_dry_run() && !config.overwrites() {
+        return Err(format!("{} already exists; pass --force to replace it", path.display()).into());
+    }
+    let (report, failed) = contributor_report(config, &contributors).await;
+    if config.is_dry_run() {
+        eprintln!("Dry run: {} was not written.", path.display());
+        match config.output_format() {
+            OutputFormat::Text => println!("{}", report),
+            OutputFormat::Json => print_json(&serde_json::json!({ "path": null, "contributors": contributors.len(), "failed": failed }))?,
+        }
+    } else {
+        std::fs::write(&path, report)?;
+        match config.output_format() {
+            OutputFormat::Text => println!("Contributor report written to {}", path.display()),
+            OutputFormat::Json => print_json(&serde_json::json!({ "path": path, "contributors": contributors.len(), "failed": failed }))?,
+        }
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Analysis failed for {} of {} contributors", failed.len(), contributors.len()).into())
+    }
+}
+
+async fn handle_commit_message(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    if config.against_base() {
+        // Those commits exist already, so the message is only offered to copy, e.g. as a
+        // pull request description
+        return match branch_diffs(config, repo, |notice| println!("🌿 {}\n", notice)) {
+            Ok((_, file_diffs)) => review_and_commit(config, None, join_file_diffs(config, file_diffs), git::DiffScope::All, false).await,
+            Err(NoahError::NoChanges) => {
+                ui::print_section("📝 Repository Status");
+                println!("No commits on this branch since {}.\n", config.base_branch(repo)?);
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        };
+    }
+    match initial_commit_diff(config, repo, |notice| println!("ℹ️ {}\n", notice)) {
+        Ok((scope, diff)) => review_and_commit(config, Some(repo), diff, scope, true).await,
+        Err(NoahError::NoChanges) => {
+            ui::print_section("📝 Repository Status");
+            println!("No changes to commit. Your working directory is clean.\n");
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn handle_interactive_stage(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let hunks = git::get_hunks(repo)?;
+    if hunks.is_empty() {
+        ui::print_section("🧩 Repository Status");
+        println!("No unstaged changes to review.\n");
+        return Ok(());
+    }
+
+    ui::print_section("🧩 Review Hunks");
+    // In dry-run mode the selection is only collected, never applied to the index
+    let mut selected = Vec::new();
+    // Quitting halfway through puts the index back instead of leaving some hunks staged
+    let index_guard = shutdown::protect_index(repo)?;
+    for (idx, hunk) in hunks.iter().enumerate() {
+        ui::print_subsection(&format!("📁 {} ({}/{}) {}", hunk.path, idx + 1, hunks.len(), hunk.header));
+        let options = if hunk.is_binary {
+            println!("(binary file)\n");
+            ["✅ Stage file", "⏭️ Skip file", "🛑 Stop reviewing"]
+        } else {
+            println!("{}", hunk.content);
+            ["✅ Stage hunk", "⏭️ Skip hunk", "🛑 Stop reviewing"]
+        };
+
+        match ui::show_selection_menu("Stage this change?", &options, 0)? {
+            0 => {
+                if !config.is_dry_run() {
+                    index_guard.apply(|| git::stage_hunk(repo, hunk))?;
+                }
+                selected.push(hunk);
+            }
+            1 => continue,
+            _ => break,
+        }
+    }
+    index_guard.disarm();
+
+    if selected.is_empty() {
+        println!("No hunks staged.\n");
+        return Ok(());
+    }
+
+    let diff = if config.is_dry_run() {
+        selected.iter()
+            .map(|h| format!("diff --git a/{0} b/{0}\n{1}\n{2}", h.path, h.header, h.content))
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        git::get_staged_diff(repo, config.diff_context_lines())?
+    };
+    let diff = git::truncate_diff(&diff, config.max_diff_bytes());
+    review_and_commit(config, Some(repo), diff, git::DiffScope::Staged, false).await
+}
+
+/// Suggests commits for all changes, lets the user merge or skip them, then stages and
+/// commits each group in turn. Skipped groups stay in the working tree, unstaged.
+async fn handle_split_commits(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let full_diff = match git::get_diff_scoped(repo, git::DiffScope::All, config.diff_context_lines()) {
+        Ok(diff) => diff,
+        Err(NoahError::NoChanges) => {
+            ui::print_section("🪓 Repository Status");
+            println!("No changes to commit. Your working directory is clean.\n");
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let Some(diff) = prepare_diff(config, &git::truncate_diff(&full_diff, config.max_diff_bytes()))? else {
+        return Ok(());
+    };
+
+    let spinner = ui::create_spinner("Grouping the changes into commits")?;
+    let suggested = config.suggest_commit_groups(&diff).await;
+    spinner.finish_and_clear();
+    let mut groups = suggested?;
+    let mut skipped = vec![false; groups.len()];
+    let ungrouped = ungrouped_files(&full_diff, &groups);
+
+    loop {
+        print_commit_groups(&groups, &skipped, &ungrouped);
+
+        let count = skipped.iter().filter(|skip| !**skip).count();
+        let commit_label = if config.is_dry_run() {
+            format!("✅ Preview {} commit(s) (dry-run)", count)
+        } else {
+            format!("✅ Commit {} group(s)", count)
+        };
+        let options = [commit_label.as_str(), "🔗 Merge two groups", "⏭️ Choose groups to skip", "❌ Cancel"];
+        match ui::show_selection_menu("What would you like to do?", &options, 0)? {
+            0 if count == 0 => println!("Every group is skipped, so there is nothing to commit.\n"),
+            0 => break,
+            1 if groups.len() < 2 => println!("There is only one group.\n"),
+            1 => merge_commit_groups(&mut groups, &mut skipped)?,
+            2 => {
+                let labels: Vec<&str> = groups.iter().map(|group| group.message.summary.as_str()).collect();
+                let chosen = ui::show_multi_select_menu("Skip which groups? (space to toggle)", &labels, &skipped)?;
+                skipped = (0..groups.len()).map(|idx| chosen.contains(&idx)).collect();
+            }
+            _ => return Ok(()),
+        }
+    }
+
+    let file_diffs = git::split_diff(&diff);
+    for (group, _) in groups.iter().zip(&skipped).filter(|(_, skip)| !**skip) {
+        let group_diff: Vec<&(String, String)> = file_diffs.iter().filter(|(path, _)| group.files.contains(path)).collect();
+        // A rename is only complete with its old path staged as removed
+        let mut paths = group.files.clone();
+        for (_, file_diff) in &group_diff {
+            if let git::ChangeKind::Renamed { from, .. } = git::ChangeKind::from_diff(file_diff) {
+                paths.push(from);
+            }
+        }
+        let group_diff: String = group_diff.iter().map(|(_, file_diff)| file_diff.as_str()).collect();
+
+        ui::print_subsection(&format!("📝 {}", group.message.summary));
+        // A failed or interrupted commit leaves the index as it was before this group
+        let index_guard = shutdown::protect_index(repo)?;
+        if !config.is_dry_run() {
+            index_guard.apply(|| git::stage_paths(repo, &paths))?;
+        }
+        commit(config, repo, &group_diff, &group.message, false, None)?;
+        index_guard.disarm();
+    }
+    if skipped.contains(&true) {
+        println!("The skipped groups are left uncommitted in the working tree.\n");
+    }
+    if !ungrouped.is_empty() {
+        println!("{} file(s) in no group are left uncommitted in the working tree.\n", ungrouped.len());
+    }
+    Ok(())
+}
+
+/// The changed files in `diff` that none of `groups` covers, e.g. because `truncate_diff`
+/// left them out of what the model saw
+fn ungrouped_files(diff: &str, groups: &[CommitGroup]) -> Vec<String> {
+    git::split_diff(diff)
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| !groups.iter().any(|group| group.files.contains(path)))
+        .collect()
+}
+
+/// The suggested commits of `handle_split_commits` with their files, and the files in none
+fn print_commit_groups(groups: &[CommitGroup], skipped: &[bool], ungrouped: &[String]) {
+    ui::print_section("🪓 Suggested Commits");
+    for (idx, group) in groups.iter().enumerate() {
+        let note = if skipped[idx] { " (skipped)" } else { "" };
+        println!("{}. {}{}", idx + 1, group.message.summary, note);
+        for file in &group.files {
+            println!("     {}", file);
+        }
+    }
+    if !ungrouped.is_empty() {
+        println!("Not in any group, so left uncommitted:");
+        for file in ungrouped {
+            println!("     {}", file);
+        }
+    }
+    println!();
+}
+
+/// Lets the user pick two groups and merges the second into the first, with the message
+/// of their choice
+fn merge_commit_groups(groups: &mut Vec<CommitGroup>, skipped: &mut Vec<bool>) -> Result<(), Box<dyn Error>> {
+    let labels: Vec<String> = groups.iter().enumerate().map(|(idx, group)| format!("{}. {}", idx + 1, group.message.summary)).collect();
+    let first = ui::show_selection_menu("Merge which group?", &labels, 0)?;
+    let others: Vec<usize> = (0..groups.len()).filter(|idx| *idx != first).collect();
+    let other_labels: Vec<&str> = others.iter().map(|idx| labels[*idx].as_str()).collect();
+    let second = others[ui::show_selection_menu("With which group?", &other_labels, 0)?];
+
+    let messages = [groups[first].message.summary.as_str(), groups[second].message.summary.as_str()];
+    let keep_second = ui::show_selection_menu("Keep which message?", &messages, 0)? == 1;
+    let merged = groups.remove(second);
+    let merged_skipped = skipped.remove(second);
+    let target = if second < first { first - 1 } else { first };
+    groups[target].files.extend(merged.files);
+    if keep_second {
+        groups[target].message = merged.message;
+    }
+    skipped[target] = skipped[target] && merged_skipped;
+    Ok(())
+}
+
+/// Actions offered once a commit message has been generated
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CommitAction {
+    Regenerate,
+    RegenerateWithFeedback,
+    RegenerateSubject,
+    ToggleBody,
+    AutoFix,
+    EditType,
+    EditInEditor,
+    CoAuthors,
+    Author,
+    ChangeScope,
+    PreviewDiff,
+    CopyToClipboard,
+    Commit,
+    Amend,
+    Cancel,
+}
+
+/// Generates a commit message for `diff`, which covers `diff_scope`, and lets the user refine
+/// and commit it. Staged diffs commit only the index; the other scopes stage everything first.
+/// With `switchable` set the user may regenerate the message from a different scope.
+/// Without a repository the message can only be refined, not committed.
+async fn review_and_commit(config: &Config, repo: Option<&Repository>, diff: String, mut diff_scope: git::DiffScope, switchable: bool) -> Result<(), Box<dyn Error>> {
+    let Some(mut diff) = prepare_diff(config, &diff)? else {
+        return Ok(());
+    };
+
+    let mut detailed = false;
+    let mut commit_message = CommitMessage::default();
+    let mut warnings: Vec<LintWarning> = Vec::new();
+    // Kept across regenerations, which would otherwise drop the trailers
+    let mut co_authors: Vec<String> = Vec::new();
+    // Replaces the configured author and the current date, e.g. for imported work
+    let mut author: Option<git2::Signature<'static>> = None;
+    // Only applies to the next generation
+    let mut hint: Option<String> = None;
+    let mut regenerate = true;
+    // Set when the user asked for a new message, which mustn't come from the response cache
+    let mut fresh = false;
+    loop {
+        let stage_all = diff_scope != git::DiffScope::Staged;
+        let commit_label = if config.is_dry_run() {
+            "✅ Preview commit (dry-run)"
+        } else if stage_all {
+            "✅ Stage and commit"
+        } else {
+            "✅ Commit staged changes"
+        };
+        let scope_label = format!("🔀 Change diff scope ({})", diff_scope.name());
+        let co_authors_label = match co_authors.len() {
+            0 => "👥 Add co-authors".to_string(),
+            count => format!("👥 Co-authors ({})", count),
+        };
+        let author_label = match &author {
+            Some(author) => format!("🪪 Author ({})", git::describe_author(author)),
+            None => "🪪 Set author and date".to_string(),
+        };
+        let mut actions = vec![
+            ("✨ Regenerate message", CommitAction::Regenerate),
+            ("💬 Regenerate with feedback", CommitAction::RegenerateWithFeedback),
+            ("✍️ Regenerate subject only", CommitAction::RegenerateSubject),
+            ("👀 Preview diff", CommitAction::PreviewDiff),
+            ("📄 Toggle detailed body", CommitAction::ToggleBody),
+            ("📝 Edit commit type", CommitAction::EditType),
+            ("✏️ Edit in $EDITOR", CommitAction::EditInEditor),
+            (co_authors_label.as_str(), CommitAction::CoAuthors),
+            (author_label.as_str(), CommitAction::Author),
+            ("📋 Copy to clipboard", CommitAction::CopyToClipboard),
+            (commit_label, CommitAction::Commit),
+            ("🔁 Amend last commit", CommitAction::Amend),
+            ("❌ Cancel", CommitAction::Cancel),
+        ];
+        if repo.is_none() {
+            actions.retain(|(_, action)| !matches!(action, CommitAction::Author | CommitAction::Commit | CommitAction::Amend | CommitAction::Cancel));
+            actions.push(("✅ Done", CommitAction::Cancel));
+        } else if switchable {
+            actions.insert(3, (scope_label.as_str(), CommitAction::ChangeScope));
+        }
+
+        // A new message is printed whenever this is set, and the diff stat goes with it
+        let show_stat = regenerate;
+        if regenerate && commit_message.summary.is_empty() && repo.is_some() {
+            if let Some(previous) = reuse_from_history(config, &diff, &co_authors)? {
+                warnings = config.lint_commit_message(&previous.to_string());
+                print_lint_warnings(&warnings);
+                commit_message = previous;
+                regenerate = false;
+            } else if let Some(trivial) = config.trivial_commit_message(&diff) {
+                println!("ℹ️ {} \"Regenerate message\" asks it anyway.", trivial_diff_note(config));
+                commit_message = trivial.with_co_authors(&co_authors);
+                print_commit_message("📝 Commit Message from the File Names", &commit_message);
+                warnings = config.lint_commit_message(&commit_message.to_string());
+                print_lint_warnings(&warnings);
+                regenerate = false;
+            }
+        }
+        if regenerate {
+            // Several candidates are never cached, so they are always fresh
+            let generated = if config.candidates() > 1 {
+                pick_candidate(config, &diff, detailed, hint.take().as_deref(), &co_authors).await
+            } else {
+                generate_with_spinner(config, &diff, detailed, hint.take().as_deref(), fresh, &co_authors).await
+            };
+            fresh = false;
+            match generated {
+                Ok(generated) => (commit_message, warnings) = generated,
+                // A failed retry, e.g. a timeout, keeps the previous message instead of ending the review
+                Err(e) if !commit_message.summary.is_empty() => {
+                    ui::clear_spinner();
+                    eprintln!("\n❌ {}\n", e);
+                    print_commit_message("📝 Generated Commit Message", &commit_message);
+                    print_lint_warnings(&warnings);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        regenerate = true;
+        // Only offered when there is something to fix, and never required
+        if warnings.iter().any(LintWarning::is_subject) {
+            actions.insert(1, ("🔧 Auto-fix subject", CommitAction::AutoFix));
+        }
+        // Left out in dry-run mode, where hunks picked for staging never reach the index
+        if let Some(repo) = repo.filter(|_| show_stat && !config.is_dry_run()) {
+            // Helps to notice that more was staged than the message describes
+            println!("📊 {}\n", git::diff_stat(repo, diff_scope)?);
+        }
+        let labels: Vec<&str> = actions.iter().map(|(label, _)| *label).collect();
+        let default = actions.iter().position(|(_, a)| *a == CommitAction::Commit).unwrap_or(0);
+        
+        match actions[ui::show_selection_menu("What would you like to do?", &labels, default)?].1 {
+            CommitAction::Regenerate => {
+                fresh = true;
+                continue;
+            }
+            CommitAction::RegenerateWithFeedback => {
+                let feedback = ui::get_text_input("What should be different? (e.g. make it shorter)")?;
+                hint = Some(feedback).filter(|feedback| !feedback.trim().is_empty());
+                fresh = true;
+                continue;
+            }
+            CommitAction::RegenerateSubject => {
+                let spinner = ui::create_spinner("Writing a new subject line")?;
+                let generated = config.generate_subject_only(&diff, &commit_message).await;
+                spinner.finish_and_clear();
+                match generated {
+                    Ok(generated) => commit_message = generated,
+                    Err(e) => eprintln!("\n❌ Couldn't regenerate the subject: {}\n", e),
+                }
+                warnings = config.lint_commit_message(&commit_message.to_string());
+                print_commit_message("📝 Generated Commit Message", &commit_message);
+                print_lint_warnings(&warnings);
+                regenerate = false;
+                continue;
+            }
+            CommitAction::ToggleBody => {
+                detailed = !detailed;
+                continue;
+            }
+            CommitAction::PreviewDiff => {
+                ui::print_section("👀 Diff");
+                ui::print_diff(&diff);
+                print_commit_message("📝 Generated Commit Message", &commit_message);
+                print_lint_warnings(&warnings);
+                regenerate = false;
+                continue;
+            }
+            CommitAction::AutoFix => {
+                let spinner = ui::create_spinner("Fixing the subject line")?;
+                let fixed = config.fix_commit_subject(&commit_message, &warnings).await;
+                spinner.finish_and_clear();
+                match fixed {
+                    Ok(fixed) => commit_message = fixed,
+                    Err(e) => eprintln!("\n❌ Auto-fix failed: {}\n", e),
+                }
+                warnings = config.lint_commit_message(&commit_message.to_string());
+                print_commit_message("📝 Generated Commit Message", &commit_message);
+                print_lint_warnings(&warnings);
+                regenerate = false;
+                continue;
+            }
+            CommitAction::CopyToClipboard => {
+                let message = commit_message.to_string();
+                match ui::copy_to_clipboard(&message) {
+                    Ok(()) => println!("📋 Copied the commit message to the clipboard\n"),
+                    Err(e) => println!("ℹ️ {}, so here is the message to copy:\n\n{}\n", e, message),
+                }
+                regenerate = false;
+                continue;
+            }
+            CommitAction::CoAuthors => {
+                co_authors = select_co_authors(config, &co_authors)?;
+                commit_message = commit_message.with_co_authors(&co_authors);
+                print_commit_message("📝 Generated Commit Message", &commit_message);
+                regenerate = false;
+                continue;
+            }
+            CommitAction::Author => {
+                if let Some(repo) = repo {
+                    author = select_author(repo, author.as_ref())?;
+                }
+                regenerate = false;
+                continue;
+            }
+            CommitAction::ChangeScope => {
+                if let Some((new_scope, new_diff)) = repo.map(|repo| select_diff_scope(config, repo, diff_scope)).transpose()?.flatten() {
+                    diff_scope = new_scope;
+                    diff = new_diff;
+                }
+                continue;
+            }
+            CommitAction::EditType => {
+                let types = config.commit_types();
+                let labels: Vec<String> = types.iter().map(|t| t.menu_label(config.message_style())).collect();
+                let last_idx = config.last_commit_type().and_then(|last| types.iter().position(|t| t.prefix == last)).unwrap_or(0);
+                let type_idx = ui::show_selection_menu("Select commit type", &labels, last_idx)?;
+                let selected_type = &types[type_idx].prefix;
+                config.remember_commit_type(selected_type);
+                let scope = select_scope(&diff)?;
+                let description = commit_description(config.without_template(&commit_message.summary));
+                let new_message = CommitMessage {
+                    summary: match scope {
+                        Some(scope) => format!("{}({}): {}", selected_type, scope, description),
+                        None => format!("{}: {}", selected_type, description),
+                    },
+                    ..commit_message.clone()
+                };
+                let new_message = config.format_commit_message(new_message);
+                
+                match confirm_edited_message(config, repo, &diff, &new_message, stage_all, author.as_ref())? {
+                    EditedMessage::Finished => break,
+                    EditedMessage::StartOver => {}
+                    EditedMessage::KeepRefining => {
+                        warnings = config.lint_commit_message(&new_message.to_string());
+                        commit_message = new_message;
+                        regenerate = false;
+                    }
+                }
+            }
+            CommitAction::EditInEditor => {
+                let Some(edited) = ui::edit_in_editor(&commit_message.to_string())? else {
+                    println!("Empty commit message, nothing was committed.");
+                    break;
+                };
+                let edited = CommitMessage::parse(&edited);
+                match confirm_edited_message(config, repo, &diff, &edited, stage_all, author.as_ref())? {
+                    EditedMessage::Finished => break,
+                    EditedMessage::StartOver => {}
+                    EditedMessage::KeepRefining => {
+                        warnings = config.lint_commit_message(&edited.to_string());
+                        commit_message = edited;
+                        regenerate = false;
+                    }
+                }
+            }
+            CommitAction::Commit => {
+                if let Some(repo) = repo {
+                    commit(config, repo, &diff, &commit_message, stage_all, author.as_ref())?;
+                }
+                break;
+            }
+            CommitAction::Amend => {
+                if let Some(repo) = repo {
+                    amend(config, repo, &commit_message, author.as_ref())?;
+                }
+                break;
+            }
+            CommitAction::Cancel => break,
+        }
+    }
+    Ok(())
+}
+
+/// Offers a message committed before for a similar diff, before any request is made.
+/// Returns it if the user takes it.
+fn reuse_from_history(config: &Config, diff: &str, co_authors: &[String]) -> Result<Option<CommitMessage>, Box<dyn Error>> {
+    let Some(previous) = config.suggest_from_history(diff) else {
+        return Ok(None);
+    };
+    let message = CommitMessage::parse(&previous).with_co_authors(co_authors);
+    print_commit_message("🕘 Message Used for a Similar Change", &message);
+    let options = ["♻️ Use this message", "✨ Generate a new one"];
+    match ui::show_selection_menu("A similar change was committed before. Reuse its message?", &options, 0)? {
+        0 => Ok(Some(message)),
+        _ => Ok(None),
+    }
+}
+
+/// Says why no request was made for a diff below `min_diff_lines`
+fn trivial_diff_note(config: &Config) -> String {
+    format!("Fewer than {} lines changed, so the message was made from the file names without asking the AI.", config.min_diff_lines())
+}
+
+/// `(path, diff)` pairs as sent for file analysis
+type FileDiffs = Vec<(String, String)>;
+
+/// Runs the secret scan and token estimate for a diff about to be sent.
+/// Returns the diff to send, or `None` if the user backed out.
+fn prepare_diff(config: &Config, diff: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let Some(diff) = guard_diff(config, diff, true)? else {
+        return Ok(None);
+    };
+    if !confirm_estimate(config, Config::estimate_tokens(&diff))? {
+        return Ok(None);
+    }
+    Ok(Some(diff))
+}
+
+/// Lets the user pick another diff scope and returns it with its prepared diff.
+/// `None` keeps the current scope.
+fn select_diff_scope(config: &Config, repo: &Repository, current: git::DiffScope) -> Result<Option<(git::DiffScope, String)>, Box<dyn Error>> {
+    let scopes = git::DiffScope::all();
+    let descriptions: Vec<&str> = scopes.iter().map(|s| s.description()).collect();
+    let current_idx = scopes.iter().position(|s| *s == current).unwrap_or(0);
+    let scope = scopes[ui::show_selection_menu("Generate the message from", &descriptions, current_idx)?];
+    if scope == current {
+        return Ok(None);
+    }
+
+    let diff = match git::get_diff_scoped(repo, scope, config.diff_context_lines()) {
+        Ok(diff) => git::truncate_diff(&diff, config.max_diff_bytes()),
+        Err(NoahError::NoChanges) => {
+            println!("No {} changes, keeping the current scope.\n", scope.name());
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    Ok(prepare_diff(config, &diff)?.map(|diff| (scope, diff)))
+}
+
+/// The diff a commit message starts from: the staged changes, or all changes if nothing
+/// is staged yet (reported through `notice`)
+fn initial_commit_diff(config: &Config, repo: &Repository, notice: impl Fn(&str)) -> Result<(git::DiffScope, String), NoahError> {
+    let (scope, diff) = match git::get_diff_scoped(repo, git::DiffScope::Staged, config.diff_context_lines()) {
+        Ok(diff) => (git::DiffScope::Staged, diff),
+        Err(NoahError::NoChanges) => {
+            let diff = git::get_diff_scoped(repo, git::DiffScope::All, config.diff_context_lines())?;
+            notice("Nothing is staged, so the message covers all changes and they are staged on commit.");
+            (git::DiffScope::All, diff)
+        }
+        Err(e) => return Err(e),
+    };
+    Ok((scope, git::truncate_diff(&diff, config.max_diff_bytes())))
+}
+
+/// `guard_secrets` for a single combined diff
+fn guard_diff(config: &Config, diff: &str, interactive: bool) -> Result<Option<String>, Box<dyn Error>> {
+    let guarded = guard_secrets(config, vec![("diff".to_string(), diff.to_string())], interactive)?;
+    Ok(guarded.and_then(|files| files.into_iter().next()).map(|(_, diff)| diff))
+}
+
+/// Checks `(name, text)` pairs for likely secrets before they leave the machine.
+/// Interactively the user can redact them, send anyway or cancel (`None`); headless runs
+/// always redact.
+fn guard_secrets(config: &Config, files: FileDiffs, interactive: bool) -> Result<Option<FileDiffs>, Box<dyn Error>> {
+    if !config.scans_secrets() {
+        return Ok(Some(files));
+    }
+    let hits: Vec<Vec<git::SecretHit>> = files.iter().map(|(_, text)| git::scan_for_secrets(text)).collect();
+    let count: usize = hits.iter().map(Vec::len).sum();
+    if count == 0 {
+        return Ok(Some(files));
+    }
+
+    if interactive {
+        ui::print_section("⚠️ Possible Secrets Found");
+        for ((name, text), hits) in files.iter().zip(&hits) {
+            let lines: Vec<&str> = text.split('\n').collect();
+            for hit in hits {
+                let secret: String = lines[hit.line - 1][hit.start..hit.end].chars().take(4).collect();
+                println!("- {} line {}: {} ({}…)", name, hit.line, hit.kind, secret);
+            }
+        }
+        println!();
+
+        let options = ["🙈 Redact and send", "⚠️ Send anyway", "❌ Cancel"];
+        match ui::show_selection_menu("This would be sent to the AI provider. What would you like to do?", &options, 0)? {
+            0 => (),
+            1 => return Ok(Some(files)),
+            _ => return Ok(None),
+        }
+    } else {
+        eprintln!("Redacted {} possible secret(s) before sending.", count);
+    }
+
+    Ok(Some(files
+        .into_iter()
+        .zip(&hits)
+        .map(|((name, text), hits)| {
+            let redacted = git::redact_secrets(&text, hits);
+            (name, redacted)
+        })
+        .collect()))
+}
+
+/// What the user chose to do with a hand-edited message
+enum EditedMessage {
+    /// Committed or cancelled, the review is over
+    Finished,
+    /// Generate a fresh message
+    StartOver,
+    /// Go back to the menu with the edited message, e.g. to regenerate only its subject
+    KeepRefining,
+}
+
+/// Shows a hand-edited message and commits it if confirmed.
+/// Without a repository the message is only shown.
+fn confirm_edited_message(config: &Config, repo: Option<&Repository>, diff: &str, message: &CommitMessage, stage_all: bool, author: Option<&git2::Signature>) -> Result<EditedMessage, Box<dyn Error>> {
+    print_commit_message("📝 New Commit Message", message);
+    print_lint_warnings(&config.lint_commit_message(&message.to_string()));
+    let Some(repo) = repo else {
+        return Ok(EditedMessage::Finished);
+    };
+
+    let confirm_options = [
+        "✅ Confirm and commit", 
+        "📝 Keep refining",
+        "🔄 Start over", 
+        "❌ Cancel"
+    ];
+    match ui::show_selection_menu("Would you like to proceed with this commit message?", &confirm_options, 0)? {
+        0 => {
+            commit(config, repo, diff, message, stage_all, author)?;
+            Ok(EditedMessage::Finished)
+        }
+        1 => Ok(EditedMessage::KeepRefining),
+        2 => Ok(EditedMessage::StartOver),
+        _ => Ok(EditedMessage::Finished),
+    }
+}
+
+/// Replaces the previous commit's message, and its author if `author` is set; only already
+/// staged changes are folded in
+fn amend(config: &Config, repo: &Repository, message: &CommitMessage, author: Option<&git2::Signature>) -> Result<(), Box<dyn Error>> {
+    if config.is_dry_run() {
+        ui::print_section("🧪 Dry Run");
+        println!("The last commit was not amended. This is the message that would be used:\n");
+        println!("{}\n", message);
+        print_dry_run_author(author);
+        return Ok(());
+    }
+
+    git::amend_commit(repo, &message.to_string(), author)?;
+    // Undoing an amended commit would also drop the commit it replaced
+    config.set_session_commit(None);
+    println!("Last commit amended successfully!");
+    Ok(())
+}
+
+/// `diff` is what the message was written for; it's remembered for `Config::suggest_from_history`.
+/// `author` replaces the configured author and the date, see `select_author`.
+fn commit(config: &Config, repo: &Repository, diff: &str, message: &CommitMessage, stage_all: bool, author: Option<&git2::Signature>) -> Result<(), Box<dyn Error>> {
+    if config.is_dry_run() {
+        ui::print_section("🧪 Dry Run");
+        println!("Nothing was staged or committed. This is the message that would be used:\n");
+        println!("{}\n", message);
+        print_dry_run_author(author);
+        return Ok(());
+    }
+
+    if stage_all {
+        git::stage_and_commit(repo, &message.to_string(), author)?;
+    } else {
+        git::commit_staged(repo, &message.to_string(), author)?;
+    }
+    config.set_session_commit(repo.head().ok().and_then(|head| head.target()));
+    // Losing the history only means the next similar commit asks the model again
+    let _ = config.remember_message(repo, diff, message);
+    println!("Changes committed successfully!");
+    Ok(())
+}
+
+fn print_dry_run_author(author: Option<&git2::Signature>) {
+    if let Some(author) = author {
+        println!("Author: {}\n", git::describe_author(author));
+    }
+}
+
+/// Prompts for the author and date of the commit, starting from `current` or the user's
+/// git config. Returns `None` when the user keeps the configured author and leaves the
+/// date empty, so the commit is made as usual. The committer is never changed.
+fn select_author(repo: &Repository, current: Option<&git2::Signature>) -> Result<Option<git2::Signature<'static>>, Box<dyn Error>> {
+    let configured = repo.signature().ok();
+    let (default_name, default_email) = match current.or(configured.as_ref()) {
+        Some(author) => (author.name().unwrap_or_default().to_string(), author.email().unwrap_or_default().to_string()),
+        None => (String::new(), String::new()),
+    };
+    let name = ui::get_text_with_default("Author name", &default_name)?;
+    let email = ui::get_text_with_default("Author email", &default_email)?;
+    let date = loop {
+        let date = ui::get_text_input("Author date (YYYY-MM-DD [HH:MM], empty for now)")?;
+        match git::parse_commit_date(&date) {
+            Err(e) if !date.trim().is_empty() => println!("❌ {}", e),
+            _ => break date,
+        }
+    };
+    let is_configured = configured.as_ref().is_some_and(|author| author.name() == Some(name.trim()) && author.email() == Some(email.trim()));
+    if is_configured && date.trim().is_empty() {
+        println!("🪪 Committing as configured in git\n");
+        return Ok(None);
+    }
+    match git::commit_author(&name, &email, &date) {
+        Ok(author) => {
+            println!("🪪 Committing as {}\n", git::describe_author(&author));
+            Ok(Some(author))
+        }
+        // e.g. an empty email; the author stays what it was
+        Err(e) => {
+            println!("❌ {}\n", e);
+            Ok(current.map(git2::Signature::to_owned))
+        }
+    }
+}
+
+/// Undoes the commit created in this session, as long as it's still the last commit
+pub(crate) fn undo_session_commit(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let commit = config.session_commit().ok_or("No commit was created in this session")?;
+    let head = repo.head().ok().and_then(|head| head.target());
+    if head != Some(commit) {
+        config.set_session_commit(None);
+        return Err("The last commit wasn't created in this session, so it won't be undone".into());
+    }
+    if !ui::confirm("Undo the last commit? Its changes stay staged")? {
+        return Ok(());
+    }
+
+    git::undo_last_commit(repo)?;
+    config.set_session_commit(None);
+    println!("Commit undone; its changes are staged again.");
+    Ok(())
+}
+
+/// Lets the user pick co-authors from the configured ones and add others by hand.
+/// `current` is preselected so the choice can be changed later.
+fn select_co_authors(config: &Config, current: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut known: Vec<String> = config.co_authors().to_vec();
+    for co_author in current {
+        if !known.contains(co_author) {
+            known.push(co_author.clone());
+        }
+    }
+
+    let mut selected: Vec<String> = if known.is_empty() {
+        Vec::new()
+    } else {
+        let defaults: Vec<bool> = known.iter().map(|c| current.contains(c)).collect();
+        ui::show_multi_select_menu("Select co-authors (space to toggle)", &known, &defaults)?
+            .into_iter()
+            .map(|idx| known[idx].clone())
+            .collect()
+    };
+
+    while ui::confirm("Add someone else?")? {
+        let co_author = ui::get_text_input("Co-author (Name <email>)")?;
+        let co_author = co_author.trim();
+        if !(co_author.contains('<') && co_author.ends_with('>')) {
+            println!("Co-authors need the form \"Name <email>\", so that one was skipped.");
+        } else if !selected.iter().any(|c| c == co_author) {
+            selected.push(co_author.to_string());
+        }
+    }
+    Ok(selected)
+}
+
+/// Prompts for an optional Conventional Commits scope, suggesting the changed directories
+fn select_scope(diff: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let mut options = vec!["🚫 No scope".to_string()];
+    options.extend(git::changed_directories(diff));
+    options.push("✏️ Custom scope".to_string());
+
+    let selection = ui::show_selection_menu("Select commit scope", &options, 0)?;
+    let scope = if selection == 0 {
+        String::new()
+    } else if selection == options.len() - 1 {
+        ui::get_text_input("Enter commit scope")?
+    } else {
+        options[selection].clone()
+    };
+
+    let scope = scope.trim();
+    Ok(if scope.is_empty() { None } else { Some(scope.to_string()) })
+}
+
+/// Strips the `type(scope):` prefix and any emoji before it from a conventional commit message
+fn commit_description(message: &str) -> &str {
+    let message = crate::strip_leading_emoji(message);
+    match message.split_once(':') {
+        Some((prefix, rest)) if !prefix.trim().is_empty() && !prefix.contains(char::is_whitespace) => rest.trim(),
+        _ => message.trim(),
+    }
+}
+
+async fn handle_file_analysis(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    if config.against_base() {
+        return match branch_diffs(config, repo, |notice| println!("🌿 {}\n", notice)) {
+            Ok((_, file_diffs)) => analyze_selected_files(config, Some(repo), file_diffs).await,
+            Err(NoahError::NoChanges) => {
+                ui::print_section("📊 Repository Status");
+                println!("No commits on this branch since {}.\n", config.base_branch(repo)?);
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        };
+    }
+    if let Some(path) = config.only_file() {
+        let path = git::repo_relative_path(repo, path);
+        let file_diffs = single_file_diffs(config, repo, &path)?;
+        if file_diffs.is_empty() {
+            ui::print_section("📊 Repository Status");
+            println!("No changes in {}.\n", path);
+            return Ok(());
+        }
+        return analyze_files(config, Some(repo), file_diffs).await;
+    }
+    match config.file_diffs(repo, None) {
+        Ok(file_diffs) => analyze_selected_files(config, Some(repo), file_diffs).await,
+        Err(NoahError::NoChanges) => {
+            ui::print_section("📊 Repository Status");
+            println!("No changes to analyze. Your working directory is clean.\n");
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn handle_review(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    match git::get_diff_scoped(repo, git::DiffScope::All, config.diff_context_lines()) {
+        Ok(diff) => review_diff(config, &git::truncate_diff(&diff, config.max_diff_bytes())).await,
+        Err(NoahError::NoChanges) => {
+            ui::print_section("📊 Repository Status");
+            println!("No changes to review. Your working directory is clean.\n");
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn handle_explain_failure(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let diff = failure_diff(config, repo, |notice| println!("ℹ️ {}\n", notice))?;
+    explain_failure(config, &diff).await
+}
+
+/// The changes a failure is explained with: the uncommitted ones, or those of the last
+/// commit when there are none, or the branch's with `--base`
+fn failure_diff(config: &Config, repo: &Repository, notify: impl Fn(&str)) -> Result<String, Box<dyn Error>> {
+    if config.against_base() {
+        let (_, file_diffs) = branch_diffs(config, repo, &notify)?;
+        return Ok(join_file_diffs(config, file_diffs));
+    }
+    match git::get_diff(repo, config.diff_context_lines()) {
+        Ok(diff) => Ok(git::truncate_diff(&diff, config.max_diff_bytes())),
+        Err(NoahError::NoChanges) if repo.head().is_ok() => {
+            notify("No uncommitted changes, so the log is explained with the changes of the last commit.");
+            let changes = git::revision_changes(repo, "HEAD", config.diff_context_lines())?;
+            Ok(join_file_diffs(config, config.revision_file_diffs(repo, &changes)?))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Asks for the error log, unless `--log` gave one, and shows what went wrong in `diff`
+async fn explain_failure(config: &Config, diff: &str) -> Result<(), Box<dyn Error>> {
+    let log = match config.failure_log() {
+        Some(path) => input::read_log(path)?,
+        None => match ui::paste_in_editor("# Paste the error log below this line, e.g. of the failed build or test run, then save and close the editor.")? {
+            Some(log) => input::log_tail(&log),
+            None => {
+                println!("No error log given, so there is nothing to explain.\n");
+                return Ok(());
+            }
+        },
+    };
+    let Some((diff, log)) = guard_failure(config, diff, &log, true)? else {
+        return Ok(());
+    };
+    if !confirm_estimate(config, Config::estimate_tokens(&diff) + Config::estimate_tokens(&log))? {
+        return Ok(());
+    }
+
+    let spinner = ui::create_spinner("Explaining the failure")?;
+    let explanation = config.explain_failure(&diff, &log).await;
+    spinner.finish_and_clear();
+
+    ui::print_section("🚨 Failure Explained");
+    ui::print_markdown_paged(&explanation?);
+    Ok(())
+}
+
+/// `diff` and `log` checked for likely secrets, see `guard_secrets`; `None` if cancelled
+fn guard_failure(config: &Config, diff: &str, log: &str, interactive: bool) -> Result<Option<(String, String)>, Box<dyn Error>> {
+    let texts = vec![("diff".to_string(), diff.to_string()), ("log".to_string(), log.to_string())];
+    let Some(mut texts) = guard_secrets(config, texts, interactive)? else {
+        return Ok(None);
+    };
+    let (_, log) = texts.pop().unwrap_or_default();
+    let (_, diff) = texts.pop().unwrap_or_default();
+    Ok(Some((diff, log)))
+}
+
+/// Reviews `diff` and shows the findings of at least `--severity`, then lets the user
+/// narrow them down or widen them by severity
+async fn review_diff(config: &Config, diff: &str) -> Result<(), Box<dyn Error>> {
+    let Some(diff) = prepare_diff(config, diff)? else {
+        return Ok(());
+    };
+    let spinner = ui::create_spinner("Reviewing changes")?;
+    let findings = config.review_changes(&diff).await;
+    spinner.finish_and_clear();
+    let findings = findings?;
+
+    let mut min_severity = config.min_severity();
+    loop {
+        let shown = Level::filter(&findings, min_severity, |finding| Some(finding.severity));
+        ui::print_section("🧐 Review");
+        match findings.len() - shown.len() {
+            0 => (),
+            hidden => println!("{} less severe finding(s) hidden.\n", hidden),
+        }
+        ui::print_markdown_paged(&export::review_sections(&shown));
+        if findings.is_empty() {
+            return Ok(());
+        }
+
+        match pick_level("Show findings of", &[], ["🔴 High only", "🟠 Medium and high", "🟢 All findings"])? {
+            LevelChoice::AtLeast(severity) => min_severity = severity,
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// A choice in a menu narrowing results down by level, see `pick_level`
+enum LevelChoice {
+    /// One of the options listed before the filters, by index
+    Listed(usize),
+    /// Show what is at least this serious
+    AtLeast(Level),
+    Back,
+}
+
+/// Offers `options`, then `filters` for high, medium and high, and all results, then
+/// "↩️ Back". Shared by the review and the file analysis results.
+fn pick_level(prompt: &str, options: &[&str], filters: [&str; 3]) -> Result<LevelChoice, Box<dyn Error>> {
+    let levels = [Level::High, Level::Medium, Level::Low];
+    let mut labels = options.to_vec();
+    labels.extend(filters);
+    labels.push("↩️ Back");
+    let idx = ui::show_selection_menu(prompt, &labels, labels.len() - 1)?;
+    Ok(match idx.checked_sub(options.len()) {
+        None => LevelChoice::Listed(idx),
+        Some(filter) => levels.get(filter).map_or(LevelChoice::Back, |level| LevelChoice::AtLeast(*level)),
+    })
+}
+
+async fn handle_branch_name(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let diff = match initial_commit_diff(config, repo, |_| println!("Nothing is staged, so the name covers all changes.\n")) {
+        Ok((_, diff)) => diff,
+        Err(NoahError::NoChanges) => {
+            ui::print_section("📊 Repository Status");
+            println!("No changes to name a branch after. Your working directory is clean.\n");
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let Some(diff) = prepare_diff(config, &diff)? else {
+        return Ok(());
+    };
+
+    let mut name = suggest_branch_name_with_spinner(config, &diff, false).await?;
+    loop {
+        ui::print_section("🌿 Branch Name");
+        println!("{}\n", name);
+        let actions = [
+            "✅ Create and check out",
+            "✨ Suggest another",
+            "✏️ Edit name",
+            "❌ Cancel",
+        ];
+        match ui::show_selection_menu("What would you like to do?", &actions, 0)? {
+            0 if config.is_dry_run() => {
+                ui::print_section("🧪 Dry Run");
+                println!("No branch was created. This is the branch that would be checked out:\n\n{}\n", name);
+                return Ok(());
+            }
+            0 => match git::create_and_checkout_branch(repo, &name) {
+                Ok(()) => {
+                    println!("✅ Switched to the new branch {}. Your changes came along.", name);
+                    return Ok(());
+                }
+                Err(e) => println!("❌ {}\n", e),
+            },
+            1 => name = suggest_branch_name_with_spinner(config, &diff, true).await?,
+            2 => {
+                let edited = ui::get_text_with_default("Branch name", &name)?;
+                let edited = edited.trim();
+                if git::is_valid_branch_name(edited) {
+                    name = edited.to_string();
+                } else {
+                    println!("⚠️ {} isn't a valid branch name; keeping {}.", edited, name);
+                }
+            }
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// `another` asks the model again instead of using a cached suggestion
+async fn suggest_branch_name_with_spinner(config: &Config, diff: &str, another: bool) -> Result<String, Box<dyn Error>> {
+    let spinner = ui::create_spinner("Suggesting a branch name")?;
+    let name = if another {
+        config.suggest_another_branch_name(diff).await
+    } else {
+        config.suggest_branch_name(diff).await
+    };
+    spinner.finish_and_clear();
+    Ok(name?)
+}
+
+async fn handle_explain_commit(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let changes = prompt_revision(config, repo)?;
+
+    ui::print_section(&format!("🕰️ Explaining {}", changes.revision));
+    for commit in changes.commits.iter().take(MAX_LISTED_COMMITS) {
+        println!("• {}", commit);
+    }
+    if changes.commits.len() > MAX_LISTED_COMMITS {
+        println!("• … and {} more", changes.commits.len() - MAX_LISTED_COMMITS);
+    }
+    println!();
+
+    // No blame: it describes the working tree, not who wrote these commits
+    analyze_selected_files(config, None, config.revision_file_diffs(repo, &changes)?).await
+}
+
+/// Commits listed before explaining a range
+const MAX_LISTED_COMMITS: usize = 10;
+
+/// Asks for the commit or range to explain, re-prompting until it resolves.
+/// `--rev` is offered as the default.
+fn prompt_revision(config: &Config, repo: &Repository) -> Result<git::RevisionChanges, Box<dyn Error>> {
+    let default = config.revision().unwrap_or("HEAD");
+    loop {
+        let revision = ui::get_text_with_default("Commit or range to explain (e.g. HEAD~2 or main..feature)", default)?;
+        match git::revision_changes(repo, &revision, config.diff_context_lines()) {
+            Ok(changes) => return Ok(changes),
+            Err(e) => println!("{}. Please try again.", e),
+        }
+    }
+}
+
+async fn handle_reword_commit(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let revision = prompt_commit(config, repo)?;
+    let plan = git::plan_reword(repo, &revision)?;
+    let original = repo.find_commit(plan.commit)?;
+    let short_id = original.as_object().short_id()?.as_str().unwrap_or_default().to_string();
+
+    ui::print_section(&format!("✏️ Rewording {}", short_id));
+    println!("{}\n", original.message().unwrap_or("No message").trim_end());
+    println!(
+        "⚠️ This rewrites history: {} and the {} commit(s) after it are recreated with new ids. Clones or stashes that still have the old commits will have to be rebased.\n",
+        short_id,
+        plan.descendants.len()
+    );
+
+    let diff = match git::commit_patch(repo, plan.commit, config.diff_context_lines()) {
+        Ok(diff) => git::truncate_diff(&diff, config.max_diff_bytes()),
+        Err(NoahError::NoChanges) => return Err(format!("{} changes no files, so there is nothing to describe", short_id).into()),
+        Err(e) => return Err(e.into()),
+    };
+    let Some(diff) = prepare_diff(config, &diff)? else {
+        return Ok(());
+    };
+
+    let mut detailed = false;
+    let (mut message, _) = generate_with_spinner(config, &diff, detailed, None, false, &[]).await?;
+    loop {
+        let actions = [
+            "✅ Reword commit",
+            "✨ Regenerate message",
+            "📄 Toggle detailed body",
+            "✏️ Edit in $EDITOR",
+            "❌ Cancel",
+        ];
+        match ui::show_selection_menu("What would you like to do?", &actions, 0)? {
+            0 => break,
+            1 => (message, _) = generate_with_spinner(config, &diff, detailed, None, true, &[]).await?,
+            2 => {
+                detailed = !detailed;
+                (message, _) = generate_with_spinner(config, &diff, detailed, None, false, &[]).await?;
+            }
+            3 => {
+                let Some(edited) = ui::edit_in_editor(&message.to_string())? else {
+                    println!("Empty commit message, nothing was rewritten.");
+                    return Ok(());
+                };
+                message = CommitMessage::parse(&edited);
+                print_commit_message("📝 New Commit Message", &message);
+                print_lint_warnings(&config.lint_commit_message(&message.to_string()));
+            }
+            _ => return Ok(()),
         }
     }
 
-    pub async fn execute(&self, config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
-        match self {
-            Mode::CommitMessage => handle_commit_message(config, repo).await,
-            Mode::FileAnalysis => handle_file_analysis(config, repo).await,
-            Mode::ContributorAnalysis => handle_contributor_analysis(config, repo).await,
+    if config.is_dry_run() {
+        ui::print_section("🧪 Dry Run");
+        println!("{} was not reworded. This is the message that would be used:\n", short_id);
+        println!("{}\n", message);
+        return Ok(());
+    }
+    let question = match plan.descendants.len() {
+        0 => format!("Rewrite {}?", short_id),
+        count => format!("Rewrite {} and the {} commit(s) after it?", short_id, count),
+    };
+    if !ui::confirm(&question)? {
+        return Ok(());
+    }
+
+    let reworded = git::reword_commit(repo, &plan, &message.to_string())?;
+    // The commit made in this session, if any, was just replaced
+    config.set_session_commit(None);
+    let new_id = repo.find_commit(reworded)?.as_object().short_id()?.as_str().unwrap_or_default().to_string();
+    println!("Reworded {}, which is now {}.", short_id, new_id);
+    Ok(())
+}
+
+/// Asks for the commit to reword, re-prompting until it resolves. `--rev` is offered as
+/// the default.
+fn prompt_commit(config: &Config, repo: &Repository) -> Result<String, Box<dyn Error>> {
+    let default = config.revision().unwrap_or("HEAD");
+    loop {
+        let revision = ui::get_text_with_default("Commit to reword (e.g. HEAD~2 or a1b2c3d)", default)?;
+        match repo.revparse_single(revision.trim()).and_then(|object| object.peel_to_commit()) {
+            Ok(_) => return Ok(revision),
+            Err(e) => println!("Can't find commit {}: {}. Please try again.", revision.trim(), e.message()),
+        }
+    }
+}
+
+/// Lets the user pick files from `file_diffs` and shows their analyses, see `analyze_files`
+async fn analyze_selected_files(config: &Config, repo: Option<&Repository>, file_diffs: FileDiffs) -> Result<(), Box<dyn Error>> {
+    if file_diffs.is_empty() {
+        ui::print_section("📊 File Analysis Results");
+        println!("All changed files were excluded from analysis.\n");
+        return Ok(());
+    }
+
+    let paths: Vec<&str> = file_diffs.iter().map(|(path, _)| path.as_str()).collect();
+    let defaults = vec![true; paths.len()];
+    let selection = ui::show_multi_select_menu("Select files to analyze (space to toggle)", &paths, &defaults)?;
+    if selection.is_empty() {
+        ui::print_section("📊 File Analysis Results");
+        println!("No files selected.\n");
+        return Ok(());
+    }
+
+    let selected: FileDiffs = selection.into_iter().map(|idx| file_diffs[idx].clone()).collect();
+    analyze_files(config, repo, selected).await
+}
+
+/// The commits of the current branch since it left the base branch and the file diffs of
+/// what they changed, passing `notify` a line naming the base and how many commits there are
+fn branch_diffs(config: &Config, repo: &Repository, notify: impl Fn(&str)) -> Result<(Vec<String>, FileDiffs), NoahError> {
+    let changes = config.branch_changes(repo)?;
+    let commits = if changes.commits.len() == 1 { "commit" } else { "commits" };
+    notify(&format!("Changes of {} {} since {}", changes.commits.len(), commits, config.base_branch(repo)?));
+    let file_diffs = config.revision_file_diffs(repo, &changes)?;
+    Ok((changes.commits, file_diffs))
+}
+
+/// `file_diffs` as a single diff, cut to `max_diff_bytes`
+fn join_file_diffs(config: &Config, file_diffs: FileDiffs) -> String {
+    let diff = file_diffs.into_iter().map(|(_, diff)| diff).collect::<Vec<_>>().join("\n");
+    git::truncate_diff(&diff, config.max_diff_bytes())
+}
+
+/// The diff of just `path`, for `--file`. Empty when the file has no changes or is excluded.
+fn single_file_diffs(config: &Config, repo: &Repository, path: &str) -> Result<FileDiffs, Box<dyn Error>> {
+    match config.file_diffs(repo, Some(&[path.to_string()])) {
+        Ok(file_diffs) => Ok(file_diffs),
+        Err(NoahError::NoChanges) => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Shows the analyses of `selected`, riskiest first, and offers to export them or to show
+/// only the riskier ones. With a repository each analysis also names who last touched the
+/// changed lines.
+async fn analyze_files(config: &Config, repo: Option<&Repository>, selected: FileDiffs) -> Result<(), Box<dyn Error>> {
+    let Some(selected) = guard_secrets(config, selected, true)? else {
+        return Ok(());
+    };
+    let tokens = selected.iter().map(|(_, diff)| Config::estimate_tokens(diff)).sum();
+    if !confirm_estimate(config, tokens)? {
+        return Ok(());
+    }
+
+    let spinner = ui::create_spinner(&format!("Analyzing {} files", selected.len()))?;
+    let results = config.analyze_file_diffs(selected).await;
+    spinner.finish_and_clear();
+
+    let (analyses, failures) = split_analyses(results);
+    let mut min_risk = config.min_risk();
+    loop {
+        let shown = Level::filter(&analyses, min_risk, |analysis| analysis.risk);
+        print_file_analyses(repo, &analyses, &shown, &failures);
+        if analyses.is_empty() {
+            return Ok(());
+        }
+
+        match pick_level("What would you like to do with the results?", &["💾 Export results"], ["🔴 High risk only", "🟠 Medium and high risk", "🟢 All files"])? {
+            LevelChoice::Listed(0) => return export_analyses(&shown),
+            LevelChoice::AtLeast(risk) => min_risk = risk,
+            _ => return Ok(()),
         }
     }
 }
 
-async fn handle_commit_message(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
-    match git::get_diff(repo) {
-        Ok(diff) => {
-            loop {
-                let commit_message = generate_with_spinner(config, &diff).await?;
-                
-                let options = [
-                    "✨ Regenerate message",
-                    "📝 Edit commit type",
-                    "✅ Stage and commit",
-                    "❌ Cancel"
-                ];
-                
-                match ui::show_selection_menu("What would you like to do?", &options, 2)? {
-                    0 => continue, // Regenerate
-                    1 => {
-                        let types = [
-                            "feat: ✨ New feature",
-                            "fix: 🐛 Bug fix", 
-                            "docs: 📚 Documentation",
-                            "style: 💅 Formatting",
-                            "refactor: ♻️ Code restructure",
-                            "test: 🧪 Testing",
-                            "chore: 🔧 Maintenance",
-                        ];
-                        
-                        let type_idx = ui::show_selection_menu("Select commit type", &types, 0)?;
-                        let selected_type = types[type_idx].split(':').next().unwrap();
-                        let description = commit_message.split(':').nth(1).unwrap_or(&commit_message).trim();
-                        let new_message = format!("{}: {}", selected_type, description);
-                        
-                        ui::print_section("📝 New Commit Message");
-                        println!("{}\n", new_message);
-
-                        let confirm_options = [
-                            "✅ Confirm and commit", 
-                            "🔄 Start over", 
-                            "❌ Cancel"
-                        ];
-                        match ui::show_selection_menu("Would you like to proceed with this commit message?", &confirm_options, 0)? {
-                            0 => {
-                                git::stage_and_commit(repo, &new_message)?;
-                                println!("Changes committed successfully!");
-                                break;
-                            }
-                            1 => continue,
-                            _ => break,
-                        }
-                    }
-                    2 => {
-                        git::stage_and_commit(repo, &commit_message)?;
-                        println!("Changes committed successfully!");
-                        break;
-                    }
-                    _ => break,
-                }
-            }
-            Ok(())
+/// The `shown` analyses grouped by directory, how many of `analyses` the risk filter hid
+/// and the files that failed
+fn print_file_analyses(repo: Option<&Repository>, analyses: &[FileAnalysis], shown: &[FileAnalysis], failures: &[FileAnalysisError]) {
+    ui::print_section("📊 File Analysis Results");
+    match analyses.len() - shown.len() {
+        0 => (),
+        hidden => println!("{} less risky file(s) hidden.\n", hidden),
+    }
+
+    for (dir, group) in group_by_top_level_dir(shown) {
+        let files = if group.len() == 1 { "1 file".to_string() } else { format!("{} files", group.len()) };
+        match dir {
+            Some(dir) => ui::print_section(&format!("📂 {}/ ({})", dir, files)),
+            None => ui::print_section(&format!("📂 Repository root ({})", files)),
         }
-        Err(e) => {
-            if e.to_string() == "No changes to commit" {
-                ui::print_section("📝 Repository Status");
-                println!("No changes to commit. Your working directory is clean.\n");
-                Ok(())
-            } else {
-                Err(e)
+
+        let mut report = Vec::new();
+        for analysis in group {
+            report.push(analysis.to_markdown());
+            // Only a hint for reviewers, so a failed blame just leaves it out
+            let blame = repo.and_then(|repo| git::blame_changed_lines(repo, &analysis.path).ok());
+            if let Some(summary) = blame.and_then(|lines| blame_summary(&lines)) {
+                report.push(summary);
             }
         }
+        ui::print_markdown_paged(&report.join("\n\n"));
+    }
+    if !failures.is_empty() {
+        ui::print_subsection("⚠️ Failed Files");
+        for failure in failures {
+            println!("- {}", failure);
+        }
+        println!();
     }
 }
 
-async fn handle_file_analysis(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
-    let spinner = ui::create_spinner("Analyzing changes")?;
-    let result = config.analyze_changes(repo).await;
-    spinner.finish_and_clear();
-    
-    match result {
-        Ok(analyses) => {
-            ui::print_section("📊 File Analysis Results");
-            
-            for analysis in analyses {
-                let markdown = format!("## 📁 {}\n{}", analysis.path, analysis.explanation);
-                ui::print_markdown(&markdown);
-            }
-            
-            Ok(())
+/// Analyses grouped by the first component of their path, `None` for files at the
+/// repository root. Directories with the riskiest change come first, then those with
+/// the most changed files; `analyses` are expected riskiest first, see `split_analyses`.
+fn group_by_top_level_dir(analyses: &[FileAnalysis]) -> Vec<(Option<&str>, Vec<&FileAnalysis>)> {
+    let mut groups: Vec<(Option<&str>, Vec<&FileAnalysis>)> = Vec::new();
+    for analysis in analyses {
+        let dir = analysis.path.split_once('/').map(|(dir, _)| dir);
+        match groups.iter_mut().find(|(name, _)| *name == dir) {
+            Some((_, group)) => group.push(analysis),
+            None => groups.push((dir, vec![analysis])),
         }
-        Err(e) => {
-            if e.to_string() == "No changes to commit" {
-                ui::print_section("📊 Repository Status");
-                println!("No changes to analyze. Your working directory is clean.\n");
-                Ok(())
-            } else {
-                Err(e)
-            }
+    }
+    groups.sort_by(|a, b| risk_order(a.1[0]).cmp(&risk_order(b.1[0])).then_with(|| b.1.len().cmp(&a.1.len())).then_with(|| a.0.cmp(&b.0)));
+    groups
+}
+
+/// One line naming who last touched the changed lines, most lines first
+fn blame_summary(lines: &[(u32, String)]) -> Option<String> {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for (_, author) in lines {
+        match counts.iter_mut().find(|(name, _)| name == author) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((author, 1)),
+        }
+    }
+    if counts.is_empty() {
+        return None;
+    }
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    let authors: Vec<String> = counts
+        .iter()
+        .map(|(name, count)| format!("{} ({} line{})", name, count, if *count == 1 { "" } else { "s" }))
+        .collect();
+    Some(format!("👤 Last touched by: {}", authors.join(", ")))
+}
+
+/// Separates successful analyses from failed files. Analyses are sorted riskiest first,
+/// files of the same risk and failures keep their order.
+fn split_analyses(results: Vec<FileAnalysisResult>) -> (Vec<FileAnalysis>, Vec<FileAnalysisError>) {
+    let mut analyses = Vec::new();
+    let mut failures = Vec::new();
+    for result in results {
+        match result {
+            Ok(analysis) => analyses.push(analysis),
+            Err(failure) => failures.push(failure),
         }
     }
+    analyses.sort_by_key(risk_order);
+    (analyses, failures)
+}
+
+/// Sorts by risk, riskiest first, with unrated files last
+fn risk_order(analysis: &FileAnalysis) -> (bool, Option<RiskLevel>) {
+    (analysis.risk.is_none(), analysis.risk)
+}
+
+fn export_analyses(analyses: &[FileAnalysis]) -> Result<(), Box<dyn Error>> {
+    let formats = ["📝 Markdown", "🧾 JSON"];
+    let format = match ui::show_selection_menu("Select export format", &formats, 0)? {
+        0 => ExportFormat::Markdown,
+        _ => ExportFormat::Json,
+    };
+
+    let filename = ui::get_text_with_default("File name", format.default_filename())?;
+    let path = Path::new(&filename);
+    if path.exists() && !ui::confirm(&format!("{} already exists. Overwrite it?", filename))? {
+        println!("Export cancelled.\n");
+        return Ok(());
+    }
+
+    export::export_analyses(analyses, format, path)?;
+    println!("Results exported to {}\n", filename);
+    Ok(())
 }
 
 async fn handle_contributor_analysis(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
-    let contributors = git::get_contributors(repo)?;
+    let path = ui::get_text_with_default("Only count changes under (path relative to the repository root)", ".")?;
+    let filter = prompt_contributor_filter()?.with_path(&path);
+    let contributors = load_contributors(config, repo, &filter)?;
+    if contributors.is_empty() {
+        ui::print_section("👥 Repository Contributors");
+        match &filter.path {
+            Some(path) => println!("No commits touching {} found in the selected date range.\n", path),
+            None => println!("No commits found in the selected date range.\n"),
+        }
+        return Ok(());
+    }
     
-    ui::print_section("👥 Repository Contributors");
+    match &filter.path {
+        Some(path) => ui::print_section(&format!("👥 Contributors to {}", path)),
+        None => ui::print_section("👥 Repository Contributors"),
+    }
     
-    let mut contributor_items: Vec<String> = contributors.iter().map(|c| {
-        format!("{} <{}> ({} commits)", c.name, c.email, c.commit_count)
-    }).collect();
-    contributor_items.push("❌ Exit".to_string());
-
+    let mut contributors = contributors;
+    let mut order = ContributorOrder::Commits;
+    order.sort(&mut contributors);
+    let mut page = 0;
     loop {
-        let selection = ui::show_selection_menu("Select a contributor to view details", &contributor_items, 0)?;
+        let now = chrono::Utc::now().timestamp();
+        let contributor_items: Vec<String> = contributors.iter().map(|c| order.label(c, now)).collect();
+        let other_order = order.other();
+        let sort_action = format!("🔃 Sort by {}", other_order.name());
+        let actions = ["📊 Team overview", "🔎 Search all contributors", sort_action.as_str(), "❌ Exit"];
+        let selection = match ui::show_paged_select("Select a contributor to view details", &contributor_items, &actions, &mut page)? {
+            ui::PagedSelection::Item(idx) => Some(idx),
+            // The team overview
+            ui::PagedSelection::Action(0) => None,
+            ui::PagedSelection::Action(1) => {
+                let mut items = contributor_items.clone();
+                items.push("↩️ Back".to_string());
+                match ui::show_fuzzy_select("Select a contributor to view details (type to search)", &items, 0)? {
+                    idx if idx < contributors.len() => Some(idx),
+                    _ => continue,
+                }
+            }
+            ui::PagedSelection::Action(2) => {
+                order = other_order;
+                order.sort(&mut contributors);
+                page = 0;
+                continue;
+            }
+            ui::PagedSelection::Action(_) => break,
+        };
 
-        if selection == contributor_items.len() - 1 {
-            break;
-        }
+        let summary = if let Some(idx) = selection {
+            let contributor = &contributors[idx];
+            display_contributor_info(contributor);
+            
+            let commits = git::get_contributor_commits(repo, &contributor.name, &contributor.email, &filter)?;
+            ui::print_subsection("🔄 Recent Commits");
+            for commit in commits.iter().take(config.recent_commits_shown()) {
+                println!("• {}", commit);
+            }
 
-        let contributor = &contributors[selection];
-        display_contributor_info(contributor);
-        
-        let stats = format_contributor_stats(contributor, repo)?;
-        let spinner = ui::create_spinner("Analyzing contributor's work")?;
-        let summary = config.analyze_contributor(&stats).await?;
-        spinner.finish_and_clear();
+            let stats = format_contributor_stats(contributor, &commits, config.recent_commits_shown());
+            let spinner = ui::create_spinner("Analyzing contributor's work")?;
+            let summary = config.analyze_contributor(&stats).await?;
+            spinner.finish_and_clear();
+            summary
+        } else {
+            let team = git::TeamStats::from_contributors(&contributors);
+            let stats = format_team_stats(&team);
+            ui::print_section("📊 Team Overview");
+            ui::print_markdown(&stats);
+
+            let spinner = ui::create_spinner("Analyzing the team's work")?;
+            let summary = config.analyze_team(&stats).await;
+            spinner.finish_and_clear();
+            summary?
+        };
         
         ui::print_section("🤖 AI Analysis");
-        ui::print_markdown(&summary);
+        ui::print_markdown_paged(&summary);
 
         println!("\nPress Enter to continue...");
         std::io::stdin().read_line(&mut String::new())?;
-        println!("\x1B[2J\x1B[1;1H"); // Clear screen
+        if config.clear_between_contributors() {
+            ui::clear_screen();
+        } else {
+            ui::print_separator();
+        }
+    }
+
+    Ok(())
+}
+
+/// How the contributor list is sorted
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ContributorOrder {
+    /// Most commits first
+    Commits,
+    /// Most recently active first
+    Recent,
+}
+
+impl ContributorOrder {
+    fn name(&self) -> &'static str {
+        match self {
+            ContributorOrder::Commits => "commit count",
+            ContributorOrder::Recent => "recent activity",
+        }
+    }
+
+    fn other(&self) -> ContributorOrder {
+        match self {
+            ContributorOrder::Commits => ContributorOrder::Recent,
+            ContributorOrder::Recent => ContributorOrder::Commits,
+        }
+    }
+
+    /// Sorts `contributors`, by name where they are level
+    fn sort(&self, contributors: &mut [git::ContributorStats]) {
+        match self {
+            ContributorOrder::Commits => contributors.sort_by(|a, b| b.commit_count.cmp(&a.commit_count).then_with(|| a.name.cmp(&b.name))),
+            ContributorOrder::Recent => contributors.sort_by(|a, b| {
+                let last_active = |c: &git::ContributorStats| c.active_period().map(|(_, last)| last);
+                last_active(b).cmp(&last_active(a)).then_with(|| a.name.cmp(&b.name))
+            }),
+        }
+    }
+
+    /// The menu entry for `contributor`, with what the list is sorted by
+    fn label(&self, contributor: &git::ContributorStats, now: i64) -> String {
+        let name = format!("{} <{}>", contributor.name, contributor.email);
+        match (self, contributor.active_period()) {
+            (ContributorOrder::Recent, Some((_, last))) => {
+                format!("{} (last active {}, {} commits)", name, ui::format_time_ago(last, now), contributor.commit_count)
+            }
+            _ => format!("{} ({} commits)", name, contributor.commit_count),
+        }
     }
+}
+
+async fn handle_contributor_report(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let contributors = report_contributors(config, repo)?;
+    if contributors.is_empty() {
+        ui::print_section("📒 Contributor Report");
+        println!("No commits found.\n");
+        return Ok(());
+    }
+    let tokens = contributors.iter().map(|(_, stats)| Config::estimate_tokens(stats)).sum();
+    if !confirm_estimate(config, tokens)? {
+        return Ok(());
+    }
+    let path = contributor_report_path(repo)?;
+    if path.exists() && !config.is_dry_run() && !ui::confirm(&format!("{} already exists. Replace it?", path.display()))? {
+        return Ok(());
+    }
+
+    let spinner = ui::create_spinner(&format!("Analyzing {} contributors", contributors.len()))?;
+    let (report, failed) = contributor_report(config, &contributors).await;
+    spinner.finish_and_clear();
 
+    if config.is_dry_run() {
+        ui::print_section("🧪 Dry Run");
+        println!("{} was not written. This is the report that would be written:\n", path.display());
+        ui::print_markdown(&report);
+    } else {
+        std::fs::write(&path, report)?;
+        ui::print_section("📒 Contributor Report");
+        println!("Contributor report for {} contributors written to {}\n", contributors.len(), path.display());
+    }
+    if !failed.is_empty() {
+        ui::print_subsection("⚠️ Failed Contributors");
+        for name in &failed {
+            println!("- {}", name);
+        }
+        println!();
+    }
     Ok(())
 }
 
-async fn generate_with_spinner(config: &Config, diff: &str) -> Result<String, Box<dyn Error>> {
+/// The report goes to the repository root
+const CONTRIBUTOR_REPORT_FILE: &str = "contributors.md";
+
+fn contributor_report_path(repo: &Repository) -> Result<std::path::PathBuf, Box<dyn Error>> {
+    Ok(repo.workdir().unwrap_or_else(|| repo.path()).join(CONTRIBUTOR_REPORT_FILE))
+}
+
+/// Every contributor over the whole history, most commits first, with their statistics
+/// formatted for `analyze_contributor`
+fn report_contributors(config: &Config, repo: &Repository) -> Result<Vec<(git::ContributorStats, String)>, Box<dyn Error>> {
+    let filter = git::ContributorFilter::default();
+    let mut contributors = load_contributors(config, repo, &filter)?;
+    contributors.sort_by(|a, b| b.commit_count.cmp(&a.commit_count).then_with(|| a.name.cmp(&b.name)));
+    contributors
+        .into_iter()
+        .map(|contributor| {
+            let commits = git::get_contributor_commits(repo, &contributor.name, &contributor.email, &filter)?;
+            let stats = format_contributor_stats(&contributor, &commits, config.recent_commits_shown());
+            Ok((contributor, stats))
+        })
+        .collect()
+}
+
+/// Analyzes `contributors` for the report written to `contributors.md`, with a table of
+/// contents. A failed analysis is noted in its section instead of failing the report.
+/// Returns the report and the names of the contributors whose analysis failed.
+async fn contributor_report(config: &Config, contributors: &[(git::ContributorStats, String)]) -> (String, Vec<String>) {
+    let stats: Vec<String> = contributors.iter().map(|(_, stats)| stats.clone()).collect();
+    let summaries = config.analyze_contributors(&stats).await;
+
+    let mut anchors: Vec<String> = Vec::new();
+    let mut contents = Vec::new();
+    let mut sections = Vec::new();
+    let mut failed = Vec::new();
+    for ((contributor, stats), summary) in contributors.iter().zip(summaries) {
+        let anchor = unique_anchor(&format!("Contributor: {} <{}>", contributor.name, contributor.email), &anchors);
+        contents.push(format!("- [{}](#{}) ({} commits)", contributor.name, anchor, contributor.commit_count));
+        anchors.push(anchor);
+        let summary = match summary {
+            Ok(summary) => summary,
+            Err(e) => {
+                failed.push(contributor.name.clone());
+                format!("_The analysis failed: {}_", e)
+            }
+        };
+        sections.push(format!("{}\n\n### AI Analysis\n{}", stats.trim_end(), summary.trim()));
+    }
+
+    let report = format!(
+        "# Contributor Report\n\nGenerated on {} for {} contributors.\n\n## Contents\n{}\n\n{}\n",
+        chrono::Local::now().format("%Y-%m-%d"),
+        contributors.len(),
+        contents.join("\n"),
+        sections.join("\n\n"),
+    );
+    (report, failed)
+}
+
+/// The anchor GitHub and most markdown renderers give `heading`, with `-1`, `-2`, ...
+/// appended when an earlier heading in `taken` already has it
+fn unique_anchor(heading: &str, taken: &[String]) -> String {
+    let base: String = heading
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == ' ' || *c == '-' || *c == '_')
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect();
+    let mut anchor = base.clone();
+    let mut suffix = 1;
+    while taken.contains(&anchor) {
+        anchor = format!("{}-{}", base, suffix);
+        suffix += 1;
+    }
+    anchor
+}
+
+/// Prints the estimated request size and cost, asking for confirmation when it is
+/// above the configured threshold. Returns false if the user cancelled.
+fn confirm_estimate(config: &Config, tokens: usize) -> Result<bool, Box<dyn Error>> {
+    let estimate = match config.estimate_cost(tokens) {
+        Some(cost) => format!("≈ {} tokens, ~${}", ui::format_number(tokens), ui::format_cost(cost)),
+        None => format!("≈ {} tokens", ui::format_number(tokens)),
+    };
+    println!("{}", estimate);
+
+    if tokens <= config.token_threshold() {
+        return Ok(true);
+    }
+
+    let options = ["🚀 Send anyway", "❌ Cancel"];
+    let prompt = format!("This request is above the {} token threshold", ui::format_number(config.token_threshold()));
+    Ok(ui::show_selection_menu(&prompt, &options, 1)? == 0)
+}
+
+/// Streams a new commit message, adds the `co_authors` trailers and shows it with its lint warnings
+/// `hint` is the user's feedback on the previous message, if any; a `fresh` message is never
+/// taken from the response cache.
+async fn generate_with_spinner(
+    config: &Config,
+    diff: &str,
+    detailed: bool,
+    hint: Option<&str>,
+    fresh: bool,
+    co_authors: &[String],
+) -> Result<(CommitMessage, Vec<LintWarning>), Box<dyn Error>> {
     let spinner = ui::create_spinner("Generating commit message")?;
-    let commit_message = config.generate_commit_message(diff).await?;
+    let mut stream = config.generate_commit_message_stream(diff, detailed, hint, fresh).await?;
+    let mut text = String::new();
+    while let Some(chunk) = stream.next().await {
+        text.push_str(&chunk?);
+        spinner.set_message(format!("Generating commit message: {}", ui::stream_preview(&text)));
+    }
     spinner.finish_and_clear();
+    // Trailers the model wrote itself are only replaced once co-authors were picked
+    let commit_message = match co_authors {
+        [] => config.parse_commit_message(&text),
+        co_authors => config.parse_commit_message(&text).with_co_authors(co_authors),
+    };
+    let warnings = config.lint_generated_message(&text);
 
-    ui::print_section("📝 Generated Commit Message");
-    println!("{}\n", commit_message);
+    print_commit_message("📝 Generated Commit Message", &commit_message);
+    print_lint_warnings(&warnings);
     
-    Ok(commit_message)
+    Ok((commit_message, warnings))
+}
+
+/// Generates `config.candidates()` messages and lets the user pick one, or have all of them
+/// generated again. The pick is printed like `generate_with_spinner` prints its message.
+async fn pick_candidate(
+    config: &Config,
+    diff: &str,
+    detailed: bool,
+    hint: Option<&str>,
+    co_authors: &[String],
+) -> Result<(CommitMessage, Vec<LintWarning>), Box<dyn Error>> {
+    loop {
+        let spinner = ui::create_spinner(&format!("Generating {} commit messages", config.candidates()))?;
+        let candidates = config.generate_commit_message_candidates(diff, config.candidates(), detailed, hint).await;
+        spinner.finish_and_clear();
+        let candidates = candidates?;
+
+        let idx = if candidates.len() == 1 {
+            0
+        } else {
+            ui::print_section("📝 Generated Commit Messages");
+            for (number, text) in candidates.iter().enumerate() {
+                // Indented under the number, without trailing spaces on blank lines
+                let indented: Vec<String> = text.trim().lines()
+                    .map(|line| if line.trim().is_empty() { String::new() } else { format!("   {}", line) })
+                    .collect();
+                println!("{}. {}\n", number + 1, indented.join("\n").trim_start());
+            }
+            let mut labels: Vec<String> = candidates
+                .iter()
+                .enumerate()
+                .map(|(number, text)| format!("{}. {}", number + 1, text.lines().next().unwrap_or("").trim()))
+                .collect();
+            labels.push("✨ Regenerate all".to_string());
+            let idx = ui::show_selection_menu("Which message would you like to use?", &labels, 0)?;
+            if idx == candidates.len() {
+                continue;
+            }
+            idx
+        };
+
+        let text = &candidates[idx];
+        let commit_message = match co_authors {
+            [] => config.parse_commit_message(text),
+            co_authors => config.parse_commit_message(text).with_co_authors(co_authors),
+        };
+        let warnings = config.lint_generated_message(text);
+        print_commit_message("📝 Generated Commit Message", &commit_message);
+        print_lint_warnings(&warnings);
+        return Ok((commit_message, warnings));
+    }
+}
+
+fn print_lint_warnings(warnings: &[LintWarning]) {
+    if warnings.is_empty() {
+        return;
+    }
+    ui::print_subsection("🔍 Style Warnings");
+    for warning in warnings {
+        println!("- {}", warning);
+    }
+    println!();
+}
+
+fn print_commit_message(title: &str, message: &CommitMessage) {
+    ui::print_section(title);
+    println!("{}\n", message.summary);
+
+    if let Some(body) = &message.body {
+        ui::print_section("📄 Body");
+        println!("{}\n", body);
+    }
+
+    if let Some(footer) = &message.footer {
+        ui::print_section("📌 Footer");
+        println!("{}\n", footer);
+    }
 }
 
+/// Words listed as a contributor's areas, see `ContributorStats::areas`
+const CONTRIBUTOR_AREAS_SHOWN: usize = 5;
+
 fn display_contributor_info(contributor: &git::ContributorStats) {
     ui::print_section(&format!("👤 Contributor Details: {}", contributor.name));
     println!("📧 Email: {}", contributor.email);
+    let areas = contributor.areas(CONTRIBUTOR_AREAS_SHOWN);
+    if !areas.is_empty() {
+        println!("🏷️ Areas: {}", areas.join(", "));
+    }
     
     ui::print_subsection("📊 Statistics");
     println!("  • Commits: {}", contributor.commit_count);
     println!("  • Lines added: {}", contributor.additions);
     println!("  • Lines deleted: {}", contributor.deletions);
     println!("  • Files changed: {}", contributor.files_changed.len());
+    if let Some((first, last)) = contributor.active_period() {
+        let now = chrono::Utc::now().timestamp();
+        println!("  • First commit: {} ({})", ui::format_date(first), ui::format_time_ago(first, now));
+        println!("  • Last commit: {} (last active {})", ui::format_date(last), ui::format_time_ago(last, now));
+        println!("  • Active for {}", ui::format_months(last - first));
+    }
+
+    ui::print_subsection("🗓️ Activity by Weekday and Hour (committer's local time)");
+    ui::print_heatmap(&contributor.commit_times);
 
     ui::print_subsection("📁 Most Modified Files");
     for (file, count) in &contributor.most_modified_files {
         println!("  • {} ({} modifications)", file, count);
     }
 
+    if !contributor.hotspot_files.is_empty() {
+        ui::print_subsection("🔥 Hotspot files");
+        for (file, churn) in contributor.hotspot_files.iter().take(5) {
+            println!("  • {} ({} lines changed)", file, churn);
+        }
+    }
+
     ui::print_subsection("🔧 File Types");
     let mut file_types: Vec<_> = contributor.file_types.iter().collect();
     file_types.sort_by(|a, b| b.1.cmp(a.1));
@@ -199,38 +2439,103 @@ fn display_contributor_info(contributor: &git::ContributorStats) {
     }
 
     ui::print_subsection("📈 Largest Contributions");
-    for (additions, deletions, message) in &contributor.largest_commits {
-        println!("  • +{} -{} : {}", additions, deletions, message);
+    for commit in &contributor.largest_commits {
+        println!("  • {}", commit);
     }
 }
 
-fn format_contributor_stats(
-    contributor: &git::ContributorStats,
+fn load_contributors(
+    config: &Config,
     repo: &Repository,
-) -> Result<String, Box<dyn Error>> {
-    let commits = git::get_contributor_commits(
-        repo,
-        &contributor.name,
-        &contributor.email
-    )?;
-
-    ui::print_subsection("🔄 Recent Commits");
-    for commit in commits.iter().take(5) {
-        println!("• {}", commit);
+    filter: &git::ContributorFilter,
+) -> Result<Vec<git::ContributorStats>, Box<dyn Error>> {
+    let progress = ui::create_progress_bar("Reading history")?;
+    let on_progress = |done, total| ui::set_progress(&progress, done, total);
+    let contributors = if config.uses_cache() {
+        git::get_contributors_cached(repo, filter, !config.is_dry_run(), on_progress)
+    } else {
+        git::get_contributors(repo, filter, on_progress)
+    };
+    progress.finish_and_clear();
+    Ok(contributors?)
+}
+
+/// Asks for an optional date range, re-prompting until both bounds parse
+fn prompt_contributor_filter() -> Result<git::ContributorFilter, Box<dyn Error>> {
+    loop {
+        let since = ui::get_text_input("Only include commits since (e.g. 2024-01-01 or 3 months ago, empty for all history)")?;
+        let until = ui::get_text_input("Only include commits until (empty for now)")?;
+        match git::ContributorFilter::parse(&since, &until) {
+            Ok(filter) => return Ok(filter.with_merges(ui::confirm("Include merge commits?")?)),
+            Err(e) => println!("{}. Please try again.", e),
+        }
     }
+}
+
+fn format_team_stats(team: &git::TeamStats) -> String {
+    let share = |count: usize| if team.total_commits == 0 { 0.0 } else { count as f64 * 100.0 / team.total_commits as f64 };
+    format!(
+        "## Team
+
+### Statistics
+- Contributors: {}
+- Total commits: {}
+- Lines added: {}
+- Lines deleted: {}
+- Bus factor: {} (contributors behind half of all commits)
+
+### Top contributors
+{}
+
+### Most frequently modified files
+{}",
+        team.contributor_count,
+        team.total_commits,
+        team.additions,
+        team.deletions,
+        team.bus_factor,
+        team.top_contributors.iter()
+            .take(10)
+            .map(|(name, count)| format!("- {}: {} commits ({:.1}%)", name, count, share(*count)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        team.top_files.iter()
+            .map(|(file, count)| format!("- {} ({} modifications)", file, count))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
 
-    Ok(format!(
+/// The statistics sent for a contributor, with the `recent` latest of their `commits`
+fn format_contributor_stats(contributor: &git::ContributorStats, commits: &[String], recent: usize) -> String {
+    let activity = match contributor.active_period() {
+        Some((first, last)) => format!(
+            "\n- First commit: {}\n- Last commit: {}\n- Active for: {}",
+            ui::format_date(first),
+            ui::format_date(last),
+            ui::format_months(last - first)
+        ),
+        None => String::new(),
+    };
+    let areas = match contributor.areas(CONTRIBUTOR_AREAS_SHOWN) {
+        areas if areas.is_empty() => String::new(),
+        areas => format!("\n- Areas: {}", areas.join(", ")),
+    };
+    format!(
         "## Contributor: {} <{}>
 
 ### Statistics
 - Total commits: {}
 - Lines added: {}
 - Lines deleted: {}
-- Files modified: {}
+- Files modified: {}{}{}
 
 ### Most frequently modified files
 {}
 
+### Hotspot files (most lines added plus deleted)
+{}
+
 ### File type distribution
 {}
 
@@ -248,20 +2553,27 @@ fn format_contributor_stats(
         contributor.additions,
         contributor.deletions,
         contributor.files_changed.len(),
+        activity,
+        areas,
         contributor.most_modified_files.iter()
             .map(|(file, count)| format!("- {} ({} modifications)", file, count))
             .collect::<Vec<_>>()
             .join("\n"),
+        contributor.hotspot_files.iter()
+            .take(5)
+            .map(|(file, churn)| format!("- {} ({} lines changed)", file, churn))
+            .collect::<Vec<_>>()
+            .join("\n"),
         contributor.file_types.iter()
             .map(|(ext, count)| format!("- {}: {} files", ext, count))
             .collect::<Vec<_>>()
             .join("\n"),
         contributor.largest_commits.iter()
-            .map(|(add, del, msg)| format!("- +{} -{} : {}", add, del, msg))
+            .map(|commit| format!("- {}", commit))
             .collect::<Vec<_>>()
             .join("\n"),
         commits.iter()
-            .take(5)
+            .take(recent)
             .map(|c| format!("- {}", c))
             .collect::<Vec<_>>()
             .join("\n"),
@@ -269,5 +2581,279 @@ fn format_contributor_stats(
             .map(|f| format!("- {}", f))
             .collect::<Vec<_>>()
             .join("\n")
-    ))
-} 
\ No newline at end of file
+    )
+}
+
+/// Commits used for a changelog when the repository has no tags
+const CHANGELOG_FALLBACK_COMMITS: usize = 50;
+
+/// The release range and the commits in it: everything since the last tag, or the most
+/// recent commits (reported through `notice`) if there are no tags
+fn changelog_commits(repo: &Repository, notice: impl Fn(&str)) -> Result<(String, Vec<String>), Box<dyn Error>> {
+    match git::commits_since_last_tag(repo)? {
+        Some(tagged) => Ok((format!("Changes since {}", tagged.tag), tagged.commits)),
+        None => {
+            notice(&format!("No tags found, using the last {} commits instead.", CHANGELOG_FALLBACK_COMMITS));
+            Ok(("Unreleased changes".to_string(), git::recent_commits(repo, CHANGELOG_FALLBACK_COMMITS)?))
+        }
+    }
+}
+
+/// `range` from `changelog_commits` as part of a sentence, e.g. "changes since v1.2"
+fn changelog_range_label(range: &str) -> String {
+    let mut chars = range.chars();
+    chars.next().map(|first| first.to_lowercase().chain(chars).collect()).unwrap_or_default()
+}
+
+async fn handle_changelog(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let (range, commits) = changelog_commits(repo, |notice| println!("ℹ️ {}\n", notice))?;
+    if commits.is_empty() {
+        ui::print_section("📜 Changelog");
+        println!("No commits in {}.\n", changelog_range_label(&range));
+        return Ok(());
+    }
+    if !confirm_estimate(config, Config::estimate_tokens(&commits.join("\n")))? {
+        return Ok(());
+    }
+
+    let spinner = ui::create_spinner(&format!("Summarizing {} commits", commits.len()))?;
+    let changelog = config.generate_changelog(&range, &commits).await;
+    spinner.finish_and_clear();
+    let changelog = changelog?;
+
+    ui::print_section("📜 Changelog");
+    ui::print_markdown(&changelog);
+
+    let options = ["💾 Write to CHANGELOG.md", "↩️ Back"];
+    if ui::show_selection_menu("What would you like to do with the changelog?", &options, 1)? == 0 {
+        write_changelog(config, repo, &changelog)?;
+    }
+    Ok(())
+}
+
+async fn handle_pull_request(config: &Config, repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let base = config.base_branch(repo)?;
+    let (commits, file_diffs) = match branch_diffs(config, repo, |notice| println!("🌿 {}\n", notice)) {
+        Ok(changes) => changes,
+        Err(NoahError::NoChanges) => {
+            ui::print_section("🔀 Pull Request");
+            println!("No commits on this branch since {}. Switch to the branch to describe, or pick another base with --base.\n", base);
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let Some(diff) = prepare_diff(config, &join_file_diffs(config, file_diffs))? else {
+        return Ok(());
+    };
+
+    let spinner = ui::create_spinner(&format!("Describing {} commits", commits.len()))?;
+    let description = config.generate_pr_description(&diff, &commits).await;
+    spinner.finish_and_clear();
+    let description = description?;
+    let markdown = description.to_markdown();
+
+    ui::print_section("🔀 Pull Request");
+    ui::print_markdown(&markdown);
+
+    let options = ["📋 Copy to clipboard", "💾 Write to a file", "↩️ Back"];
+    loop {
+        match ui::show_selection_menu("What would you like to do with the description?", &options, 0)? {
+            0 => match ui::copy_to_clipboard(&markdown) {
+                Ok(()) => println!("📋 Copied the description to the clipboard\n"),
+                Err(e) => println!("ℹ️ {}, so copy the description from above.\n", e),
+            },
+            1 => write_pr_description(repo, &markdown)?,
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// Suggested file name for `write_pr_description`
+const PR_DESCRIPTION_FILE: &str = "pull-request.md";
+
+/// Saves `markdown` to a file the user names, relative to the repository root, asking
+/// before overwriting one
+fn write_pr_description(repo: &Repository, markdown: &str) -> Result<(), Box<dyn Error>> {
+    let filename = ui::get_text_with_default("File name", PR_DESCRIPTION_FILE)?;
+    let path = repo.workdir().ok_or("Repository has no working directory")?.join(&filename);
+    if path.exists() && !ui::confirm(&format!("{} already exists. Overwrite it?", path.display()))? {
+        println!("Nothing written.\n");
+        return Ok(());
+    }
+    std::fs::write(&path, markdown)?;
+    println!("Description written to {}\n", path.display());
+    Ok(())
+}
+
+/// Adds `changelog` to the top of CHANGELOG.md in the repository root, below its title
+fn write_changelog(config: &Config, repo: &Repository, changelog: &str) -> Result<(), Box<dyn Error>> {
+    let path = repo.workdir().ok_or("Repository has no working directory")?.join("CHANGELOG.md");
+    if config.is_dry_run() {
+        ui::print_section("🧪 Dry Run");
+        println!("{} was not changed. This is the entry that would be added:\n", path.display());
+        println!("{}\n", changelog.trim());
+        return Ok(());
+    }
+    let content = match std::fs::read_to_string(&path) {
+        Ok(existing) => {
+            let (title, rest) = match existing.split_once('\n') {
+                Some((first, rest)) if first.starts_with("# ") => (format!("{}\n\n", first), rest.trim_start()),
+                _ => (String::new(), existing.as_str()),
+            };
+            format!("{}{}\n\n{}", title, changelog.trim(), rest)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => format!("# Changelog\n\n{}\n", changelog.trim()),
+        Err(e) => return Err(e.into()),
+    };
+
+    std::fs::write(&path, content)?;
+    println!("Changelog written to {}\n", path.display());
+    Ok(())
+}
+
+fn handle_install_hook(repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let path = hook::hook_path(repo)?;
+    match hook::status(repo)? {
+        HookStatus::Installed => {
+            let prompt = format!("The commit message hook is installed at {}", path.display());
+            let options = ["🔄 Reinstall", "🗑️ Uninstall", "↩️ Back"];
+            match ui::show_selection_menu(&prompt, &options, 0)? {
+                0 => {
+                    hook::install(repo, false)?;
+                    println!("Commit message hook reinstalled at {}\n", path.display());
+                }
+                1 => {
+                    hook::uninstall(repo)?;
+                    println!("Commit message hook removed from {}\n", path.display());
+                }
+                _ => (),
+            }
+        }
+        status => {
+            if status == HookStatus::Foreign
+                && !ui::confirm(&format!("{} already exists. Replace it with the commit message hook?", path.display()))?
+            {
+                return Ok(());
+            }
+            hook::install(repo, true)?;
+            println!("Commit message hook installed at {}", path.display());
+            println!("Run `git commit` without -m and the editor opens with a suggested message.\n");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{ada, commit_file, test_config, temp_repo, write_file};
+
+    /// The tree the index would be committed as
+    fn index_tree(repo: &Repository) -> git2::Oid {
+        repo.index().unwrap().write_tree().unwrap()
+    }
+
+    #[test]
+    fn dry_run_commit_leaves_head_and_index_alone() {
+        let (_dir, repo) = temp_repo();
+        let head = commit_file(&repo, "a.txt", "a\n", "Add a", &ada());
+        write_file(&repo, "a.txt", "a\nb\n");
+        let diff = git::get_diff(&repo, git::DEFAULT_CONTEXT_LINES).unwrap();
+        let message = CommitMessage::parse("feat: add b");
+        let remembered = || crate::history::MessageHistory::open(repo.path()).best_match(&diff, 1.0).is_some();
+
+        let index = index_tree(&repo);
+        commit(&test_config(&repo).with_dry_run(true), &repo, &diff, &message, true, None).unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(head));
+        assert_eq!(index_tree(&repo), index);
+        assert!(!remembered());
+
+        commit(&test_config(&repo), &repo, &diff, &message, true, None).unwrap();
+        assert_ne!(repo.head().unwrap().target(), Some(head));
+        assert!(remembered());
+    }
+
+    #[test]
+    fn dry_run_keeps_what_was_staged_before() {
+        let (_dir, repo) = temp_repo();
+        let head = commit_file(&repo, "a.txt", "a\n", "Add a", &ada());
+        write_file(&repo, "a.txt", "a\nb\n");
+        let mut index = repo.index().unwrap();
+        index.add_path(std::path::Path::new("a.txt")).unwrap();
+        index.write().unwrap();
+        let staged = index_tree(&repo);
+
+        let diff = git::get_staged_diff(&repo, git::DEFAULT_CONTEXT_LINES).unwrap();
+        commit(&test_config(&repo).with_dry_run(true), &repo, &diff, &CommitMessage::parse("feat: add b"), false, None).unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(head));
+        assert_eq!(index_tree(&repo), staged);
+    }
+
+    /// A bare clone of a repository with one commit
+    fn bare_clone() -> (tempfile::TempDir, tempfile::TempDir, Repository) {
+        let (dir, repo) = temp_repo();
+        commit_file(&repo, "a.txt", "a\n", "Add a", &ada());
+        let bare_dir = tempfile::tempdir().unwrap();
+        let bare = git2::build::RepoBuilder::new()
+            .bare(true)
+            .clone(&dir.path().display().to_string(), bare_dir.path())
+            .unwrap();
+        (dir, bare_dir, bare)
+    }
+
+    #[test]
+    fn bare_repositories_only_allow_history_modes() {
+        let (_dir, _bare_dir, bare) = bare_clone();
+        for mode in [Mode::CommitMessage, Mode::InteractiveStage, Mode::FileAnalysis] {
+            let error = mode.check_repository(&bare).unwrap_err().to_string();
+            assert!(error.starts_with("This command needs a working tree"), "{:?}: {}", mode, error);
+        }
+        Mode::ContributorAnalysis.check_repository(&bare).unwrap();
+        Mode::Changelog.check_repository(&bare).unwrap();
+    }
+
+    #[test]
+    fn history_modes_need_a_commit() {
+        let (_dir, repo) = temp_repo();
+        let error = Mode::ContributorAnalysis.check_repository(&repo).unwrap_err().to_string();
+        assert!(error.contains("no commits yet"), "{}", error);
+        Mode::CommitMessage.check_repository(&repo).unwrap();
+    }
+
+    #[test]
+    fn only_committing_modes_warn_about_a_detached_head() {
+        let (_dir, repo) = temp_repo();
+        let head = commit_file(&repo, "a.txt", "a\n", "Add a", &ada());
+        assert_eq!(Mode::CommitMessage.detached_head_warning(&repo), None);
+
+        repo.set_head_detached(head).unwrap();
+        assert!(Mode::CommitMessage.detached_head_warning(&repo).is_some());
+        assert!(Mode::InteractiveStage.detached_head_warning(&repo).is_some());
+        assert_eq!(Mode::ContributorAnalysis.detached_head_warning(&repo), None);
+    }
+
+    #[test]
+    fn changelogs_cover_the_commits_since_the_last_tag() {
+        let (_dir, repo) = temp_repo();
+        let first = commit_file(&repo, "a.txt", "a\n", "Add a", &ada());
+        repo.tag_lightweight("v1.0", &repo.find_object(first, None).unwrap(), false).unwrap();
+        let (range, commits) = changelog_commits(&repo, |_| panic!("there is a tag")).unwrap();
+        assert_eq!((range.as_str(), commits.len()), ("Changes since v1.0", 0));
+        assert_eq!(changelog_range_label(&range), "changes since v1.0");
+
+        commit_file(&repo, "b.txt", "b\n", "Add b", &ada());
+        let (_, commits) = changelog_commits(&repo, |_| ()).unwrap();
+        assert_eq!(commits.len(), 1);
+    }
+
+    #[test]
+    fn changelogs_without_tags_use_the_recent_commits() {
+        let (_dir, repo) = temp_repo();
+        commit_file(&repo, "a.txt", "a\n", "Add a", &ada());
+        let notices = std::cell::RefCell::new(Vec::new());
+        let (range, commits) = changelog_commits(&repo, |notice| notices.borrow_mut().push(notice.to_string())).unwrap();
+        assert_eq!((range.as_str(), commits.len()), ("Unreleased changes", 1));
+        assert_eq!(changelog_range_label(&range), "unreleased changes");
+        assert_eq!(notices.into_inner(), [format!("No tags found, using the last {} commits instead.", CHANGELOG_FALLBACK_COMMITS)]);
+    }
+}
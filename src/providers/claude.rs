@@ -1,9 +1,13 @@
 use async_trait::async_trait;
 use std::error::Error;
-use reqwest::Client;
-use serde_json::{json, Value};
+use std::ops::RangeInclusive;
+use reqwest::{Client, RequestBuilder};
+use serde_json::json;
 
-use super::Provider;
+use super::{response_text, send, send_json, sse_text_stream, Provider, ProviderError, Sampling, TextStream};
+
+/// The Messages API requires `max_tokens`, so this is sent unless one is configured
+const DEFAULT_MAX_TOKENS: u32 = 8192;
 
 #[derive(Debug)]
 pub struct ClaudeProvider {
@@ -12,50 +16,69 @@ pub struct ClaudeProvider {
 }
 
 impl ClaudeProvider {
-    pub fn new() -> Self {
-        Self {
+    /// Fails with `ProviderError::MissingApiKey` unless `ANTHROPIC_API_KEY` is set
+    pub fn new() -> Result<Self, ProviderError> {
+        Ok(Self {
             client: Client::new(),
-            api_key: std::env::var("ANTHROPIC_API_KEY").expect("ANTHROPIC_API_KEY must be set"),
-        }
-    }
-}
-
-#[async_trait]
-impl Provider for ClaudeProvider {
-    fn name(&self) -> &str {
-        "Claude"
+            api_key: super::api_key("ANTHROPIC_API_KEY").ok_or(ProviderError::MissingApiKey { var: "ANTHROPIC_API_KEY" })?,
+        })
     }
 
-    async fn generate_text(
-        &self,
-        system_prompt: &str,
-        user_input: &str,
-        temperature: f32,
-    ) -> Result<String, Box<dyn Error>> {
-        let response = self.client
+    fn request(&self, model: &str, system_prompt: &str, user_input: &str, sampling: Sampling, stream: bool) -> RequestBuilder {
+        self.client
             .post("https://api.anthropic.com/v1/messages")
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
             .json(&json!({
-                "model": "claude-3-5-haiku-latest",
+                "model": model,
                 "messages": [
                     {
                         "role": "user",
                         "content": user_input
                     }
                 ],
-                "temperature": temperature,
+                "temperature": sampling.temperature,
                 "system": system_prompt,
-                "max_tokens": 8192
+                "max_tokens": sampling.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+                "stream": stream
             }))
-            .send()
-            .await?
-            .json::<Value>()
-            .await?;
-
-        Ok(response["content"][0]["text"]
-            .as_str()
-            .unwrap_or("Failed to generate text")
-            .to_string())
     }
-} 
\ No newline at end of file
+}
+
+#[async_trait]
+impl Provider for ClaudeProvider {
+    fn name(&self) -> &str {
+        "Claude"
+    }
+
+    fn temperature_range(&self) -> RangeInclusive<f32> {
+        0.0..=1.0
+    }
+
+    fn available_models(&self) -> Vec<&str> {
+        vec!["claude-3-5-haiku-latest", "claude-3-5-sonnet-latest", "claude-3-7-sonnet-latest"]
+    }
+
+    async fn generate_text(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        user_input: &str,
+        sampling: Sampling,
+    ) -> Result<String, Box<dyn Error>> {
+        let response = send_json(self.name(), self.request(model, system_prompt, user_input, sampling, false)).await?;
+
+        response_text(self.name(), response["content"][0]["text"].as_str())
+    }
+
+    async fn generate_text_stream(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        user_input: &str,
+        sampling: Sampling,
+    ) -> Result<TextStream, Box<dyn Error>> {
+        let response = send(self.name(), self.request(model, system_prompt, user_input, sampling, true)).await?;
+        Ok(sse_text_stream(self.name(), response, |event| event["delta"]["text"].as_str().map(str::to_string)))
+    }
+}
\ No newline at end of file
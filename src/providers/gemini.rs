@@ -1,9 +1,9 @@
 use async_trait::async_trait;
 use std::error::Error;
-use reqwest::Client;
-use serde_json::{json, Value};
+use reqwest::{Client, RequestBuilder};
+use serde_json::json;
 
-use super::Provider;
+use super::{response_text, send, send_json, sse_text_stream, Provider, ProviderError, Sampling, TextStream};
 
 #[derive(Debug)]
 pub struct GeminiProvider {
@@ -12,11 +12,38 @@ pub struct GeminiProvider {
 }
 
 impl GeminiProvider {
-    pub fn new() -> Self {
-        Self {
+    /// Fails with `ProviderError::MissingApiKey` unless `GEMINI_API_KEY` is set
+    pub fn new() -> Result<Self, ProviderError> {
+        Ok(Self {
             client: Client::new(),
-            api_key: std::env::var("GEMINI_API_KEY").expect("GEMINI_API_KEY must be set"),
+            api_key: super::api_key("GEMINI_API_KEY").ok_or(ProviderError::MissingApiKey { var: "GEMINI_API_KEY" })?,
+        })
+    }
+
+    fn request(&self, model: &str, system_prompt: &str, user_input: &str, sampling: Sampling, stream: bool) -> RequestBuilder {
+        let url = if stream {
+            format!("https://generativelanguage.googleapis.com/v1/models/{}:streamGenerateContent?alt=sse", model)
+        } else {
+            format!("https://generativelanguage.googleapis.com/v1/models/{}:generateContent", model)
+        };
+
+        let mut generation_config = json!({ "temperature": sampling.temperature });
+        if let Some(max_tokens) = sampling.max_tokens {
+            generation_config["maxOutputTokens"] = json!(max_tokens);
         }
+
+        self.client
+            .post(url)
+            .query(&[("key", &self.api_key)])
+            .json(&json!({
+                "contents": [{
+                    "role": "user",
+                    "parts": [{
+                        "text": format!("{}\n\n{}", system_prompt, user_input)
+                    }]
+                }],
+                "generationConfig": generation_config
+            }))
     }
 }
 
@@ -26,34 +53,30 @@ impl Provider for GeminiProvider {
         "Gemini"
     }
 
+    fn available_models(&self) -> Vec<&str> {
+        vec!["gemini-2.0-flash", "gemini-2.0-flash-lite", "gemini-1.5-pro"]
+    }
+
     async fn generate_text(
         &self,
+        model: &str,
         system_prompt: &str,
         user_input: &str,
-        temperature: f32,
+        sampling: Sampling,
     ) -> Result<String, Box<dyn Error>> {
-        let response = self.client
-            .post("https://generativelanguage.googleapis.com/v1/models/gemini-2.0-flash:generateContent")
-            .query(&[("key", &self.api_key)])
-            .json(&json!({
-                "contents": [{
-                    "role": "user",
-                    "parts": [{
-                        "text": format!("{}\n\n{}", system_prompt, user_input)
-                    }]
-                }],
-                "generationConfig": {
-                    "temperature": temperature
-                }
-            }))
-            .send()
-            .await?
-            .json::<Value>()
-            .await?;
-
-        Ok(response["candidates"][0]["content"]["parts"][0]["text"]
-            .as_str()
-            .unwrap_or("Failed to generate text")
-            .to_string())
+        let response = send_json(self.name(), self.request(model, system_prompt, user_input, sampling, false)).await?;
+
+        response_text(self.name(), response["candidates"][0]["content"]["parts"][0]["text"].as_str())
+    }
+
+    async fn generate_text_stream(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        user_input: &str,
+        sampling: Sampling,
+    ) -> Result<TextStream, Box<dyn Error>> {
+        let response = send(self.name(), self.request(model, system_prompt, user_input, sampling, true)).await?;
+        Ok(sse_text_stream(self.name(), response, |event| event["candidates"][0]["content"]["parts"][0]["text"].as_str().map(str::to_string)))
     }
-} 
\ No newline at end of file
+}
\ No newline at end of file
@@ -0,0 +1,97 @@
+use async_trait::async_trait;
+use std::error::Error;
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{response_text, send, send_json, sse_text_stream, Provider, Sampling, TextStream};
+
+/// Where a local, OpenAI-compatible server listens and which models it serves
+#[derive(Debug, Clone, Deserialize)]
+pub struct LocalEndpoint {
+    /// API root including the version, e.g. `http://localhost:11434/v1` for Ollama
+    pub base_url: String,
+    /// Model names as the server knows them; the first one is the default
+    #[serde(default)]
+    pub models: Vec<String>,
+}
+
+/// Any server with an OpenAI-compatible chat completions API, such as Ollama or LM Studio.
+/// Sends `LOCAL_API_KEY` as a bearer token if it is set; most local servers don't need one.
+#[derive(Debug)]
+pub struct LocalProvider {
+    client: Client,
+    endpoint: LocalEndpoint,
+    api_key: Option<String>,
+}
+
+impl LocalProvider {
+    pub fn new(endpoint: LocalEndpoint) -> Self {
+        Self {
+            client: Client::new(),
+            endpoint,
+            api_key: super::api_key("LOCAL_API_KEY"),
+        }
+    }
+
+    fn request(&self, model: &str, system_prompt: &str, user_input: &str, sampling: Sampling, stream: bool) -> RequestBuilder {
+        let url = format!("{}/chat/completions", self.endpoint.base_url.trim_end_matches('/'));
+        let mut request = self.client.post(url);
+        if let Some(api_key) = &self.api_key {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
+        }
+        let mut body = json!({
+            "model": model,
+            "messages": [
+                {
+                    "role": "system",
+                    "content": system_prompt
+                },
+                {
+                    "role": "user",
+                    "content": user_input
+                }
+            ],
+            "temperature": sampling.temperature,
+            "stream": stream
+        });
+        if let Some(max_tokens) = sampling.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        request.json(&body)
+    }
+}
+
+#[async_trait]
+impl Provider for LocalProvider {
+    fn name(&self) -> &str {
+        "Local"
+    }
+
+    fn available_models(&self) -> Vec<&str> {
+        self.endpoint.models.iter().map(String::as_str).collect()
+    }
+
+    async fn generate_text(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        user_input: &str,
+        sampling: Sampling,
+    ) -> Result<String, Box<dyn Error>> {
+        let response = send_json(self.name(), self.request(model, system_prompt, user_input, sampling, false)).await?;
+
+        response_text(self.name(), response["choices"][0]["message"]["content"].as_str())
+    }
+
+    async fn generate_text_stream(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        user_input: &str,
+        sampling: Sampling,
+    ) -> Result<TextStream, Box<dyn Error>> {
+        let response = send(self.name(), self.request(model, system_prompt, user_input, sampling, true)).await?;
+        Ok(sse_text_stream(self.name(), response, |event| event["choices"][0]["delta"]["content"].as_str().map(str::to_string)))
+    }
+}
@@ -1,84 +1,391 @@
 use async_trait::async_trait;
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt::Debug;
+use std::ops::RangeInclusive;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
 use dialoguer::{theme::ColorfulTheme, Select};
+use futures::stream::{LocalBoxStream, StreamExt};
+use serde_json::Value;
 
 pub mod openai;
 pub mod claude;
 pub mod gemini;
 pub mod deepseek;
+pub mod local;
+pub mod rate_limit;
 
 pub use openai::OpenAIProvider;
 pub use claude::ClaudeProvider;
 pub use gemini::GeminiProvider;
 pub use deepseek::DeepSeekProvider;
+pub use local::{LocalEndpoint, LocalProvider};
+pub use rate_limit::RateLimitStatus;
 
 /// Base trait for AI model providers with general capabilities
 #[async_trait]
 pub trait Provider: Send + Sync + Debug {
     fn name(&self) -> &str;
+
+    /// Models offered by this provider; the first one is the default
+    fn available_models(&self) -> Vec<&str>;
+
+    /// Temperatures the API accepts; others are rejected with an error
+    fn temperature_range(&self) -> RangeInclusive<f32> {
+        0.0..=2.0
+    }
     
-    /// Generate text based on a system prompt and user input
+    /// Generate text with the given model based on a system prompt and user input
     async fn generate_text(
         &self,
+        model: &str,
         system_prompt: &str,
         user_input: &str,
-        temperature: f32,
+        sampling: Sampling,
     ) -> Result<String, Box<dyn Error>>;
+
+    /// Generate text as a stream of chunks that concatenate to the full response.
+    /// Providers without streaming support yield the complete text as one chunk.
+    async fn generate_text_stream(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        user_input: &str,
+        sampling: Sampling,
+    ) -> Result<TextStream, Box<dyn Error>> {
+        let text = self.generate_text(model, system_prompt, user_input, sampling).await?;
+        Ok(futures::stream::once(async move { Ok(text) }).boxed_local())
+    }
+}
+
+/// Sampling parameters sent with a request
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    pub temperature: f32,
+    /// `None` leaves the length of the response to the provider's default
+    pub max_tokens: Option<u32>,
+}
+
+/// Configured generation settings. Unset values fall back to the defaults of the
+/// task, e.g. a lower temperature for fixing a subject line.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GenerationOptions {
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+}
+
+impl GenerationOptions {
+    /// Values set in `self` win over those in `base`
+    pub fn or(self, base: GenerationOptions) -> GenerationOptions {
+        GenerationOptions {
+            temperature: self.temperature.or(base.temperature),
+            max_tokens: self.max_tokens.or(base.max_tokens),
+        }
+    }
+
+    /// The parameters to send, using `default_temperature` if none is configured
+    pub fn sampling(&self, default_temperature: f32) -> Sampling {
+        Sampling {
+            temperature: self.temperature.unwrap_or(default_temperature),
+            max_tokens: self.max_tokens,
+        }
+    }
+}
+
+/// Input price in US dollars per million tokens for the models offered by the providers
+pub fn input_price_per_million(model: &str) -> Option<f64> {
+    match model {
+        "gpt-4-turbo-preview" => Some(10.0),
+        "gpt-4o" => Some(2.5),
+        "gpt-4o-mini" => Some(0.15),
+        "claude-3-5-haiku-latest" => Some(0.8),
+        "claude-3-5-sonnet-latest" | "claude-3-7-sonnet-latest" => Some(3.0),
+        "deepseek-chat" => Some(0.27),
+        "deepseek-reasoner" => Some(0.55),
+        "gemini-2.0-flash" => Some(0.1),
+        "gemini-2.0-flash-lite" => Some(0.075),
+        "gemini-1.5-pro" => Some(1.25),
+        _ => None,
+    }
+}
+
+/// Sends a request, turning non-success HTTP statuses into `ProviderError::RequestFailed`
+pub(crate) async fn send(provider: &str, request: reqwest::RequestBuilder) -> Result<reqwest::Response, Box<dyn Error>> {
+    if log::log_enabled!(log::Level::Debug) {
+        // Only the body: headers carry the API key, as does Gemini's `key` query parameter
+        if let Some(built) = request.try_clone().and_then(|request| request.build().ok()) {
+            let body = built.body().and_then(|body| body.as_bytes()).map(String::from_utf8_lossy).unwrap_or_default();
+            log::debug!("{} request to {}: {}", provider, loggable_url(built.url()), body);
+        }
+    }
+    // The URL may hold the API key, and errors are printed and logged
+    let response = request.send().await.map_err(reqwest::Error::without_url)?;
+    let status = response.status();
+    log::debug!("{} response status: {}", provider, status);
+    rate_limit::record(provider, response.headers());
+    if !status.is_success() {
+        return Err(Box::new(ProviderError::RequestFailed {
+            provider: provider.to_string(),
+            status: status.as_u16(),
+            message: response.text().await.unwrap_or_default(),
+        }));
+    }
+    Ok(response)
+}
+
+/// `url` with the value of a `key` query parameter left out
+fn loggable_url(url: &reqwest::Url) -> String {
+    if !url.query_pairs().any(|(name, _)| name == "key") {
+        return url.to_string();
+    }
+    let query: Vec<String> = url
+        .query_pairs()
+        .map(|(name, value)| if name == "key" { format!("{}=[redacted]", name) } else { format!("{}={}", name, value) })
+        .collect();
+    let mut url = url.clone();
+    url.set_query(None);
+    format!("{}?{}", url, query.join("&"))
+}
+
+/// Sends a request and parses the JSON response body
+pub(crate) async fn send_json(provider: &str, request: reqwest::RequestBuilder) -> Result<Value, Box<dyn Error>> {
+    let response = send(provider, request).await?;
+    let json = response.json::<Value>().await?;
+    log::debug!("{} response: {}", provider, json);
+    Ok(json)
+}
+
+/// Incrementally generated text chunks
+pub type TextStream = LocalBoxStream<'static, Result<String, Box<dyn Error>>>;
+
+/// Ends `stream` with `ProviderError::TimedOut` if it hasn't finished within `timeout`.
+/// The rest of the response is dropped, which cancels the request.
+pub(crate) fn stream_with_timeout(stream: TextStream, timeout: std::time::Duration) -> TextStream {
+    let deadline = tokio::time::Instant::now() + timeout;
+    futures::stream::unfold(Some(stream), move |stream| async move {
+        let mut stream = stream?;
+        match tokio::time::timeout_at(deadline, stream.next()).await {
+            Ok(Some(chunk)) => Some((chunk, Some(stream))),
+            Ok(None) => None,
+            Err(_) => {
+                let error: Box<dyn Error> = Box::new(ProviderError::TimedOut { seconds: timeout.as_secs() });
+                Some((Err(error), None))
+            }
+        }
+    })
+    .boxed_local()
+}
+
+/// The text of a response, which is an error when the model answered with none
+pub(crate) fn response_text(provider: &str, text: Option<&str>) -> Result<String, Box<dyn Error>> {
+    match text.filter(|text| !text.trim().is_empty()) {
+        Some(text) => Ok(text.to_string()),
+        None => Err(Box::new(ProviderError::EmptyResponse { provider: provider.to_string() })),
+    }
+}
+
+/// Turns a server-sent events response into a stream of text chunks,
+/// using `extract` to pull the text out of each `data:` event. A response without any text
+/// ends with `ProviderError::EmptyResponse`, like the providers' `generate_text`.
+pub(crate) fn sse_text_stream(provider: &str, response: reqwest::Response, extract: fn(&Value) -> Option<String>) -> TextStream {
+    let state = (response.bytes_stream().boxed(), Vec::new(), VecDeque::new(), false, false, provider.to_string());
+
+    futures::stream::unfold(state, move |(mut bytes, mut buffer, mut pending, mut done, mut yielded, provider)| async move {
+        loop {
+            if let Some(item) = pending.pop_front() {
+                return Some((item, (bytes, buffer, pending, done, true, provider)));
+            }
+            if done {
+                if yielded {
+                    return None;
+                }
+                yielded = true;
+                let error: Box<dyn Error> = Box::new(ProviderError::EmptyResponse { provider: provider.clone() });
+                return Some((Err(error), (bytes, buffer, pending, done, yielded, provider)));
+            }
+
+            match bytes.next().await {
+                Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                Some(Err(e)) => {
+                    pending.push_back(Err(e.into()));
+                    done = true;
+                    continue;
+                }
+                None => done = true,
+            }
+
+            // Only complete lines are parsed so multi-byte characters never get split
+            while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim().strip_prefix("data:") else { continue };
+                let data = data.trim();
+                log::debug!("Stream event: {}", data);
+                if data == "[DONE]" {
+                    continue;
+                }
+
+                match serde_json::from_str::<Value>(data) {
+                    Ok(event) => {
+                        if let Some(text) = extract(&event).filter(|t| !t.is_empty()) {
+                            pending.push_back(Ok(text));
+                        }
+                    }
+                    Err(e) => pending.push_back(Err(e.into())),
+                }
+            }
+        }
+    })
+    .boxed_local()
 }
 
-/// Error type for provider selection
+/// Error type for provider selection and requests
 #[derive(Debug)]
 pub enum ProviderError {
     NoProvidersAvailable,
+    /// A provider was created without its API key in the environment or the config files
+    MissingApiKey { var: &'static str },
     InvalidSelection,
+    /// The provider answered with a non-success HTTP status
+    RequestFailed { provider: String, status: u16, message: String },
+    /// No complete answer within the request timeout; the request was cancelled
+    TimedOut { seconds: u64 },
+    /// The provider's quota only allows more requests after longer than noah waits
+    RateLimited { provider: String, seconds: u64 },
+    /// The request succeeded, but the answer had no text in it
+    EmptyResponse { provider: String },
 }
 
 impl std::fmt::Display for ProviderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::NoProvidersAvailable => write!(f, "No AI providers available"),
+            Self::NoProvidersAvailable => write!(
+                f,
+                "No AI provider is set up. Set OPENAI_API_KEY, ANTHROPIC_API_KEY, DEEPSEEK_API_KEY or GEMINI_API_KEY, \
+                 or configure a local model server under [local] in noah.toml, to use AI features"
+            ),
+            Self::MissingApiKey { var } => write!(f, "{} is not set", var),
             Self::InvalidSelection => write!(f, "Invalid provider selection"),
+            Self::RequestFailed { provider, status, message } => {
+                write!(f, "{} request failed ({}): {}", provider, status, message)
+            }
+            Self::TimedOut { seconds } => write!(f, "AI request timed out after {}s", seconds),
+            Self::RateLimited { provider, seconds } => {
+                write!(f, "{} rate limit reached; more requests are allowed in {}s", provider, seconds)
+            }
+            Self::EmptyResponse { provider } => write!(f, "{} answered without any text", provider),
         }
     }
 }
 
 impl Error for ProviderError {}
 
-/// Get all available providers based on environment variables
-pub fn get_available_providers() -> Vec<Box<dyn Provider>> {
-    use std::env;
+/// The local endpoint from the config file, offered alongside the cloud providers
+static LOCAL_ENDPOINT: Mutex<Option<LocalEndpoint>> = Mutex::new(None);
+
+/// Makes a local, OpenAI-compatible server available as the "Local" provider
+pub fn set_local_endpoint(endpoint: LocalEndpoint) {
+    if let Ok(mut current) = LOCAL_ENDPOINT.lock() {
+        *current = Some(endpoint);
+    }
+}
+
+/// API keys from the `[api_keys]` of the config files, by variable name
+static CONFIGURED_API_KEYS: LazyLock<Mutex<HashMap<String, String>>> = LazyLock::new(Default::default);
+
+/// Uses `key` for the provider reading `var` when the environment doesn't set that
+/// variable. Unlike exporting it, this is safe while other threads are running. The key is
+/// redacted from the log from then on, see `known_api_keys`.
+pub fn set_api_key(var: &str, key: &str) {
+    if let Ok(mut keys) = CONFIGURED_API_KEYS.lock() {
+        keys.insert(var.to_string(), key.to_string());
+    }
+}
 
+/// The API key in `var`, from the environment or else the config files. An empty key,
+/// e.g. `OPENAI_API_KEY=` left in `.env`, would only fail later as an auth error.
+pub fn api_key(var: &str) -> Option<String> {
+    std::env::var(var)
+        .ok()
+        .filter(|key| !key.trim().is_empty())
+        .or_else(|| CONFIGURED_API_KEYS.lock().ok()?.get(var).cloned())
+        .filter(|key| !key.trim().is_empty())
+}
+
+/// Every key `api_key` can return, longest first, for `logging` to keep out of the log
+pub(crate) fn known_api_keys() -> Vec<String> {
+    let mut keys: Vec<String> = API_KEY_VARS
+        .iter()
+        .map(|(_, var)| *var)
+        .chain(["LOCAL_API_KEY"])
+        .filter_map(|var| std::env::var(var).ok())
+        .collect();
+    if let Ok(configured) = CONFIGURED_API_KEYS.lock() {
+        keys.extend(configured.values().cloned());
+    }
+    keys.retain(|key| !key.trim().is_empty());
+    // A key that contains another is replaced whole
+    keys.sort_by_key(|key| std::cmp::Reverse(key.len()));
+    keys
+}
+
+/// The hosted providers by name, with the environment variable holding their API key
+pub const API_KEY_VARS: [(&str, &str); 4] = [
+    ("OpenAI", "OPENAI_API_KEY"),
+    ("Claude", "ANTHROPIC_API_KEY"),
+    ("DeepSeek", "DEEPSEEK_API_KEY"),
+    ("Gemini", "GEMINI_API_KEY"),
+];
+
+/// The providers whose API key is set, plus the local server if one is configured.
+/// Empty when nothing is set up; see `ProviderError::NoProvidersAvailable`.
+pub fn get_available_providers() -> Vec<Box<dyn Provider>> {
     let mut providers = Vec::new();
     
-    if env::var("OPENAI_API_KEY").is_ok() {
-        providers.push(Box::new(OpenAIProvider::new()) as Box<dyn Provider>);
+    if let Ok(provider) = OpenAIProvider::new() {
+        providers.push(Box::new(provider) as Box<dyn Provider>);
     }
     
-    if env::var("ANTHROPIC_API_KEY").is_ok() {
-        providers.push(Box::new(ClaudeProvider::new()) as Box<dyn Provider>);
+    if let Ok(provider) = ClaudeProvider::new() {
+        providers.push(Box::new(provider) as Box<dyn Provider>);
     }
     
-    if env::var("DEEPSEEK_API_KEY").is_ok() {
-        providers.push(Box::new(DeepSeekProvider::new()) as Box<dyn Provider>);
+    if let Ok(provider) = DeepSeekProvider::new() {
+        providers.push(Box::new(provider) as Box<dyn Provider>);
     }
     
-    if env::var("GEMINI_API_KEY").is_ok() {
-        providers.push(Box::new(GeminiProvider::new()) as Box<dyn Provider>);
+    if let Ok(provider) = GeminiProvider::new() {
+        providers.push(Box::new(provider) as Box<dyn Provider>);
     }
 
-    if providers.is_empty() {
-        eprintln!("No AI providers found. Please set at least one API key:");
-        eprintln!("  OPENAI_API_KEY for OpenAI");
-        eprintln!("  ANTHROPIC_API_KEY for Claude");
-        eprintln!("  DEEPSEEK_API_KEY for DeepSeek");
-        eprintln!("  GEMINI_API_KEY for Google");
-        std::process::exit(1);
+    if let Some(endpoint) = LOCAL_ENDPOINT.lock().ok().and_then(|endpoint| endpoint.clone()) {
+        providers.push(Box::new(LocalProvider::new(endpoint)) as Box<dyn Provider>);
     }
-    
+
     providers
 }
 
+/// Stands in when no provider is set up, so that what works without AI still can.
+/// Every request fails with `ProviderError::NoProvidersAvailable`.
+#[derive(Debug)]
+pub struct UnavailableProvider;
+
+#[async_trait]
+impl Provider for UnavailableProvider {
+    fn name(&self) -> &str {
+        "None"
+    }
+
+    fn available_models(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    async fn generate_text(&self, _model: &str, _system_prompt: &str, _user_input: &str, _sampling: Sampling) -> Result<String, Box<dyn Error>> {
+        Err(Box::new(ProviderError::NoProvidersAvailable))
+    }
+}
+
 /// Select a provider from the available ones
 pub fn select_provider(providers: &[Box<dyn Provider>]) -> Result<usize, Box<dyn Error>> {
     if providers.is_empty() {
@@ -95,4 +402,42 @@ pub fn select_provider(providers: &[Box<dyn Provider>]) -> Result<usize, Box<dyn
         .items(&provider_names)
         .default(0)
         .interact()?)
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_without_text_are_errors() {
+        assert_eq!(response_text("Test", Some("feat: add login")).unwrap(), "feat: add login");
+        for text in [None, Some(""), Some(" \n")] {
+            let error = response_text("Test", text).unwrap_err();
+            assert!(matches!(error.downcast_ref::<ProviderError>(), Some(ProviderError::EmptyResponse { .. })));
+        }
+    }
+
+    #[test]
+    fn providers_need_their_api_key() {
+        // Nothing to check where the key is really set
+        if api_key("DEEPSEEK_API_KEY").is_some() {
+            return;
+        }
+        let error = DeepSeekProvider::new().unwrap_err();
+        assert_eq!(error.to_string(), "DEEPSEEK_API_KEY is not set");
+        assert!(get_available_providers().iter().all(|provider| provider.name() != "DeepSeek"));
+    }
+
+    #[test]
+    fn configured_keys_are_known() {
+        set_api_key("NOAH_TEST_CONFIGURED_KEY", "sk-configured-secret");
+        assert!(known_api_keys().contains(&"sk-configured-secret".to_string()));
+    }
+
+    #[test]
+    fn key_query_parameters_are_left_out_of_logged_urls() {
+        let url = reqwest::Url::parse("https://example.com/v1/models/gemini:generateContent?key=secret&alt=sse").unwrap();
+        assert_eq!(loggable_url(&url), "https://example.com/v1/models/gemini:generateContent?key=[redacted]&alt=sse");
+        let url = reqwest::Url::parse("https://example.com/v1/chat?monkey=1").unwrap();
+        assert_eq!(loggable_url(&url), "https://example.com/v1/chat?monkey=1");
+    }
+}
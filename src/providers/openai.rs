@@ -1,9 +1,9 @@
 use async_trait::async_trait;
 use std::error::Error;
-use reqwest::Client;
-use serde_json::{json, Value};
+use reqwest::{Client, RequestBuilder};
+use serde_json::json;
 
-use super::Provider;
+use super::{response_text, send, send_json, sse_text_stream, Provider, ProviderError, Sampling, TextStream};
 
 #[derive(Debug)]
 pub struct OpenAIProvider {
@@ -12,11 +12,38 @@ pub struct OpenAIProvider {
 }
 
 impl OpenAIProvider {
-    pub fn new() -> Self {
-        Self {
+    /// Fails with `ProviderError::MissingApiKey` unless `OPENAI_API_KEY` is set
+    pub fn new() -> Result<Self, ProviderError> {
+        Ok(Self {
             client: Client::new(),
-            api_key: std::env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY must be set"),
+            api_key: super::api_key("OPENAI_API_KEY").ok_or(ProviderError::MissingApiKey { var: "OPENAI_API_KEY" })?,
+        })
+    }
+
+    fn request(&self, model: &str, system_prompt: &str, user_input: &str, sampling: Sampling, stream: bool) -> RequestBuilder {
+        let mut body = json!({
+            "model": model,
+            "messages": [
+                {
+                    "role": "system",
+                    "content": system_prompt
+                },
+                {
+                    "role": "user",
+                    "content": user_input
+                }
+            ],
+            "temperature": sampling.temperature,
+            "stream": stream
+        });
+        if let Some(max_tokens) = sampling.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
         }
+
+        self.client
+            .post("https://api.openai.com/v1/chat/completions")
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&body)
     }
 }
 
@@ -26,37 +53,30 @@ impl Provider for OpenAIProvider {
         "OpenAI"
     }
 
+    fn available_models(&self) -> Vec<&str> {
+        vec!["gpt-4-turbo-preview", "gpt-4o", "gpt-4o-mini"]
+    }
+
     async fn generate_text(
         &self,
+        model: &str,
         system_prompt: &str,
         user_input: &str,
-        temperature: f32,
+        sampling: Sampling,
     ) -> Result<String, Box<dyn Error>> {
-        let response = self.client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&json!({
-                "model": "gpt-4-turbo-preview",
-                "messages": [
-                    {
-                        "role": "system",
-                        "content": system_prompt
-                    },
-                    {
-                        "role": "user",
-                        "content": user_input
-                    }
-                ],
-                "temperature": temperature
-            }))
-            .send()
-            .await?
-            .json::<Value>()
-            .await?;
-
-        Ok(response["choices"][0]["message"]["content"]
-            .as_str()
-            .unwrap_or("Failed to generate text")
-            .to_string())
+        let response = send_json(self.name(), self.request(model, system_prompt, user_input, sampling, false)).await?;
+
+        response_text(self.name(), response["choices"][0]["message"]["content"].as_str())
+    }
+
+    async fn generate_text_stream(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        user_input: &str,
+        sampling: Sampling,
+    ) -> Result<TextStream, Box<dyn Error>> {
+        let response = send(self.name(), self.request(model, system_prompt, user_input, sampling, true)).await?;
+        Ok(sse_text_stream(self.name(), response, |event| event["choices"][0]["delta"]["content"].as_str().map(str::to_string)))
     }
-} 
\ No newline at end of file
+}
\ No newline at end of file
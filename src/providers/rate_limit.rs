@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::header::HeaderMap;
+
+use super::ProviderError;
+use crate::ui;
+
+/// Longest pause before a request; a quota that resets later than this fails the request instead
+const MAX_WAIT: Duration = Duration::from_secs(120);
+
+/// How often requests held back for a probe (see `Quota::probing`) check whether it's done
+const PROBE_POLL: Duration = Duration::from_millis(100);
+
+/// Headers with the number of requests left, as sent by OpenAI (and compatible servers) or Anthropic
+const REMAINING_HEADERS: [&str; 3] = ["x-ratelimit-remaining-requests", "anthropic-ratelimit-requests-remaining", "x-ratelimit-remaining"];
+/// Headers with when the request quota is refilled
+const RESET_HEADERS: [&str; 3] = ["x-ratelimit-reset-requests", "anthropic-ratelimit-requests-reset", "x-ratelimit-reset"];
+
+/// What a provider's responses said about its request quota
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RateLimitStatus {
+    /// Requests left until the quota is refilled, less those sent since the last response
+    pub remaining: Option<u64>,
+    /// How long until requests may be sent again, if they can't be right now
+    pub wait: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+struct Quota {
+    remaining: Option<u64>,
+    reset_at: Option<Instant>,
+    /// From `Retry-After`, sent with 429 responses
+    retry_at: Option<Instant>,
+    /// A request is out to learn the refilled quota after it ran out; the others wait for its answer
+    probing: bool,
+}
+
+impl Quota {
+    fn wait(&self, now: Instant) -> Option<Duration> {
+        let retry_wait = self.retry_at.map(|at| at.saturating_duration_since(now));
+        let reset_wait = match self.remaining {
+            Some(0) => self.reset_at.map(|at| at.saturating_duration_since(now)),
+            _ => None,
+        };
+        retry_wait.into_iter().chain(reset_wait).max().filter(|wait| !wait.is_zero())
+    }
+}
+
+/// Quotas by provider name
+static QUOTAS: LazyLock<Mutex<HashMap<String, Quota>>> = LazyLock::new(Default::default);
+
+/// The request quota of `provider` as of its latest response
+pub fn status(provider: &str) -> RateLimitStatus {
+    let quotas = QUOTAS.lock().unwrap_or_else(|e| e.into_inner());
+    match quotas.get(provider) {
+        Some(quota) => RateLimitStatus { remaining: quota.remaining, wait: quota.wait(Instant::now()) },
+        None => RateLimitStatus::default(),
+    }
+}
+
+/// Remembers the rate limit headers of a response from `provider`
+pub(crate) fn record(provider: &str, headers: &HeaderMap) {
+    let header = |names: &[&str]| names.iter().find_map(|name| headers.get(*name)?.to_str().ok().map(str::trim));
+    let remaining = header(&REMAINING_HEADERS).and_then(|value| value.parse::<u64>().ok());
+    let reset = header(&RESET_HEADERS).and_then(parse_delay);
+    let retry_after = headers.get(reqwest::header::RETRY_AFTER).and_then(|value| value.to_str().ok()).and_then(parse_delay);
+    if remaining.is_none() && reset.is_none() && retry_after.is_none() {
+        return;
+    }
+
+    let now = Instant::now();
+    let mut quotas = QUOTAS.lock().unwrap_or_else(|e| e.into_inner());
+    let quota = quotas.entry(provider.to_string()).or_default();
+    if remaining.is_some() {
+        quota.remaining = remaining;
+    }
+    if let Some(reset) = reset {
+        quota.reset_at = Some(now + reset);
+    }
+    if let Some(retry_after) = retry_after {
+        quota.retry_at = Some(now + retry_after);
+    }
+    log::debug!("{} rate limit: {:?} requests remaining, reset in {:?}, retry after {:?}", provider, remaining, reset, retry_after);
+}
+
+/// Permission to send one request, from `acquire`. Dropped once the request is done.
+pub(crate) struct Permit {
+    /// The provider whose probe this is, see `Quota::probing`
+    probe: Option<String>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(provider) = self.probe.take() {
+            if let Some(quota) = QUOTAS.lock().unwrap_or_else(|e| e.into_inner()).get_mut(&provider) {
+                quota.probing = false;
+            }
+        }
+    }
+}
+
+/// Waits until `provider`'s quota allows another request, then counts the request against it,
+/// so that requests sent in parallel hold back once the quota runs low. The spinner says how
+/// long the wait is.
+pub(crate) async fn acquire(provider: &str) -> Result<Permit, Box<dyn Error>> {
+    loop {
+        let wait = {
+            let now = Instant::now();
+            let mut quotas = QUOTAS.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(quota) = quotas.get_mut(provider) else {
+                return Ok(Permit { probe: None });
+            };
+            match quota.wait(now) {
+                Some(wait) => wait,
+                // Refilled by now, or never said when: one request finds out
+                None if quota.remaining == Some(0) && quota.probing => PROBE_POLL,
+                None if quota.remaining == Some(0) => {
+                    quota.probing = true;
+                    return Ok(Permit { probe: Some(provider.to_string()) });
+                }
+                None => {
+                    quota.remaining = quota.remaining.map(|remaining| remaining - 1);
+                    return Ok(Permit { probe: None });
+                }
+            }
+        };
+        if wait > MAX_WAIT {
+            return Err(Box::new(ProviderError::RateLimited { provider: provider.to_string(), seconds: wait.as_secs() }));
+        }
+        if wait > PROBE_POLL {
+            ui::set_spinner_message(&format!("Rate limited, waiting {}s", wait.as_secs_f64().ceil()));
+        }
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// A delay as rate limit headers give it: seconds (`"20"`), a duration like OpenAI's
+/// `"1m30s"` or `"250ms"`, a Unix timestamp, or a date (RFC 3339 or HTTP date)
+fn parse_delay(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    // An empty duration would otherwise add up to zero
+    if value.is_empty() {
+        return None;
+    }
+    let until = |at: chrono::DateTime<chrono::FixedOffset>| (at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok().or(Some(Duration::ZERO));
+    if let Ok(seconds) = value.parse::<f64>() {
+        // Unix timestamps are far beyond any sensible delay
+        return if seconds > 1e9 {
+            let at = chrono::DateTime::from_timestamp(seconds as i64, 0)?;
+            until(at.fixed_offset())
+        } else {
+            Duration::try_from_secs_f64(seconds).ok()
+        };
+    }
+    if let Ok(at) = chrono::DateTime::parse_from_rfc3339(value) {
+        return until(at);
+    }
+    if let Ok(at) = chrono::DateTime::parse_from_rfc2822(value) {
+        return until(at);
+    }
+    parse_go_duration(value)
+}
+
+/// Durations like `"6m0s"`, `"1.5s"` or `"20ms"`
+fn parse_go_duration(value: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let number_len = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let number: f64 = rest[..number_len].parse().ok()?;
+        rest = &rest[number_len..];
+        let unit_len = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        total += number * match &rest[..unit_len] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            _ => return None,
+        };
+        rest = &rest[unit_len..];
+    }
+    Duration::try_from_secs_f64(total).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    /// Whether `delay` is about `seconds` away, allowing for the time the test takes
+    fn about(delay: Option<Duration>, seconds: u64) -> bool {
+        delay.is_some_and(|delay| delay <= Duration::from_secs(seconds) && delay >= Duration::from_secs(seconds - 5))
+    }
+
+    #[test]
+    fn plain_numbers_are_seconds() {
+        assert_eq!(parse_delay("20"), Some(Duration::from_secs(20)));
+        assert_eq!(parse_delay("0.5"), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn durations_are_added_up_from_their_units() {
+        assert_eq!(parse_delay("1m30s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_delay("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_go_duration("1h0m1.5s"), Some(Duration::from_millis(3_601_500)));
+        assert_eq!(parse_go_duration("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_go_duration("5x"), None);
+        assert_eq!(parse_go_duration("s"), None);
+    }
+
+    #[test]
+    fn dates_and_timestamps_are_delays_until_then() {
+        let at = chrono::Utc::now() + chrono::Duration::seconds(60);
+        assert!(about(parse_delay(&at.to_rfc3339()), 60));
+        assert!(about(parse_delay(&at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()), 60));
+        assert!(about(parse_delay(&at.timestamp().to_string()), 60));
+    }
+
+    #[test]
+    fn dates_in_the_past_are_no_delay() {
+        assert_eq!(parse_delay("2015-10-21T07:28:00Z"), Some(Duration::ZERO));
+        assert_eq!(parse_delay("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn anything_else_is_not_a_delay() {
+        assert_eq!(parse_delay("soon"), None);
+        assert_eq!(parse_delay("-5"), None);
+        assert_eq!(parse_delay(""), None);
+        assert_eq!(parse_delay("  "), None);
+    }
+
+    #[test]
+    fn empty_headers_leave_the_quota_alone() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining-requests", HeaderValue::from_static("0"));
+        headers.insert("x-ratelimit-reset-requests", HeaderValue::from_static(""));
+        headers.insert(reqwest::header::RETRY_AFTER, HeaderValue::from_static(" "));
+        record("rate-limit-test-empty", &headers);
+        assert_eq!(super::status("rate-limit-test-empty"), RateLimitStatus { remaining: Some(0), wait: None });
+        let quotas = QUOTAS.lock().unwrap();
+        let quota = &quotas["rate-limit-test-empty"];
+        assert_eq!((quota.reset_at, quota.retry_at), (None, None));
+    }
+
+    #[test]
+    fn a_spent_quota_waits_until_it_is_reset() {
+        let now = Instant::now();
+        let reset_at = Some(now + Duration::from_secs(30));
+        let spent = Quota { remaining: Some(0), reset_at, ..Default::default() };
+        assert_eq!(spent.wait(now), Some(Duration::from_secs(30)));
+        assert_eq!(spent.wait(now + Duration::from_secs(31)), None);
+
+        let left = Quota { remaining: Some(3), reset_at, ..Default::default() };
+        assert_eq!(left.wait(now), None);
+    }
+
+    #[test]
+    fn retry_after_waits_even_with_requests_left() {
+        let now = Instant::now();
+        let quota = Quota {
+            remaining: Some(0),
+            reset_at: Some(now + Duration::from_secs(10)),
+            retry_at: Some(now + Duration::from_secs(20)),
+            ..Default::default()
+        };
+        assert_eq!(quota.wait(now), Some(Duration::from_secs(20)));
+        let quota = Quota { remaining: Some(5), ..quota };
+        assert_eq!(quota.wait(now), Some(Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn headers_are_recorded_per_provider() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining-requests", HeaderValue::from_static("0"));
+        headers.insert("x-ratelimit-reset-requests", HeaderValue::from_static("1m0s"));
+        record("rate-limit-test-openai", &headers);
+        let status = status("rate-limit-test-openai");
+        assert_eq!(status.remaining, Some(0));
+        assert!(about(status.wait, 60));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("anthropic-ratelimit-requests-remaining", HeaderValue::from_static(" 42 "));
+        record("rate-limit-test-claude", &headers);
+        assert_eq!(super::status("rate-limit-test-claude"), RateLimitStatus { remaining: Some(42), wait: None });
+
+        record("rate-limit-test-none", &HeaderMap::new());
+        assert_eq!(super::status("rate-limit-test-none"), RateLimitStatus::default());
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::providers::TextStream;
+use crate::NoahError;
+
+/// Stored in the git directory, next to the contributor cache and the message history
+const CACHE_DIR: &str = ".noah-responses";
+
+/// A model's answer and when it was given, in seconds since the epoch
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    created: u64,
+    response: String,
+}
+
+/// Answers of earlier AI requests, one file per request, so that asking the same thing
+/// again (e.g. explaining a commit a second time) doesn't wait for the model
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    /// The cache kept in `git_dir`, whose answers are used for `ttl` after they were given
+    pub fn open(git_dir: &Path, ttl: Duration) -> Self {
+        Self { dir: git_dir.join(CACHE_DIR), ttl }
+    }
+
+    /// The key for a request made of `parts`, e.g. model, prompt and settings. Parts are
+    /// separated so that `["ab", "c"]` and `["a", "bc"]` get different keys.
+    pub fn key(parts: &[&str]) -> String {
+        let joined = parts.join("\0");
+        // Only fails for object types other than blobs
+        git2::Oid::hash_object(git2::ObjectType::Blob, joined.as_bytes())
+            .map(|oid| oid.to_string())
+            .unwrap_or_default()
+    }
+
+    /// The answer stored for `key`, unless there is none or it is older than the TTL.
+    /// Expired and unreadable entries are removed.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let path = self.path(key);
+        let json = fs::read_to_string(&path).ok()?;
+        match serde_json::from_str::<CachedResponse>(&json) {
+            Ok(entry) if now().saturating_sub(entry.created) < self.ttl.as_secs() => Some(entry.response),
+            _ => {
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    /// Stores `response` as the answer for `key`. An empty answer isn't stored, so that
+    /// asking again gets a real one.
+    pub fn put(&self, key: &str, response: &str) -> Result<(), NoahError> {
+        if response.trim().is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let entry = CachedResponse { created: now(), response: response.to_string() };
+        fs::write(self.path(key), serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+
+    /// Passes `stream` through, storing the whole text for `key` once it has ended without
+    /// an error
+    pub fn record_stream(self, key: String, stream: TextStream) -> TextStream {
+        futures::stream::unfold((stream, String::new(), Some((self, key))), |(mut stream, mut text, mut store)| async move {
+            match stream.next().await {
+                Some(Ok(chunk)) => {
+                    text.push_str(&chunk);
+                    Some((Ok(chunk), (stream, text, store)))
+                }
+                Some(Err(e)) => Some((Err(e), (stream, text, None))),
+                None => {
+                    if let Some((cache, key)) = store.take() {
+                        if let Err(e) = cache.put(&key, &text) {
+                            log::info!("Couldn't cache the response: {}", e);
+                        }
+                    }
+                    None
+                }
+            }
+        })
+        .boxed_local()
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+/// Removes every cached answer in `git_dir`, returning how many there were
+pub fn clear(git_dir: &Path) -> Result<usize, NoahError> {
+    let dir = git_dir.join(CACHE_DIR);
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let count = fs::read_dir(&dir)?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .count();
+    fs::remove_dir_all(&dir)?;
+    Ok(count)
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::ProviderError;
+
+    fn stream_of(items: Vec<Result<String, Box<dyn std::error::Error>>>) -> TextStream {
+        futures::stream::iter(items).boxed_local()
+    }
+
+    #[test]
+    fn answers_are_stored_unless_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ResponseCache::open(dir.path(), Duration::from_secs(60));
+        cache.put("full", "feat: add login").unwrap();
+        cache.put("empty", " \n").unwrap();
+        assert_eq!(cache.get("full").as_deref(), Some("feat: add login"));
+        assert_eq!(cache.get("empty"), None);
+    }
+
+    #[tokio::test]
+    async fn only_streams_that_end_with_text_are_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ResponseCache::open(dir.path(), Duration::from_secs(60));
+        let chunks = vec![Ok("feat: ".to_string()), Ok("add login".to_string())];
+        let _: Vec<_> = cache.clone().record_stream("whole".into(), stream_of(chunks)).collect().await;
+        assert_eq!(cache.get("whole").as_deref(), Some("feat: add login"));
+
+        let failed = vec![Ok("feat: ".to_string()), Err(Box::new(ProviderError::EmptyResponse { provider: "Test".into() }) as _)];
+        let _: Vec<_> = cache.clone().record_stream("failed".into(), stream_of(failed)).collect().await;
+        assert_eq!(cache.get("failed"), None);
+
+        let _: Vec<_> = cache.clone().record_stream("empty".into(), stream_of(Vec::new())).collect().await;
+        assert_eq!(cache.get("empty"), None);
+    }
+}
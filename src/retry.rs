@@ -0,0 +1,154 @@
+use std::error::Error;
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::providers::ProviderError;
+use crate::ui;
+
+/// How often and how patiently failed AI requests are retried
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for every further retry
+    pub base_delay: Duration,
+    /// Upper bound of the random delay added to each backoff
+    pub jitter: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            jitter: Duration::from_millis(250),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Backoff before retry number `retry` (starting at 1)
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.base_delay.saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)));
+        let jitter_ms = self.jitter.as_millis() as u64;
+        let jitter = if jitter_ms == 0 {
+            0
+        } else {
+            // Good enough randomness to keep parallel requests from retrying in lockstep
+            let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+            u64::from(nanos) % jitter_ms
+        };
+        backoff + Duration::from_millis(jitter)
+    }
+}
+
+/// Timeouts, connection failures, rate limiting and server errors are worth retrying;
+/// anything else (bad credentials, invalid requests) will fail the same way again
+pub fn is_transient(error: &(dyn Error + 'static)) -> bool {
+    if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+        return error.is_timeout() || error.is_connect();
+    }
+    if let Some(ProviderError::RequestFailed { status, .. }) = error.downcast_ref::<ProviderError>() {
+        return *status == 429 || *status >= 500;
+    }
+    false
+}
+
+/// Fails with `ProviderError::TimedOut` if `request` doesn't finish within `timeout`.
+/// The request is dropped at that point, which cancels it. Not retried by `with_retry`,
+/// since a request that hung once is likely to hang again.
+pub async fn with_timeout<T>(timeout: Duration, request: impl Future<Output = Result<T, Box<dyn Error>>>) -> Result<T, Box<dyn Error>> {
+    match tokio::time::timeout(timeout, request).await {
+        Ok(result) => result,
+        Err(_) => Err(Box::new(ProviderError::TimedOut { seconds: timeout.as_secs() })),
+    }
+}
+
+/// Runs `operation` until it succeeds, fails with a non-transient error, or runs out of attempts.
+/// The error of the last attempt is returned unchanged.
+pub async fn with_retry<T, F, Fut>(policy: &RetryPolicy, mut operation: F) -> Result<T, Box<dyn Error>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Box<dyn Error>>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(e) if attempt < policy.max_attempts && is_transient(e.as_ref()) => {
+                let delay = policy.delay(attempt);
+                attempt += 1;
+                ui::set_spinner_message(&format!("Retrying ({}/{})", attempt, policy.max_attempts));
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn failure(status: u16) -> Box<dyn Error> {
+        Box::new(ProviderError::RequestFailed { provider: "Test".to_string(), status, message: String::new() })
+    }
+
+    fn instant(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy { max_attempts, base_delay: Duration::ZERO, jitter: Duration::ZERO }
+    }
+
+    #[test]
+    fn rate_limits_and_server_errors_are_transient() {
+        assert!(is_transient(failure(429).as_ref()));
+        assert!(is_transient(failure(503).as_ref()));
+        assert!(!is_transient(failure(401).as_ref()));
+        assert!(!is_transient(&ProviderError::NoProvidersAvailable));
+        let message: Box<dyn Error> = "something else went wrong".into();
+        assert!(!is_transient(message.as_ref()));
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried_up_to_max_attempts() {
+        let attempts = Cell::new(0);
+        let result: Result<(), _> = with_retry(&instant(3), || {
+            attempts.set(attempts.get() + 1);
+            async { Err(failure(503)) }
+        }).await;
+        assert_eq!(attempts.get(), 3);
+        assert!(result.unwrap_err().to_string().contains("(503)"));
+
+        attempts.set(0);
+        let result = with_retry(&instant(3), || {
+            attempts.set(attempts.get() + 1);
+            let attempt = attempts.get();
+            async move { if attempt < 2 { Err(failure(429)) } else { Ok(attempt) } }
+        }).await;
+        assert_eq!(result.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn other_failures_are_returned_at_once() {
+        let attempts = Cell::new(0);
+        let result: Result<(), _> = with_retry(&instant(5), || {
+            attempts.set(attempts.get() + 1);
+            async { Err(failure(401)) }
+        }).await;
+        assert_eq!(attempts.get(), 1);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn backoff_doubles_within_the_jitter() {
+        let policy = RetryPolicy { max_attempts: 5, base_delay: Duration::from_millis(100), jitter: Duration::from_millis(50) };
+        for (retry, backoff) in [(1, 100), (2, 200), (3, 400), (4, 800)] {
+            let delay = policy.delay(retry);
+            let backoff = Duration::from_millis(backoff);
+            assert!(delay >= backoff && delay < backoff + policy.jitter, "retry {}: {:?}", retry, delay);
+        }
+        assert_eq!(instant(3).delay(1), Duration::ZERO);
+        // Saturates instead of overflowing
+        let policy = RetryPolicy { jitter: Duration::ZERO, ..policy };
+        assert_eq!(policy.delay(u32::MAX), Duration::from_millis(100) * u32::MAX);
+    }
+}
@@ -0,0 +1,100 @@
+use std::error::Error;
+use std::time::Duration;
+
+use git2::Repository;
+
+use crate::modes::Mode;
+use crate::providers::{self, Provider, Sampling};
+use crate::{config_file, git, ui};
+
+/// How long the test request may take before setup reports the provider as unreachable
+const CONNECTION_TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The modes the tour introduces; `?` in the menu describes all of them
+const TOUR_MODES: [Mode; 3] = [Mode::CommitMessage, Mode::InteractiveStage, Mode::FileAnalysis];
+
+/// Walks a new user through setting up a provider and writes the user config file.
+/// Nothing is written if they stop halfway, so setup is offered again next time.
+pub async fn run_wizard(repo: &Repository) -> Result<(), Box<dyn Error>> {
+    ui::print_section("👋 Welcome to noah");
+    let location = repo.workdir().unwrap_or(repo.path()).display().to_string();
+    match git::current_branch(repo) {
+        Some(branch) => println!("✅ Found a git repository at {} on {}", location, branch),
+        None => println!("✅ Found a git repository at {}", location),
+    }
+    println!("No config file yet, so let's set up an AI provider. Pass --no-setup to skip this.\n");
+
+    let mut entered_key = None;
+    let mut available = providers::get_available_providers();
+    if available.is_empty() {
+        println!("🔑 No API key was found in the environment or .env.");
+        let mut names: Vec<&str> = providers::API_KEY_VARS.iter().map(|(name, _)| *name).collect();
+        names.push("Skip setup");
+        let selected = ui::show_selection_menu("Which provider do you have an API key for?", &names, 0)?;
+        let Some(&(name, var)) = providers::API_KEY_VARS.get(selected) else {
+            return Ok(());
+        };
+        let key = ui::get_password(&format!("{} API key", name))?;
+        if key.trim().is_empty() {
+            println!("No key entered, skipping setup.");
+            return Ok(());
+        }
+        // Registered before any request is logged, so the test request below can't leak it
+        providers::set_api_key(var, key.trim());
+        entered_key = Some((var, key.trim().to_string()));
+        available = providers::get_available_providers();
+    } else {
+        let names: Vec<&str> = available.iter().map(|p| p.name()).collect();
+        println!("🔑 Found API keys for {}", names.join(", "));
+    }
+    let selected = match available.len() {
+        1 => 0,
+        _ => providers::select_provider(&available)?,
+    };
+    let provider = available.swap_remove(selected);
+
+    let models = provider.available_models();
+    let model = match models.len() {
+        0 => return Err(format!("{} offers no models", provider.name()).into()),
+        1 => models[0].to_string(),
+        _ => models[ui::show_selection_menu("Which model should noah use?", &models, 0)?].to_string(),
+    };
+
+    if let Err(e) = test_connection(provider.as_ref(), &model).await {
+        eprintln!("❌ {} didn't answer: {}", provider.name(), e);
+        if !ui::confirm("Save these settings anyway?")? {
+            return Ok(());
+        }
+    }
+
+    let api_key = entered_key.as_ref().map(|(var, key)| (*var, key.as_str()));
+    let path = config_file::save_setup(provider.name(), &model, api_key)?;
+    println!("📝 Saved {} with {} to {}", provider.name(), model, path.display());
+
+    print_tour();
+    Ok(())
+}
+
+/// Sends a tiny request, so a wrong key or an unreachable server shows up now
+async fn test_connection(provider: &dyn Provider, model: &str) -> Result<(), Box<dyn Error>> {
+    let spinner = ui::create_spinner(&format!("Checking the connection to {}", provider.name()))?;
+    let sampling = Sampling { temperature: 0.0, max_tokens: Some(5) };
+    let answer = tokio::time::timeout(CONNECTION_TEST_TIMEOUT, provider.generate_text(model, "Reply with OK.", "ping", sampling)).await;
+    spinner.finish_and_clear();
+    match answer {
+        Ok(Ok(_)) => {
+            println!("✅ {} is answering", provider.name());
+            Ok(())
+        }
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err(format!("no response within {} seconds", CONNECTION_TEST_TIMEOUT.as_secs()).into()),
+    }
+}
+
+fn print_tour() {
+    ui::print_subsection("🧭 A quick tour");
+    for mode in TOUR_MODES {
+        println!("{}\n  {}\n", mode.description(), mode.help());
+    }
+    println!("Press ? in the menu for the other modes.\n");
+}
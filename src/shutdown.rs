@@ -0,0 +1,130 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use dialoguer::console::Term;
+use git2::Repository;
+
+use crate::ui;
+
+/// Exit status for Ctrl-C, as shells report it
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// The index as it was before a change that shouldn't be left half done
+struct IndexBackup {
+    path: PathBuf,
+    /// `None` when there was no index file yet
+    content: Option<Vec<u8>>,
+}
+
+static PENDING_RESTORE: Mutex<Option<IndexBackup>> = Mutex::new(None);
+
+/// Ends the process on Ctrl-C, whatever it is doing: any running spinner is cleared, the
+/// cursor shown again and a protected index restored (see `protect_index`). Requests still
+/// in flight die with the process, and nothing is committed.
+///
+/// Runs on its own task, so it also fires while a menu blocks the main one.
+pub fn install_ctrl_c_handler() {
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            interrupted();
+        }
+    });
+}
+
+/// Exits the way Ctrl-C does, also for menus that read it as a key press before the
+/// signal handler sees it
+pub fn interrupted() -> ! {
+    finish(INTERRUPTED_EXIT_CODE, Some("\nInterrupted, nothing was committed."))
+}
+
+/// Leaves the terminal and repository tidy, then exits with `code`
+pub fn exit(code: i32) -> ! {
+    finish(code, None)
+}
+
+/// Run by `exit` once the terminal and index are tidy, see `on_exit`
+static EXIT_HOOK: Mutex<Option<Box<dyn Fn() + Send>>> = Mutex::new(None);
+
+/// Registers `hook` to run when the process ends through `exit`, e.g. after q or Esc in a
+/// menu, replacing any earlier one. Returning from `main` doesn't run it.
+pub fn on_exit(hook: impl Fn() + Send + 'static) {
+    if let Ok(mut current) = EXIT_HOOK.lock() {
+        *current = Some(Box::new(hook));
+    }
+}
+
+/// Held by whichever thread exits first, so a Ctrl-C seen by both the signal handler and a
+/// menu is reported once and can't end the process while the index is being restored
+static EXITING: Mutex<()> = Mutex::new(());
+
+fn finish(code: i32, message: Option<&str>) -> ! {
+    let _exiting = EXITING.lock();
+    ui::clear_spinner();
+    if let Some(message) = message {
+        eprintln!("{}", message);
+    }
+    let _ = Term::stdout().show_cursor();
+    let _ = Term::stderr().show_cursor();
+    restore_index();
+    if let Some(hook) = EXIT_HOOK.lock().ok().and_then(|mut hook| hook.take()) {
+        hook();
+    }
+    std::process::exit(code)
+}
+
+/// Puts back the index saved by `protect_index`, if it is still pending
+fn restore_index() {
+    if let Some(backup) = PENDING_RESTORE.lock().ok().and_then(|mut pending| pending.take()) {
+        let restored = match &backup.content {
+            Some(content) => std::fs::write(&backup.path, content),
+            None => std::fs::remove_file(&backup.path),
+        };
+        match restored {
+            Ok(()) => eprintln!("Staged changes were put back the way they were."),
+            Err(e) => eprintln!("Failed to restore the index at {}: {}", backup.path.display(), e),
+        }
+    }
+}
+
+/// Restores the index as it is now unless the returned guard is disarmed, whether the
+/// process exits first, e.g. when Ctrl-C arrives halfway through staging hunks, or the
+/// guard is dropped on an error
+pub fn protect_index(repo: &Repository) -> Result<IndexGuard, Box<dyn Error>> {
+    let path = repo.path().join("index");
+    let content = match std::fs::read(&path) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    if let Ok(mut pending) = PENDING_RESTORE.lock() {
+        *pending = Some(IndexBackup { path, content });
+    }
+    Ok(IndexGuard)
+}
+
+/// Keeps the index backup from `protect_index`, restoring it when dropped unless `disarm`
+/// was called
+pub struct IndexGuard;
+
+impl IndexGuard {
+    /// Keeps the index as it is now, once the change it protected is complete
+    pub fn disarm(self) {
+        if let Ok(mut pending) = PENDING_RESTORE.lock() {
+            *pending = None;
+        }
+    }
+
+    /// Runs `change` to the index without being interrupted halfway, which could leave a
+    /// stale `index.lock` behind: an exit waits for it to finish before restoring the index
+    pub fn apply<T>(&self, change: impl FnOnce() -> T) -> T {
+        let _pending = PENDING_RESTORE.lock();
+        change()
+    }
+}
+
+impl Drop for IndexGuard {
+    fn drop(&mut self) {
+        restore_index();
+    }
+}
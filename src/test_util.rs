@@ -0,0 +1,53 @@
+//! Scratch repositories for the unit tests
+
+use std::fs;
+use std::path::Path;
+
+use git2::{Repository, Signature};
+
+use crate::Config;
+
+/// A repository in a temporary directory, with an identity to commit as
+pub fn temp_repo() -> (tempfile::TempDir, Repository) {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    let mut config = repo.config().unwrap();
+    config.set_str("user.name", "Ada Lovelace").unwrap();
+    config.set_str("user.email", "ada@example.com").unwrap();
+    (dir, repo)
+}
+
+/// Writes `content` to `path` in the working tree, creating its directories
+pub fn write_file(repo: &Repository, path: &str, content: &str) {
+    let path = repo.workdir().unwrap().join(path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    fs::write(path, content).unwrap();
+}
+
+/// Writes `content` to `path`, stages it and commits it on HEAD as `author`
+pub fn commit_file(repo: &Repository, path: &str, content: &str, message: &str, author: &Signature) -> git2::Oid {
+    write_file(repo, path, content);
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new(path)).unwrap();
+    index.write().unwrap();
+    commit_index(repo, message, author)
+}
+
+/// Commits the index as it is on HEAD
+pub fn commit_index(repo: &Repository, message: &str, author: &Signature) -> git2::Oid {
+    let tree = repo.find_tree(repo.index().unwrap().write_tree().unwrap()).unwrap();
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    repo.commit(Some("HEAD"), author, author, message, &tree, &parents).unwrap()
+}
+
+pub fn ada() -> Signature<'static> {
+    Signature::now("Ada Lovelace", "ada@example.com").unwrap()
+}
+
+/// Settings for `repo` without a provider, so nothing is ever asked of a model
+pub fn test_config(repo: &Repository) -> Config {
+    Config::without_provider(Some(repo.workdir().unwrap().display().to_string()))
+}
@@ -1,73 +1,817 @@
 use std::error::Error;
-use dialoguer::{theme::ColorfulTheme, Select, Input};
+use std::sync::Mutex;
+use dialoguer::console::{style, Term};
+use dialoguer::theme::{ColorfulTheme, Theme};
+use dialoguer::{Confirm, FuzzySelect, Input, MultiSelect, Password};
 use indicatif::{ProgressBar, ProgressStyle};
+use serde::Deserialize;
 use termimad::{MadSkin, gray, StyledChar};
 
+use crate::git;
 use crate::modes::Mode;
 
 /// Renders markdown text in the terminal with proper styling
 pub fn print_markdown(text: &str) {
-    let mut skin = MadSkin::default();
-    // Configure markdown styling
-    skin.set_headers_fg(gray(255));  // Bright white for headers
-    skin.bold.set_fg(gray(200));     // Light gray for bold
-    skin.italic.set_fg(gray(180));   // Slightly darker for italic
-    skin.bullet = StyledChar::from_fg_char(gray(180), '•');
-    skin.quote_mark = StyledChar::from_fg_char(gray(180), '▐');
-    skin.code_block.set_fg(gray(71)); // Light green for code blocks
-    
     // Add a newline before and after for better spacing
     println!();
-    skin.print_text(text);
+    markdown_skin().print_text(text);
     println!();
 }
 
+/// Like `print_markdown`, but long output is opened in `$PAGER` (or `less`) instead of
+/// scrolling off screen. Output that isn't a terminal is never paged.
+pub fn print_markdown_paged(text: &str) {
+    use std::io::IsTerminal;
+
+    let skin = markdown_skin();
+    // Wraps at the terminal width
+    let rendered = skin.term_text(text);
+    let (_, height) = termimad::terminal_size();
+    // Leave room for the blank lines around the text and the prompt that follows
+    if std::io::stdout().is_terminal() && rendered.lines.len() + 3 > height as usize {
+        let rendered = format!("\n{}\n", rendered);
+        if page(&rendered).is_ok() {
+            return;
+        }
+        // No usable pager, so fall back to printing everything
+        print!("{}", rendered);
+        return;
+    }
+
+    println!();
+    print!("{}", rendered);
+    println!();
+}
+
+fn markdown_skin() -> MadSkin {
+    let theme = current_theme();
+    if theme == ColorTheme::Monochrome || !colors_enabled() {
+        return MadSkin::no_style();
+    }
+    let mut skin = MadSkin::default();
+    // Light grays read well on dark backgrounds, dark grays on light ones
+    let (header, bold, muted) = match theme {
+        ColorTheme::Light => (gray(0), gray(4), gray(8)),
+        _ => (gray(255), gray(200), gray(180)),
+    };
+    skin.set_headers_fg(header);
+    skin.bold.set_fg(bold);
+    skin.italic.set_fg(muted);
+    skin.bullet = StyledChar::from_fg_char(muted, '•');
+    skin.quote_mark = StyledChar::from_fg_char(muted, '▐');
+    skin.code_block.set_fg(gray(71)); // Light green for code blocks
+    skin
+}
+
+/// Pipes `text` through `$PAGER`, or `less`, and waits for the user to close it
+fn page(text: &str) -> Result<(), Box<dyn Error>> {
+    use std::io::Write;
+
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| "less".to_string());
+    let mut parts = pager.split_whitespace();
+    let program = parts.next().unwrap_or("less");
+    let mut command = std::process::Command::new(program);
+    command.args(parts).stdin(std::process::Stdio::piped());
+    // Like git: keep colors, and quit right away if it fits on one screen after all
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+
+    let mut child = command.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Quitting the pager early closes the pipe, which isn't an error
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
 /// Prints a section header with a title
 pub fn print_section(title: &str) {
-    println!("\n{}", title);
+    if is_quiet() {
+        println!("\n{}\n", plain_title(title));
+        return;
+    }
+    println!("\n{}", themed_title(title));
     println!("{}\n", "═".repeat(title.chars().count()));
 }
 
 /// Prints a subsection header with a title
 pub fn print_subsection(title: &str) {
-    println!("\n{}", title);
+    if is_quiet() {
+        println!("\n{}", plain_title(title));
+        return;
+    }
+    println!("\n{}", themed_title(title));
     println!("{}", "─".repeat(title.chars().count()));
 }
 
+/// Clears the screen and moves the cursor to the top, unless stdout isn't a terminal,
+/// where the escape codes would only end up in the output
+pub fn clear_screen() {
+    use std::io::IsTerminal;
+    if std::io::stdout().is_terminal() {
+        println!("\x1B[2J\x1B[1;1H");
+    }
+}
+
+/// Prints a line across the terminal between one result and the next
+pub fn print_separator() {
+    let (width, _) = termimad::terminal_size();
+    let line = if is_quiet() { "-" } else { "─" };
+    // Terminals that don't report their size get the full line
+    let width = match usize::from(width) {
+        0 => 80,
+        width => width.min(80),
+    };
+    println!("\n{}\n", line.repeat(width));
+}
+
+/// `title` in the theme's header color
+fn themed_title(title: &str) -> String {
+    match current_theme().header_color() {
+        Some(color) if colors_enabled() => format!("{}{}{}{}", BOLD, color, title, RESET),
+        _ => title.to_string(),
+    }
+}
+
+/// The title without its leading emoji
+fn plain_title(title: &str) -> &str {
+    title.trim_start_matches(|c: char| !c.is_alphanumeric())
+}
+
+static QUIET: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// In quiet mode spinners don't animate and section headers are plain lines, which keeps
+/// logs and CI output free of control characters
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, std::sync::atomic::Ordering::Relaxed);
+}
+
+fn is_quiet() -> bool {
+    QUIET.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+const GREEN: &str = "\x1B[32m";
+const RED: &str = "\x1B[31m";
+const CYAN: &str = "\x1B[36m";
+const BLUE: &str = "\x1B[34m";
+const BOLD: &str = "\x1B[1m";
+const RESET: &str = "\x1B[0m";
+
+/// Colors are used unless the theme is `Monochrome`, `NO_COLOR` is set or stdout isn't a terminal
+fn colors_enabled() -> bool {
+    use std::io::IsTerminal;
+    current_theme() != ColorTheme::Monochrome
+        && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        && std::io::stdout().is_terminal()
+}
+
+/// Colors of section headers, spinners and rendered markdown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorTheme {
+    /// For dark terminal backgrounds
+    #[default]
+    Dark,
+    /// For light terminal backgrounds
+    Light,
+    /// No colors at all, also in menus and diffs
+    Monochrome,
+}
+
+impl ColorTheme {
+    fn header_color(self) -> Option<&'static str> {
+        match self {
+            ColorTheme::Dark => Some(CYAN),
+            ColorTheme::Light => Some(BLUE),
+            ColorTheme::Monochrome => None,
+        }
+    }
+
+    /// Color of the spinner in an indicatif template
+    fn spinner_color(self) -> &'static str {
+        match self {
+            ColorTheme::Dark => ":.cyan",
+            ColorTheme::Light => ":.blue",
+            ColorTheme::Monochrome => "",
+        }
+    }
+}
+
+static THEME: Mutex<ColorTheme> = Mutex::new(ColorTheme::Dark);
+
+/// Sets the theme for everything printed from now on. `Monochrome` also turns off the
+/// colors of menus and progress bars.
+pub fn set_theme(theme: ColorTheme) {
+    if let Ok(mut current) = THEME.lock() {
+        *current = theme;
+    }
+    let colors = theme != ColorTheme::Monochrome;
+    dialoguer::console::set_colors_enabled(colors);
+    dialoguer::console::set_colors_enabled_stderr(colors);
+}
+
+fn current_theme() -> ColorTheme {
+    THEME.lock().map(|theme| *theme).unwrap_or_default()
+}
+
+/// Programs that put their stdin on the system clipboard, tried in order
+const CLIPBOARD_COMMANDS: [(&str, &[&str]); 5] = [
+    ("pbcopy", &[]),
+    ("clip", &[]),
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+];
+
+/// Puts `text` on the system clipboard with the platform's clipboard tool. Fails when
+/// none of them is installed or works here, e.g. over SSH or in CI.
+pub fn copy_to_clipboard(text: &str) -> Result<(), Box<dyn Error>> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    for (program, args) in CLIPBOARD_COMMANDS {
+        let Ok(mut child) = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        // Closing stdin tells the tool the text is complete
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(&clipboard_input(program, text));
+        }
+        if child.wait().is_ok_and(|status| status.success()) {
+            return Ok(());
+        }
+    }
+    Err("No clipboard is available".into())
+}
+
+/// What `program` reads from stdin for `text`. Windows' `clip` takes its input in the
+/// console code page unless it is UTF-16 with a byte order mark, which garbles non-ASCII
+/// text otherwise.
+fn clipboard_input(program: &str, text: &str) -> Vec<u8> {
+    if program != "clip" {
+        return text.as_bytes().to_vec();
+    }
+    std::iter::once(0xFEFF).chain(text.encode_utf16()).flat_map(u16::to_le_bytes).collect()
+}
+
+/// Prints a diff from `git::get_diff` with added lines in green, removed lines in red and
+/// hunk headers in cyan. Built with the `syntax-highlight` feature, code is highlighted too.
+pub fn print_diff(diff: &str) {
+    if !colors_enabled() {
+        println!("{}", diff);
+        return;
+    }
+
+    let mut highlighter = syntax::FileHighlighter::none();
+    let mut in_new_file = false;
+    for line in diff.lines() {
+        if let Some(path) = git::file_header_path(line) {
+            highlighter = syntax::FileHighlighter::for_path(path);
+            in_new_file = line.starts_with("New file: ");
+            println!("{}{}{}", BOLD, line, RESET);
+            continue;
+        }
+        if in_new_file {
+            // New files are listed as plain content, all of it added
+            match highlighter.highlight(line) {
+                Some(code) => println!("{}+{}{}", GREEN, RESET, code),
+                None => println!("{}{}{}", GREEN, line, RESET),
+            }
+            continue;
+        }
+
+        if line.starts_with("@@") {
+            println!("{}{}{}", CYAN, line, RESET);
+        } else if line.starts_with("+++ ") || line.starts_with("--- ") || line.starts_with("index ") {
+            println!("{}{}{}", BOLD, line, RESET);
+        } else if let Some(marker @ ('+' | '-' | ' ')) = line.chars().next() {
+            let color = match marker {
+                '+' => GREEN,
+                '-' => RED,
+                _ => "",
+            };
+            match highlighter.highlight(&line[1..]) {
+                Some(code) => println!("{}{}{}{}", color, marker, RESET, code),
+                None => println!("{}{}{}", color, line, RESET),
+            }
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+#[cfg(feature = "syntax-highlight")]
+mod syntax {
+    use std::sync::LazyLock;
+    use syntect::easy::HighlightLines;
+    use syntect::highlighting::ThemeSet;
+    use syntect::parsing::SyntaxSet;
+    use syntect::util::as_24_bit_terminal_escaped;
+
+    static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+    static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+    /// Highlights the lines of one file, keeping parser state from line to line
+    pub struct FileHighlighter(Option<HighlightLines<'static>>);
+
+    impl FileHighlighter {
+        pub fn none() -> Self {
+            Self(None)
+        }
+
+        pub fn for_path(path: &str) -> Self {
+            let syntax = std::path::Path::new(path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .and_then(|ext| SYNTAXES.find_syntax_by_extension(ext));
+            Self(syntax.map(|syntax| HighlightLines::new(syntax, &THEMES.themes["base16-ocean.dark"])))
+        }
+
+        pub fn highlight(&mut self, code: &str) -> Option<String> {
+            let lines = self.0.as_mut()?;
+            let line = format!("{}\n", code);
+            let ranges = lines.highlight_line(&line, &SYNTAXES).ok()?;
+            Some(format!("{}{}", as_24_bit_terminal_escaped(&ranges, false).trim_end_matches('\n'), super::RESET))
+        }
+    }
+}
+
+#[cfg(not(feature = "syntax-highlight"))]
+mod syntax {
+    /// Without the `syntax-highlight` feature code is left uncolored
+    pub struct FileHighlighter;
+
+    impl FileHighlighter {
+        pub fn none() -> Self {
+            Self
+        }
+
+        pub fn for_path(_path: &str) -> Self {
+            Self
+        }
+
+        pub fn highlight(&mut self, _code: &str) -> Option<String> {
+            None
+        }
+    }
+}
+
+/// Animation used by the progress spinner
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpinnerStyle {
+    #[default]
+    Dots,
+    Line,
+    Bounce,
+}
+
+impl SpinnerStyle {
+    /// Animation frames; the last one is shown once the spinner has finished
+    fn tick_chars(self) -> &'static str {
+        match self {
+            SpinnerStyle::Dots => "⠁⠂⠄⡀⢀⠠⠐⠈",
+            SpinnerStyle::Line => "-\\|/",
+            SpinnerStyle::Bounce => "⠁⠂⠄⡀⠄⠂⠁",
+        }
+    }
+}
+
+static SPINNER_STYLE: Mutex<SpinnerStyle> = Mutex::new(SpinnerStyle::Dots);
+
+/// Sets the animation used by spinners created from now on
+pub fn set_spinner_style(style: SpinnerStyle) {
+    if let Ok(mut current) = SPINNER_STYLE.lock() {
+        *current = style;
+    }
+}
+
+/// The most recently created spinner, so code without a handle to it can update its message
+static ACTIVE_SPINNER: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// A spinner showing `message`. In quiet mode it is hidden, but can be used (and finished)
+/// the same way.
 pub fn create_spinner(message: &str) -> Result<ProgressBar, Box<dyn Error>> {
+    if is_quiet() {
+        return Ok(ProgressBar::hidden());
+    }
+    let style = SPINNER_STYLE.lock().map(|style| *style).unwrap_or_default();
     let spinner = ProgressBar::new_spinner();
     spinner.set_style(
         ProgressStyle::default_spinner()
-            .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈")
-            .template("{spinner} {msg}...")?
+            .tick_chars(style.tick_chars())
+            .template(&format!("{{spinner{}}} {{msg}}...", current_theme().spinner_color()))?
     );
     spinner.enable_steady_tick(std::time::Duration::from_millis(100));
     spinner.set_message(format!("{}...", message));
+    if let Ok(mut active) = ACTIVE_SPINNER.lock() {
+        *active = Some(spinner.clone());
+    }
     Ok(spinner)
 }
 
-pub fn show_selection_menu<T: AsRef<str> + ToString>(prompt: &str, items: &[T], default: usize) -> Result<usize, Box<dyn Error>> {
-    Ok(Select::with_theme(&ColorfulTheme::default())
-        .with_prompt(prompt)
-        .items(items)
-        .default(default)
-        .interact()?)
+/// A progress indicator that spins, counting steps, until `set_progress` reports a known
+/// total; then it shows a bar with an ETA. Hidden in quiet mode.
+pub fn create_progress_bar(message: &str) -> Result<ProgressBar, Box<dyn Error>> {
+    let bar = create_spinner(message)?;
+    let style = SPINNER_STYLE.lock().map(|style| *style).unwrap_or_default();
+    bar.set_style(
+        ProgressStyle::default_spinner()
+            .tick_chars(style.tick_chars())
+            .template(&format!("{{spinner{}}} {{msg}}: {{human_pos}}", current_theme().spinner_color()))?
+    );
+    bar.set_message(message.to_string());
+    Ok(bar)
+}
+
+/// Moves a bar from `create_progress_bar` to `done` of `total`; a `total` of 0 means unknown
+pub fn set_progress(bar: &ProgressBar, done: usize, total: usize) {
+    if total > 0 && bar.length() != Some(total as u64) {
+        bar.set_length(total as u64);
+        if let Ok(style) = ProgressStyle::default_bar().template("{msg} [{bar:30}] {human_pos}/{human_len} (ETA {eta})") {
+            bar.set_style(style.progress_chars("=> "));
+        }
+    }
+    bar.set_position(done as u64);
+}
+
+/// Stops and removes the active spinner, e.g. when an error interrupted its owner
+pub fn clear_spinner() {
+    if let Ok(mut active) = ACTIVE_SPINNER.lock() {
+        if let Some(spinner) = active.take() {
+            spinner.finish_and_clear();
+        }
+    }
+}
+
+/// Runs `print` with the active spinner hidden, so that its lines don't get mixed into the animation
+pub fn suspend_spinner<R>(print: impl FnOnce() -> R) -> R {
+    let spinner = ACTIVE_SPINNER.lock().ok().and_then(|active| active.clone());
+    match spinner {
+        Some(spinner) if !spinner.is_finished() => spinner.suspend(print),
+        _ => print(),
+    }
 }
 
-pub async fn select_mode() -> Result<Mode, Box<dyn Error>> {
-    let modes = [
-        Mode::CommitMessage.description(),
-        Mode::FileAnalysis.description(),
-        Mode::ContributorAnalysis.description(),
-    ];
-    
-    let selection = show_selection_menu("What would you like to do?", &modes, 0)?;
-    
-    Ok(match selection {
-        0 => Mode::CommitMessage,
-        1 => Mode::FileAnalysis,
-        _ => Mode::ContributorAnalysis,
-    })
+/// Updates the message of the active spinner, if one is still running
+pub fn set_spinner_message(message: &str) {
+    if let Ok(active) = ACTIVE_SPINNER.lock() {
+        if let Some(spinner) = active.as_ref().filter(|s| !s.is_finished()) {
+            spinner.set_message(message.to_string());
+        }
+    }
+}
+
+/// Returns the tail of the last line of partially streamed text, short enough for a spinner
+pub fn stream_preview(text: &str) -> String {
+    const MAX_CHARS: usize = 60;
+    let line = text.trim_end().lines().last().unwrap_or("");
+    let chars: Vec<char> = line.chars().collect();
+    if chars.len() > MAX_CHARS {
+        format!("…{}", chars[chars.len() - MAX_CHARS..].iter().collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+/// Key hints of each kind of menu: in a footer below single-choice menus, above the
+/// searchable and checklist ones
+const SELECT_KEYS: &str = "↑↓ move · enter select · q quit";
+const SELECT_WITH_HELP_KEYS: &str = "↑↓ move · enter select · ? help · q quit";
+const FUZZY_SELECT_KEYS: &str = "↑↓ move · type to search · enter select · esc quit";
+const MULTI_SELECT_KEYS: &str = "↑↓ move · space toggle · enter confirm · q quit";
+
+/// Whether menus on `term` show their key hint; quiet mode leaves it out
+fn shows_key_hint(term: &Term) -> bool {
+    !is_quiet() && term.is_term()
+}
+
+/// Runs `menu` with the `keys` hint shown above it while it is open, see `menu_choice`
+fn with_key_hint<T>(keys: &str, menu: impl FnOnce(&Term) -> dialoguer::Result<Option<T>>) -> Result<T, Box<dyn Error>> {
+    let term = Term::stderr();
+    let show_hint = shows_key_hint(&term);
+    if show_hint {
+        term.write_line(&style(keys).dim().to_string())?;
+    }
+    let result = menu(&term);
+    if show_hint {
+        term.clear_last_lines(1)?;
+    }
+    menu_choice(result)
+}
+
+/// What was picked in a menu. `None` means the user quit, which ends noah right away.
+fn menu_choice<T>(result: dialoguer::Result<Option<T>>) -> Result<T, Box<dyn Error>> {
+    match result {
+        Ok(Some(value)) => Ok(value),
+        Ok(None) => crate::shutdown::exit(0),
+        // Menus read Ctrl-C as a key press in raw mode, so the signal handler never sees it
+        Err(dialoguer::Error::IO(e)) if e.kind() == std::io::ErrorKind::Interrupted => crate::shutdown::interrupted(),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// A single-choice menu drawn like dialoguer's `Select`, with the key hint in a footer
+/// below the items. `?` calls `help`, if there is one, and shows the menu again below
+/// what it printed. Returns `None` when the user quit with q or Esc.
+fn select_on(term: &Term, theme: &ColorfulTheme, prompt: &str, items: &[&str], default: usize, help: Option<&dyn Fn()>) -> std::io::Result<Option<usize>> {
+    use dialoguer::console::Key;
+
+    if !term.is_term() {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "not a terminal"));
+    }
+    if items.is_empty() {
+        return Err(std::io::Error::other("Empty list of items given to the menu"));
+    }
+    let footer = shows_key_hint(term).then(|| style(if help.is_some() { SELECT_WITH_HELP_KEYS } else { SELECT_KEYS }).dim().to_string());
+    let mut selected = default.min(items.len() - 1);
+    let mut first = 0;
+    term.hide_cursor()?;
+    loop {
+        // The prompt and the footer stay on screen, the items scroll between them
+        let rows = (term.size().0 as usize).saturating_sub(3).max(1);
+        first = first.min(selected).max((selected + 1).saturating_sub(rows));
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        theme.format_select_prompt(&mut line, prompt).map_err(std::io::Error::other)?;
+        lines.push(line);
+        for (idx, item) in items.iter().enumerate().skip(first).take(rows) {
+            let mut line = String::new();
+            theme.format_select_prompt_item(&mut line, item, idx == selected).map_err(std::io::Error::other)?;
+            lines.push(line);
+        }
+        lines.extend(footer.clone());
+        for line in &lines {
+            term.write_line(line)?;
+        }
+        term.flush()?;
+
+        let key = term.read_key();
+        term.clear_last_lines(lines.len())?;
+        match key {
+            Ok(Key::ArrowDown | Key::Tab | Key::Char('j')) => selected = (selected + 1) % items.len(),
+            Ok(Key::ArrowUp | Key::BackTab | Key::Char('k')) => selected = (selected + items.len() - 1) % items.len(),
+            Ok(Key::Home) => selected = 0,
+            Ok(Key::End) => selected = items.len() - 1,
+            Ok(Key::Enter | Key::Char(' ')) => {
+                term.show_cursor()?;
+                return Ok(Some(selected));
+            }
+            Ok(Key::Escape | Key::Char('q')) => {
+                term.show_cursor()?;
+                return Ok(None);
+            }
+            Ok(Key::Char('?')) => {
+                if let Some(help) = help {
+                    term.show_cursor()?;
+                    help();
+                    term.hide_cursor()?;
+                }
+            }
+            Ok(_) => (),
+            Err(e) => {
+                term.show_cursor()?;
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Prints the line a menu leaves behind once closed, like dialoguer does itself. Menus
+/// are run without it so that the key hint above them can be removed first.
+fn report_selection(render: impl FnOnce(&mut String) -> std::fmt::Result) -> Result<(), Box<dyn Error>> {
+    let mut line = String::new();
+    render(&mut line)?;
+    Term::stderr().write_line(&line)?;
+    Ok(())
+}
+
+pub fn show_selection_menu<T: AsRef<str>>(prompt: &str, items: &[T], default: usize) -> Result<usize, Box<dyn Error>> {
+    select_with_help(prompt, items, default, None)
+}
+
+/// `show_selection_menu` with the footer, see `select_on`, and `?` printing `help`
+fn select_with_help<T: AsRef<str>>(prompt: &str, items: &[T], default: usize, help: Option<&dyn Fn()>) -> Result<usize, Box<dyn Error>> {
+    let theme = ColorfulTheme::default();
+    let labels: Vec<&str> = items.iter().map(AsRef::as_ref).collect();
+    let selection = menu_choice(select_on(&Term::stderr(), &theme, prompt, &labels, default, help).map_err(dialoguer::Error::from))?;
+    report_selection(|line| theme.format_select_prompt_selection(line, prompt, labels[selection]))?;
+    Ok(selection)
+}
+
+/// Items `show_paged_select` shows at a time, fewer if the terminal is too short
+const PAGE_SIZE: usize = 20;
+
+/// What was picked in `show_paged_select`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PagedSelection {
+    /// Index of the chosen item in `items`
+    Item(usize),
+    /// Index of the chosen entry in `actions`
+    Action(usize),
+}
+
+/// `show_selection_menu` for long lists: `items` are shown 20 at a time, or as many as
+/// fit on screen, with entries for the previous and next page and `actions` below them on
+/// every page. `page` is the page to start on and is left at the one the choice was made on.
+pub fn show_paged_select<T: AsRef<str>>(prompt: &str, items: &[T], actions: &[&str], page: &mut usize) -> Result<PagedSelection, Box<dyn Error>> {
+    const PREVIOUS: &str = "⬅️ Previous page";
+    const NEXT: &str = "➡️ Next page";
+
+    // Room for the prompt, the key hint and the paging entries, so that the actions stay visible
+    let (_, height) = termimad::terminal_size();
+    let page_size = PAGE_SIZE.min((height as usize).saturating_sub(actions.len() + 5)).max(5);
+    let pages = items.len().div_ceil(page_size).max(1);
+    // After paging the cursor stays on the paging entry, so paging on is a matter of pressing enter
+    let mut cursor = None;
+    loop {
+        *page = (*page).min(pages - 1);
+        let start = *page * page_size;
+        let shown = &items[start..items.len().min(start + page_size)];
+
+        let mut entries: Vec<&str> = shown.iter().map(AsRef::as_ref).collect();
+        let previous = (*page > 0).then(|| {
+            entries.push(PREVIOUS);
+            entries.len() - 1
+        });
+        let next = (*page + 1 < pages).then(|| {
+            entries.push(NEXT);
+            entries.len() - 1
+        });
+        let first_action = entries.len();
+        entries.extend_from_slice(actions);
+        let title = match pages {
+            1 => prompt.to_string(),
+            _ => format!("{} (page {} of {})", prompt, *page + 1, pages),
+        };
+
+        let default = cursor.and_then(|label| entries.iter().rposition(|entry| *entry == label)).unwrap_or(0);
+        let selection = show_selection_menu(&title, &entries, default)?;
+        if selection < shown.len() {
+            return Ok(PagedSelection::Item(start + selection));
+        } else if Some(selection) == previous {
+            *page -= 1;
+            cursor = Some(PREVIOUS);
+        } else if Some(selection) == next {
+            *page += 1;
+            cursor = Some(NEXT);
+        } else {
+            return Ok(PagedSelection::Action(selection - first_action));
+        }
+    }
+}
+
+/// Like `show_selection_menu`, but typing filters the items by fuzzy match.
+/// Returns the index of the chosen item in `items`.
+pub fn show_fuzzy_select<T: AsRef<str> + ToString>(prompt: &str, items: &[T], default: usize) -> Result<usize, Box<dyn Error>> {
+    let theme = ColorfulTheme::default();
+    let selection = with_key_hint(FUZZY_SELECT_KEYS, |term| {
+        FuzzySelect::with_theme(&theme)
+            .with_prompt(prompt)
+            .items(items)
+            .default(default)
+            .report(false)
+            .interact_on_opt(term)
+    })?;
+    report_selection(|line| theme.format_select_prompt_selection(line, prompt, items[selection].as_ref()))?;
+    Ok(selection)
+}
+
+/// Prints commit activity as a grid of weekdays by hour, darker blocks meaning more commits.
+/// `times` are local wall-clock times as seconds, like `ContributorStats::commit_times`.
+pub fn print_heatmap(times: &[i64]) {
+    use chrono::{Datelike, Timelike};
+
+    const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+    const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+    let mut counts = [[0usize; 24]; 7];
+    for time in times.iter().filter_map(|&time| chrono::DateTime::from_timestamp(time, 0)) {
+        counts[time.weekday().num_days_from_monday() as usize][time.hour() as usize] += 1;
+    }
+    let max = counts.iter().flatten().copied().max().unwrap_or(0);
+    if max == 0 {
+        println!("  No commits to show");
+        return;
+    }
+
+    println!("      0     6     12    18");
+    for (day, row) in DAYS.iter().zip(counts) {
+        // Any activity gets at least the lightest block, the busiest hour the darkest
+        let cells: String = row.iter().map(|&count| SHADES[(count * 4).div_ceil(max)]).collect();
+        println!("  {} {}", day, cells);
+    }
+    println!("  busiest hour: {} commits", max);
+}
+
+/// A Unix time as its UTC date, e.g. 2024-03-07
+pub fn format_date(time: i64) -> String {
+    chrono::DateTime::from_timestamp(time, 0)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+/// How long before `now` a Unix time was, e.g. "3 days ago" or "yesterday"
+pub fn format_time_ago(time: i64, now: i64) -> String {
+    let days = (now - time).max(0) / 86_400;
+    match days {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        2..=13 => format!("{} days ago", days),
+        14..=59 => format!("{} weeks ago", days / 7),
+        60..=729 => format!("{} months ago", days * 12 / 365),
+        _ => format!("{} years ago", days / 365),
+    }
+}
+
+/// The length of a stretch of time in whole months, e.g. "1 month" or "less than a month"
+pub fn format_months(seconds: i64) -> String {
+    match seconds.max(0) * 12 / (365 * 86_400) {
+        0 => "less than a month".to_string(),
+        1 => "1 month".to_string(),
+        months => format!("{} months", months),
+    }
+}
+
+/// A file size for people, e.g. "512 bytes", "340 KB" or "1.2 MB"
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..1_000 => format!("{} bytes", bytes),
+        1_000..1_000_000 => format!("{} KB", bytes / 1_000),
+        _ => format!("{:.1} MB", bytes as f64 / 1_000_000.0),
+    }
+}
+
+/// Formats a number with thousands separators, e.g. 1,240
+pub fn format_number(n: usize) -> String {
+    let digits = n.to_string();
+    let mut formatted = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(c);
+    }
+    formatted
+}
+
+/// Formats a dollar amount with enough precision to show fractions of a cent
+pub fn format_cost(cost: f64) -> String {
+    if cost < 1.0 {
+        format!("{:.3}", cost)
+    } else {
+        format!("{:.2}", cost)
+    }
+}
+
+/// Shows a checklist and returns the indices of the ticked items
+pub fn show_multi_select_menu<T: AsRef<str> + ToString>(prompt: &str, items: &[T], defaults: &[bool]) -> Result<Vec<usize>, Box<dyn Error>> {
+    let theme = ColorfulTheme::default();
+    let selection = with_key_hint(MULTI_SELECT_KEYS, |term| {
+        MultiSelect::with_theme(&theme)
+            .with_prompt(prompt)
+            .items(items)
+            .defaults(defaults)
+            .report(false)
+            .interact_on_opt(term)
+    })?;
+    let selected: Vec<&str> = selection.iter().map(|&idx| items[idx].as_ref()).collect();
+    report_selection(|line| theme.format_multi_select_prompt_selection(line, prompt, &selected))?;
+    Ok(selection)
+}
+
+/// Shows the main menu, starting on `preselected` when it is one of `modes`. `?` prints
+/// a help screen describing each of them.
+pub async fn select_mode(modes: Vec<Mode>, preselected: Option<Mode>) -> Result<Mode, Box<dyn Error>> {
+    let items: Vec<&str> = modes.iter().map(|m| m.description()).collect();
+    let default = preselected.and_then(|mode| modes.iter().position(|&m| m == mode)).unwrap_or(0);
+    let selection = select_with_help("What would you like to do?", &items, default, Some(&|| print_help(&modes)))?;
+    Ok(modes[selection])
+}
+
+/// Describes each of `modes` and how to use the menus
+fn print_help(modes: &[Mode]) {
+    print_section("❓ Help");
+    for mode in modes {
+        println!("{}\n  {}\n", mode.description(), mode.help());
+    }
+
+    print_subsection("⌨️ Menus");
+    for keys in [SELECT_WITH_HELP_KEYS, FUZZY_SELECT_KEYS, MULTI_SELECT_KEYS] {
+        println!("  {}", keys);
+    }
+    println!("\n  Quitting a menu ends noah without committing anything.\n");
 }
 
 pub fn get_repository_path(default: &str) -> Result<String, Box<dyn Error>> {
@@ -77,4 +821,111 @@ pub fn get_repository_path(default: &str) -> Result<String, Box<dyn Error>> {
         .allow_empty(true)
         .interact()?;
     Ok(if path.is_empty() { ".".to_string() } else { path })
-}
\ No newline at end of file
+}
+
+pub fn get_text_input(prompt: &str) -> Result<String, Box<dyn Error>> {
+    Ok(Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .allow_empty(true)
+        .interact_text()?)
+}
+
+/// Reads a secret such as an API key without echoing it
+pub fn get_password(prompt: &str) -> Result<String, Box<dyn Error>> {
+    Ok(Password::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .interact()?)
+}
+
+pub fn get_text_with_default(prompt: &str, default: &str) -> Result<String, Box<dyn Error>> {
+    Ok(Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(default.to_string())
+        .interact_text()?)
+}
+
+pub fn confirm(prompt: &str) -> Result<bool, Box<dyn Error>> {
+    Ok(Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(false)
+        .interact()?)
+}
+
+/// Opens `text` in `$EDITOR` (vi, or notepad on Windows, if unset) and returns the saved result.
+/// Lines starting with `#` are dropped like git does; `None` means the message was emptied.
+pub fn edit_in_editor(text: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let edited = open_in_editor(
+        "noah-commit",
+        &format!("{}\n\n# Lines starting with '#' will be ignored. An empty message cancels.\n", text),
+    )?;
+    let message = edited
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let message = message.trim();
+    Ok(if message.is_empty() { None } else { Some(message.to_string()) })
+}
+
+/// Opens an empty file headed by `instruction` in `$EDITOR` for pasting text such as an error
+/// log. Only the instruction line is dropped, since pasted lines may well start with `#`;
+/// `None` means nothing was pasted.
+pub fn paste_in_editor(instruction: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let edited = open_in_editor("noah-paste", &format!("{}\n", instruction))?;
+    let pasted = match edited.split_once('\n') {
+        Some((first, rest)) if first.trim_end() == instruction => rest,
+        _ if edited.trim_end() == instruction => "",
+        _ => edited.as_str(),
+    };
+    let pasted = pasted.trim();
+    Ok(if pasted.is_empty() { None } else { Some(pasted.to_string()) })
+}
+
+/// Writes `contents` to a new temporary file named after `prefix`, waits for `$EDITOR` to
+/// close it and returns what was saved. The file gets a random name and is created fresh,
+/// so nobody can plant a file or symlink there beforehand.
+fn open_in_editor(prefix: &str, contents: &str) -> Result<String, Box<dyn Error>> {
+    use std::io::Write;
+
+    let mut file = tempfile::Builder::new().prefix(&format!("{}-", prefix)).suffix(".txt").tempfile()?;
+    file.write_all(contents.as_bytes())?;
+    // Closed so that editors on Windows can open it; the file is removed when `path` drops
+    let path = file.into_temp_path();
+
+    let editor = std::env::var("EDITOR")
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+    // Allow editors configured with arguments, e.g. "code --wait"
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let status = std::process::Command::new(program).args(parts).arg(&path).status();
+
+    let edited = std::fs::read_to_string(&path);
+    let status = status.map_err(|e| format!("Failed to start editor {}: {}", program, e))?;
+    if !status.success() {
+        return Err(format!("Editor {} exited with {}", program, status).into());
+    }
+    Ok(edited?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_gets_utf16_with_a_byte_order_mark() {
+        assert_eq!(clipboard_input("clip", "é✓"), [0xFF, 0xFE, 0xE9, 0x00, 0x13, 0x27]);
+        assert_eq!(clipboard_input("pbcopy", "é✓"), "é✓".as_bytes());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn the_editor_is_given_the_contents_and_its_exit_status_counts() {
+        // The only test touching EDITOR, so it can't race with another
+        std::env::set_var("EDITOR", "true");
+        assert_eq!(open_in_editor("noah-test", "draft\n").unwrap(), "draft\n");
+        std::env::set_var("EDITOR", "false");
+        assert!(open_in_editor("noah-test", "draft\n").unwrap_err().to_string().starts_with("Editor false exited with"));
+    }
+}